@@ -0,0 +1,129 @@
+use std::{
+    env::args,
+    panic::{AssertUnwindSafe, catch_unwind, set_hook},
+    process::exit,
+};
+
+use bril_rs::{Program, output_program};
+use fuzz::generate::{GenConfig, generate_program};
+use interp::diff::observe;
+use task3::{
+    dce::{global_dce_pass, locally_killed_pass},
+    flatten, get_basic_blocks,
+    lvn::lvn,
+    purity::analyze_purity,
+};
+use task6::{
+    cfg::form_cfg,
+    dom::{dom_frontier, find_dominators, form_dom_tree, rev_graph},
+    ssa::{get_defs, place_phi_nodes},
+};
+
+fn flag_value(flag: &str) -> Option<u64> {
+    args()
+        .skip_while(|arg| arg != flag)
+        .nth(1)
+        .map(|value| value.parse().expect("flag value should be a number"))
+}
+
+fn run_lvn(program: &mut Program, constant_folding: bool) {
+    let purity = analyze_purity(program);
+    for function in program.functions.iter_mut() {
+        let mut blocks = get_basic_blocks(function);
+        for block in blocks.iter_mut() {
+            lvn(block, constant_folding, &purity);
+        }
+        function.instrs = flatten(blocks);
+    }
+}
+
+// the same fixpoint the dce binary runs
+fn run_dce(program: &mut Program) {
+    let purity = analyze_purity(program);
+    let mut changing = true;
+    while changing {
+        changing = false;
+        for function in program.functions.iter_mut() {
+            changing |= global_dce_pass(function, &purity);
+            changing |= locally_killed_pass(function, &purity);
+        }
+    }
+}
+
+// only checks that dominators and phi placement don't crash, since they don't change the program
+fn run_ssa(program: &mut Program) {
+    for function in program.functions.iter() {
+        let blocks = task6::cfg::get_basic_blocks(function);
+        let succ = form_cfg(&blocks);
+        let pred = rev_graph(&succ);
+        let dom = find_dominators(&pred, &succ);
+        form_dom_tree(&dom);
+        let df = dom_frontier(&dom, &pred);
+        place_phi_nodes(&blocks, &get_defs(&blocks), &df, &pred, &succ);
+    }
+}
+
+type Pass = (&'static str, fn(&mut Program));
+
+const PASSES: [Pass; 4] = [
+    ("lvn", |program| run_lvn(program, false)),
+    ("lvn -f", |program| run_lvn(program, true)),
+    ("dce", run_dce),
+    ("dom/ssa", run_ssa),
+];
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+// runs each pass on generated programs and reports the ones that crash or change what the
+// program prints
+// `-s N` is the first seed, `-n N` how many programs to try, and `-g` prints the program for the
+// first seed as JSON instead
+fn main() {
+    let first = flag_value("-s").unwrap_or(0);
+    let count = flag_value("-n").unwrap_or(1000);
+    let config = GenConfig::default();
+
+    if args().any(|arg| arg == "-g") {
+        output_program(&generate_program(first, &config));
+        return;
+    }
+
+    // panics are reported with their seed instead
+    set_hook(Box::new(|_| {}));
+
+    let mut failures = 0;
+    for seed in first..first + count {
+        let program = generate_program(seed, &config);
+        let expected = observe(&program, &[], None);
+        for (name, pass) in PASSES.iter() {
+            let mut after = program.clone();
+            let result = catch_unwind(AssertUnwindSafe(|| pass(&mut after)));
+            let problem = match result {
+                Err(payload) => format!("panicked: {}", panic_message(payload.as_ref())),
+                Ok(()) => {
+                    let actual = observe(&after, &[], None);
+                    if actual.same_behavior(&expected) {
+                        continue;
+                    }
+                    format!("changed the behavior\n  expected:\n{expected}\n  actual:\n{actual}")
+                }
+            };
+            failures += 1;
+            println!("seed {seed}: `{name}` {problem}");
+            print!("{program}");
+        }
+    }
+
+    println!("{count} programs, {failures} failures");
+    if failures > 0 {
+        exit(1);
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::HashSet;
+
+use bril_rs::{
+    Argument, Code, ConstOps, EffectOps, Function, Instruction, Literal, Program, Type, ValueOps,
+};
+
+use crate::rng::Rng;
+
+#[derive(Debug, Clone)]
+pub struct GenConfig {
+    // functions besides main, which only call the ones defined before them
+    pub helpers: usize,
+    // how deep branches and loops nest
+    pub max_depth: usize,
+    pub max_stmts: usize,
+    // highest trip count of a generated loop
+    pub max_trips: i64,
+    pub memory: bool,
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        GenConfig {
+            helpers: 2,
+            max_depth: 3,
+            max_stmts: 6,
+            max_trips: 4,
+            memory: true,
+        }
+    }
+}
+
+// a helper's name and argument types, every helper returns an int
+type Signature = (String, Vec<Type>);
+
+struct FunctionBuilder<'a> {
+    rng: &'a mut Rng,
+    config: &'a GenConfig,
+    callees: &'a [Signature],
+    instrs: Vec<Code>,
+    // variables defined on every path to the current point
+    vars: Vec<(String, Type)>,
+    // loop counters and bounds, which are never redefined so loops always end
+    reserved: HashSet<String>,
+    next_var: usize,
+    next_label: usize,
+}
+
+impl<'a> FunctionBuilder<'a> {
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.next_var += 1;
+        format!("{prefix}{}", self.next_var - 1)
+    }
+
+    fn fresh_label(&mut self, prefix: &str) -> String {
+        self.next_label += 1;
+        format!("{prefix}.{}", self.next_label - 1)
+    }
+
+    fn candidates(&self, tipe: &Type) -> Vec<String> {
+        self.vars
+            .iter()
+            .filter(|(_, var_type)| var_type == tipe)
+            .map(|(var, _)| var.clone())
+            .collect()
+    }
+
+    // an existing variable of the type, or a new constant if there's none
+    fn operand(&mut self, tipe: &Type) -> String {
+        let candidates = self.candidates(tipe);
+        if candidates.is_empty() || self.rng.chance(10) {
+            let dest = self.dest(tipe);
+            let value = self.literal(tipe);
+            self.constant(&dest, tipe, value);
+            dest
+        } else {
+            self.rng.choose(&candidates).clone()
+        }
+    }
+
+    // usually a new variable, sometimes one that's redefined in place
+    fn dest(&mut self, tipe: &Type) -> String {
+        let candidates: Vec<String> = self
+            .candidates(tipe)
+            .into_iter()
+            .filter(|var| !self.reserved.contains(var))
+            .collect();
+        if !candidates.is_empty() && self.rng.chance(30) {
+            return self.rng.choose(&candidates).clone();
+        }
+        let var = self.fresh("v");
+        self.vars.push((var.clone(), tipe.clone()));
+        var
+    }
+
+    fn literal(&mut self, tipe: &Type) -> Literal {
+        match tipe {
+            Type::Bool => Literal::Bool(self.rng.chance(50)),
+            _ => Literal::Int(self.rng.range(-10, 100)),
+        }
+    }
+
+    fn constant(&mut self, dest: &str, tipe: &Type, value: Literal) {
+        self.instrs.push(Code::Instruction(Instruction::Constant {
+            dest: dest.to_string(),
+            op: ConstOps::Const,
+            pos: None,
+            const_type: tipe.clone(),
+            value,
+        }));
+    }
+
+    fn value(
+        &mut self,
+        dest: &str,
+        tipe: &Type,
+        op: ValueOps,
+        args: Vec<String>,
+        funcs: Vec<String>,
+    ) {
+        self.instrs.push(Code::Instruction(Instruction::Value {
+            args,
+            dest: dest.to_string(),
+            funcs,
+            labels: vec![],
+            op,
+            pos: None,
+            op_type: tipe.clone(),
+        }));
+    }
+
+    fn effect(&mut self, op: EffectOps, args: Vec<String>, labels: Vec<String>) {
+        self.instrs.push(Code::Instruction(Instruction::Effect {
+            args,
+            funcs: vec![],
+            labels,
+            op,
+            pos: None,
+        }));
+    }
+
+    fn label(&mut self, label: &str) {
+        self.instrs.push(Code::Label {
+            label: label.to_string(),
+            pos: None,
+        });
+    }
+
+    fn arithmetic(&mut self) {
+        let op = *self
+            .rng
+            .choose(&[ValueOps::Add, ValueOps::Sub, ValueOps::Mul, ValueOps::Div]);
+        let a = self.operand(&Type::Int);
+        let b = if op == ValueOps::Div {
+            // a fresh nonzero divisor, so division never fails
+            let b = self.fresh("d");
+            let mut divisor = self.rng.range(-5, 4);
+            if divisor >= 0 {
+                divisor += 1;
+            }
+            self.constant(&b, &Type::Int, Literal::Int(divisor));
+            b
+        } else {
+            self.operand(&Type::Int)
+        };
+        let dest = self.dest(&Type::Int);
+        self.value(&dest, &Type::Int, op, vec![a, b], vec![]);
+    }
+
+    fn comparison(&mut self) {
+        let op = *self.rng.choose(&[
+            ValueOps::Eq,
+            ValueOps::Lt,
+            ValueOps::Gt,
+            ValueOps::Le,
+            ValueOps::Ge,
+        ]);
+        let a = self.operand(&Type::Int);
+        let b = self.operand(&Type::Int);
+        let dest = self.dest(&Type::Bool);
+        self.value(&dest, &Type::Bool, op, vec![a, b], vec![]);
+    }
+
+    fn logic(&mut self) {
+        let op = *self
+            .rng
+            .choose(&[ValueOps::And, ValueOps::Or, ValueOps::Not]);
+        let mut args = vec![self.operand(&Type::Bool)];
+        if op != ValueOps::Not {
+            args.push(self.operand(&Type::Bool));
+        }
+        let dest = self.dest(&Type::Bool);
+        self.value(&dest, &Type::Bool, op, args, vec![]);
+    }
+
+    fn copy(&mut self) {
+        let tipe = if self.rng.chance(70) {
+            Type::Int
+        } else {
+            Type::Bool
+        };
+        let src = self.operand(&tipe);
+        let dest = self.dest(&tipe);
+        self.value(&dest, &tipe, ValueOps::Id, vec![src], vec![]);
+    }
+
+    fn print(&mut self) {
+        let count = self.rng.range(1, 2);
+        let args = (0..count)
+            .map(|_| {
+                let tipe = if self.rng.chance(70) {
+                    Type::Int
+                } else {
+                    Type::Bool
+                };
+                self.operand(&tipe)
+            })
+            .collect();
+        self.effect(EffectOps::Print, args, vec![]);
+    }
+
+    fn call(&mut self) {
+        let (callee, arg_types) = self.rng.choose(self.callees).clone();
+        let args = arg_types.iter().map(|tipe| self.operand(tipe)).collect();
+        let dest = self.dest(&Type::Int);
+        self.value(&dest, &Type::Int, ValueOps::Call, args, vec![callee]);
+    }
+
+    // fills a fresh allocation, reads one cell back and frees it, all with in-bounds indices
+    fn memory(&mut self) {
+        let size = self.rng.range(1, 4);
+        let count = self.fresh("n");
+        self.constant(&count, &Type::Int, Literal::Int(size));
+        let ptr_type = Type::Pointer(Box::new(Type::Int));
+        let base = self.fresh("p");
+        self.value(&base, &ptr_type, ValueOps::Alloc, vec![count], vec![]);
+
+        let cell = |builder: &mut FunctionBuilder, offset: i64| {
+            let index = builder.fresh("k");
+            builder.constant(&index, &Type::Int, Literal::Int(offset));
+            let ptr = builder.fresh("q");
+            builder.value(
+                &ptr,
+                &ptr_type,
+                ValueOps::PtrAdd,
+                vec![base.clone(), index],
+                vec![],
+            );
+            ptr
+        };
+        for offset in 0..size {
+            let ptr = cell(self, offset);
+            let value = self.operand(&Type::Int);
+            self.effect(EffectOps::Store, vec![ptr, value], vec![]);
+        }
+        let offset = self.rng.range(0, size - 1);
+        let ptr = cell(self, offset);
+        let dest = self.dest(&Type::Int);
+        self.value(&dest, &Type::Int, ValueOps::Load, vec![ptr], vec![]);
+        self.effect(EffectOps::Free, vec![base.clone()], vec![]);
+    }
+
+    fn branch(&mut self, depth: usize) {
+        let cond = self.operand(&Type::Bool);
+        let then_label = self.fresh_label("then");
+        let else_label = self.fresh_label("else");
+        let join_label = self.fresh_label("join");
+        self.effect(
+            EffectOps::Branch,
+            vec![cond],
+            vec![then_label.clone(), else_label.clone()],
+        );
+
+        // only what's defined before the branch is defined after it
+        let before = self.vars.clone();
+        self.label(&then_label);
+        self.block(depth + 1);
+        self.effect(EffectOps::Jump, vec![], vec![join_label.clone()]);
+        self.vars = before.clone();
+        self.label(&else_label);
+        self.block(depth + 1);
+        self.effect(EffectOps::Jump, vec![], vec![join_label.clone()]);
+        self.vars = before;
+        self.label(&join_label);
+    }
+
+    // counts a reserved counter up to a constant bound
+    fn for_loop(&mut self, depth: usize) {
+        let counter = self.fresh("i");
+        let bound = self.fresh("b");
+        let step = self.fresh("s");
+        let trips = self.rng.range(0, self.config.max_trips);
+        self.constant(&counter, &Type::Int, Literal::Int(0));
+        self.constant(&bound, &Type::Int, Literal::Int(trips));
+        self.constant(&step, &Type::Int, Literal::Int(1));
+        for var in [&counter, &bound, &step] {
+            self.reserved.insert(var.clone());
+            self.vars.push((var.clone(), Type::Int));
+        }
+
+        let head = self.fresh_label("loop");
+        let body = self.fresh_label("body");
+        let exit = self.fresh_label("exit");
+        self.label(&head);
+        let cond = self.fresh("c");
+        self.value(
+            &cond,
+            &Type::Bool,
+            ValueOps::Lt,
+            vec![counter.clone(), bound],
+            vec![],
+        );
+        self.effect(
+            EffectOps::Branch,
+            vec![cond.clone()],
+            vec![body.clone(), exit.clone()],
+        );
+
+        let before = self.vars.clone();
+        self.label(&body);
+        self.block(depth + 1);
+        self.value(
+            &counter,
+            &Type::Int,
+            ValueOps::Add,
+            vec![counter.clone(), step],
+            vec![],
+        );
+        self.effect(EffectOps::Jump, vec![], vec![head]);
+        self.vars = before;
+        self.label(&exit);
+    }
+
+    fn block(&mut self, depth: usize) {
+        let count = self.rng.range(1, self.config.max_stmts as i64);
+        for _ in 0..count {
+            let nested = depth < self.config.max_depth;
+            match self.rng.below(10) {
+                0 if nested => self.branch(depth),
+                1 if nested => self.for_loop(depth),
+                2 if !self.callees.is_empty() => self.call(),
+                3 if self.config.memory => self.memory(),
+                4 => self.comparison(),
+                5 => self.logic(),
+                6 => self.copy(),
+                7 => self.print(),
+                _ => self.arithmetic(),
+            }
+        }
+    }
+}
+
+fn build_function(
+    rng: &mut Rng,
+    config: &GenConfig,
+    callees: &[Signature],
+    name: &str,
+    args: Vec<Argument>,
+    return_type: Option<Type>,
+) -> Function {
+    let mut builder = FunctionBuilder {
+        rng,
+        config,
+        callees,
+        instrs: vec![],
+        vars: args
+            .iter()
+            .map(|arg| (arg.name.clone(), arg.arg_type.clone()))
+            .collect(),
+        reserved: HashSet::new(),
+        next_var: 0,
+        next_label: 0,
+    };
+    builder.block(0);
+    match return_type {
+        Some(ref tipe) => {
+            let result = builder.operand(tipe);
+            builder.effect(EffectOps::Return, vec![result], vec![]);
+        }
+        None => builder.print(),
+    }
+    Function {
+        args,
+        instrs: builder.instrs,
+        name: name.to_string(),
+        pos: None,
+        return_type,
+    }
+}
+
+// a random program that type checks, terminates, frees everything it allocates and never divides
+// by zero, with a main that takes no arguments
+pub fn generate_program(seed: u64, config: &GenConfig) -> Program {
+    let mut rng = Rng::new(seed);
+    let mut functions = vec![];
+    let mut signatures: Vec<Signature> = vec![];
+    for i in 0..config.helpers {
+        let name = format!("f{i}");
+        let arg_count = rng.below(3);
+        let args: Vec<Argument> = (0..arg_count)
+            .map(|j| Argument {
+                name: format!("a{j}"),
+                arg_type: if rng.chance(70) {
+                    Type::Int
+                } else {
+                    Type::Bool
+                },
+            })
+            .collect();
+        functions.push(build_function(
+            &mut rng,
+            config,
+            &signatures,
+            &name,
+            args.clone(),
+            Some(Type::Int),
+        ));
+        signatures.push((name, args.into_iter().map(|arg| arg.arg_type).collect()));
+    }
+    functions.push(build_function(
+        &mut rng,
+        config,
+        &signatures,
+        "main",
+        vec![],
+        None,
+    ));
+    Program {
+        functions,
+        imports: vec![],
+    }
+}
@@ -0,0 +1,20 @@
+use fuzz::generate::{GenConfig, generate_program};
+use interp::interp::run_program_with_limit;
+
+#[test]
+fn same_seed_same_program() {
+    let config = GenConfig::default();
+    assert_eq!(generate_program(7, &config), generate_program(7, &config));
+    assert_ne!(generate_program(7, &config), generate_program(8, &config));
+}
+
+// every generated program should run to completion without errors or leaks
+#[test]
+fn programs_are_well_formed() {
+    let config = GenConfig::default();
+    for seed in 0..500 {
+        let program = generate_program(seed, &config);
+        let result = run_program_with_limit(&program, &[], std::io::sink(), Some(10_000_000));
+        assert!(result.is_ok(), "seed {seed}: {result:?}\n{program}");
+    }
+}
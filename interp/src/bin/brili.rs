@@ -0,0 +1,30 @@
+use std::{
+    env::args,
+    io::{BufWriter, Write, stdout},
+    process::exit,
+};
+
+use bril_rs::load_program;
+use interp::interp::run_program;
+
+// runs a program like brili, with `-p` printing the dynamic instruction count
+fn main() {
+    let program = load_program();
+    let profile = args().any(|arg| arg == "-p");
+    let program_args: Vec<String> = args().skip(1).filter(|arg| arg != "-p").collect();
+
+    let mut out = BufWriter::new(stdout().lock());
+    let result = run_program(&program, &program_args, &mut out);
+    out.flush().expect("stdout should be writable");
+    match result {
+        Ok(stats) => {
+            if profile {
+                eprintln!("total_dyn_inst: {}", stats.total_dyn_inst);
+            }
+        }
+        Err(error) => {
+            eprintln!("error: {error}");
+            exit(error.exit_code());
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    env::args,
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use bril_rs::Program;
+use interp::{
+    diff::{Pass, observe},
+    error::InterpError,
+    parse::parse_program,
+};
+
+const FLAGS_WITH_VALUES: [&str; 3] = ["-p", "-b", "-l"];
+
+fn flag_value(flag: &str) -> Option<String> {
+    args().skip_while(|arg| arg != flag).nth(1)
+}
+
+// every argument that isn't a flag or a flag's value
+fn get_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        if FLAGS_WITH_VALUES.contains(&arg.as_str()) {
+            args.next();
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    paths
+}
+
+// the programs under each path, with directories searched one level deep
+fn get_programs(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut programs = vec![];
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)
+                .expect("directory should be readable")
+                .map(|entry| entry.expect("directory entry should be readable").path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|ext| ext == "bril" || ext == "json")
+                })
+                .collect();
+            entries.sort();
+            programs.extend(entries);
+        } else {
+            programs.push(path.clone());
+        }
+    }
+    programs
+}
+
+// the program and the args from its `# ARGS:` line
+fn load(path: &Path) -> Result<(Program, Vec<String>), String> {
+    let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let program = serde_json::from_str(&text).map_err(|error| error.to_string())?;
+        return Ok((program, vec![]));
+    }
+    let program = parse_program(&text).map_err(|error| error.to_string())?;
+    let args = text
+        .lines()
+        .find_map(|line| line.strip_prefix("# ARGS:"))
+        .map(|args| args.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+    Ok((program, args))
+}
+
+fn dump(title: &str, program: &Program) {
+    println!("--- {title}");
+    print!("{program}");
+}
+
+// runs every program before and after each pass of the pipeline and reports the first pass that
+// changes what it prints or how it exits
+// `-p` sets the pipeline (default `lvn,lvn -f,dce`), `-b` the directory the passes are in
+// (default ../task3/target/release), `-l` the instruction limit for each run
+fn main() {
+    let bin_dir = PathBuf::from(flag_value("-b").unwrap_or("../task3/target/release".to_string()));
+    let pipeline: Vec<Pass> = flag_value("-p")
+        .unwrap_or("lvn,lvn -f,dce".to_string())
+        .split(',')
+        .map(|step| Pass::parse(step, &bin_dir))
+        .collect();
+    let limit = flag_value("-l")
+        .map(|value| value.parse().expect("flag value should be a number"))
+        .unwrap_or(100_000_000);
+
+    let programs = get_programs(&get_paths());
+    let mut failures = 0;
+    let (mut total_before, mut total_after) = (0, 0);
+    for path in programs.iter() {
+        let name = path.display();
+        let (program, args) = match load(path) {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                println!("skip {name}: {error}");
+                continue;
+            }
+        };
+        let expected = observe(&program, &args, Some(limit));
+        if expected.error == Some(InterpError::OutOfFuel) {
+            println!("skip {name}: instruction limit exceeded");
+            continue;
+        }
+
+        let mut current = program;
+        let mut failed = false;
+        for (i, pass) in pipeline.iter().enumerate() {
+            let step = format!("`{}` (pass {} of {})", pass.name, i + 1, pipeline.len());
+            let after = match pass.run(&current) {
+                Ok(after) => after,
+                Err(error) => {
+                    println!("FAIL {name}: {step} failed: {error}");
+                    dump(&format!("IR before `{}`", pass.name), &current);
+                    failed = true;
+                    break;
+                }
+            };
+            let actual = observe(&after, &args, Some(limit));
+            if !actual.same_behavior(&expected) {
+                println!("FAIL {name}: {step} changed the behavior");
+                println!("  expected:\n{expected}");
+                println!("  actual:\n{actual}");
+                dump(&format!("IR before `{}`", pass.name), &current);
+                dump(&format!("IR after `{}`", pass.name), &after);
+                failed = true;
+                break;
+            }
+            current = after;
+        }
+        if failed {
+            failures += 1;
+            continue;
+        }
+
+        let actual = observe(&current, &args, Some(limit));
+        match (expected.total_dyn_inst, actual.total_dyn_inst) {
+            (Some(before), Some(after)) => {
+                total_before += before;
+                total_after += after;
+                let slower = if after > before {
+                    " (more than before)"
+                } else {
+                    ""
+                };
+                println!("ok {name}: {before} -> {after} dynamic instructions{slower}");
+            }
+            _ => println!("ok {name}"),
+        }
+    }
+
+    println!(
+        "{} programs, {failures} failed, {total_before} -> {total_after} dynamic instructions",
+        programs.len()
+    );
+    if failures > 0 {
+        exit(1);
+    }
+}
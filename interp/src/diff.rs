@@ -0,0 +1,114 @@
+use std::{
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use bril_rs::Program;
+
+use crate::{error::InterpError, interp::run_program_with_limit};
+
+// what running a program looks like from the outside
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: String,
+    // the status brili would exit with
+    pub status: i32,
+    pub error: Option<InterpError>,
+    pub total_dyn_inst: Option<u64>,
+}
+
+impl Outcome {
+    // a pass may change how many instructions run, but nothing a user can see
+    pub fn same_behavior(&self, other: &Outcome) -> bool {
+        self.output == other.output && self.status == other.status
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in self.output.lines() {
+            writeln!(f, "    {line}")?;
+        }
+        match &self.error {
+            Some(error) => write!(f, "    (status {}, error: {error})", self.status),
+            None => write!(f, "    (status 0)"),
+        }
+    }
+}
+
+pub fn observe(program: &Program, args: &[String], limit: Option<u64>) -> Outcome {
+    let mut output = vec![];
+    let result = run_program_with_limit(program, args, &mut output, limit);
+    let output = String::from_utf8_lossy(&output).into_owned();
+    match result {
+        Ok(profile) => Outcome {
+            output,
+            status: 0,
+            error: None,
+            total_dyn_inst: Some(profile.total_dyn_inst),
+        },
+        Err(error) => Outcome {
+            output,
+            status: error.exit_code(),
+            error: Some(error),
+            total_dyn_inst: None,
+        },
+    }
+}
+
+// one step of a pipeline, like `lvn -f`, run as its own process on the program's JSON
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pass {
+    pub name: String,
+    pub command: PathBuf,
+    pub flags: Vec<String>,
+}
+
+impl Pass {
+    // bare names are looked up in bin_dir, anything with a slash is used as is
+    pub fn parse(step: &str, bin_dir: &Path) -> Pass {
+        let mut words = step.split_whitespace();
+        let binary = words.next().expect("pipeline step should name a pass");
+        let command = if binary.contains('/') {
+            PathBuf::from(binary)
+        } else {
+            bin_dir.join(binary)
+        };
+        Pass {
+            name: step.trim().to_string(),
+            command,
+            flags: words.map(str::to_string).collect(),
+        }
+    }
+
+    pub fn run(&self, program: &Program) -> Result<Program, String> {
+        let mut child = Command::new(&self.command)
+            .args(&self.flags)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| format!("couldn't start {}: {error}", self.command.display()))?;
+        let json = serde_json::to_string(program).expect("program should serialize");
+        child
+            .stdin
+            .take()
+            .expect("stdin should be piped")
+            .write_all(json.as_bytes())
+            .map_err(|error| format!("couldn't write the program: {error}"))?;
+        let result = child
+            .wait_with_output()
+            .map_err(|error| format!("couldn't wait for the pass: {error}"))?;
+        if !result.status.success() {
+            return Err(format!(
+                "exited with {}\n{}",
+                result.status,
+                String::from_utf8_lossy(&result.stderr).trim_end()
+            ));
+        }
+        serde_json::from_slice(&result.stdout)
+            .map_err(|error| format!("didn't print a program: {error}"))
+    }
+}
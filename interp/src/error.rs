@@ -0,0 +1,74 @@
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpError {
+    UndefinedVariable(String),
+    // a get whose shadow variable was never set
+    UnsetShadow(String),
+    UnknownFunction(String),
+    UnknownLabel(String),
+    // a value call to a function that returned nothing
+    MissingReturn(String),
+    ArgumentArity { expected: usize, got: usize },
+    BadArgument(String),
+    // an operand holds a value of the wrong type
+    BadOperand(String),
+    DivisionByZero,
+    BadCharacter(i64),
+    NonPositiveAlloc,
+    OutOfBounds,
+    UninitializedLoad,
+    UseAfterFree,
+    // free of a pointer that doesn't point at the start of its allocation
+    BadFree,
+    DoubleFree,
+    // some allocations were never freed by the end of the program
+    Leak(usize),
+    Unsupported(String),
+    // the instruction limit ran out, probably in an infinite loop
+    OutOfFuel,
+}
+
+impl InterpError {
+    // brili reports leaks with a different status than other errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            InterpError::Leak(_) => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl Display for InterpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterpError::UndefinedVariable(var) => write!(f, "undefined variable {var}"),
+            InterpError::UnsetShadow(var) => write!(f, "shadow variable {var} was never set"),
+            InterpError::UnknownFunction(func) => write!(f, "undefined function {func}"),
+            InterpError::UnknownLabel(label) => write!(f, "undefined label {label}"),
+            InterpError::MissingReturn(func) => write!(f, "function {func} didn't return a value"),
+            InterpError::ArgumentArity { expected, got } => write!(
+                f,
+                "mismatched main argument arity: expected {expected}; got {got}"
+            ),
+            InterpError::BadArgument(arg) => write!(f, "couldn't parse argument {arg}"),
+            InterpError::BadOperand(var) => write!(f, "{var} has the wrong type"),
+            InterpError::DivisionByZero => write!(f, "division by zero"),
+            InterpError::BadCharacter(code) => write!(f, "{code} is not a valid character"),
+            InterpError::NonPositiveAlloc => {
+                write!(f, "must allocate a positive amount of memory")
+            }
+            InterpError::OutOfBounds => write!(f, "out-of-bounds memory access"),
+            InterpError::UninitializedLoad => write!(f, "uninitialized load"),
+            InterpError::UseAfterFree => write!(f, "use of freed memory"),
+            InterpError::BadFree => write!(f, "can only free the start of an allocation"),
+            InterpError::DoubleFree => write!(f, "double free"),
+            InterpError::Leak(_) => write!(
+                f,
+                "some memory locations have not been freed by end of execution"
+            ),
+            InterpError::Unsupported(op) => write!(f, "{op} is not supported"),
+            InterpError::OutOfFuel => write!(f, "instruction limit exceeded"),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::{
+    error::InterpError,
+    value::{Pointer, Value},
+};
+
+// allocations are never reused, so a dangling pointer can always be told apart
+#[derive(Debug, Default)]
+pub struct Heap {
+    // None once freed
+    allocations: Vec<Option<Vec<Option<Value>>>>,
+    live: usize,
+}
+
+impl Heap {
+    pub fn alloc(&mut self, count: i64) -> Result<Pointer, InterpError> {
+        if count <= 0 {
+            return Err(InterpError::NonPositiveAlloc);
+        }
+        self.allocations.push(Some(vec![None; count as usize]));
+        self.live += 1;
+        Ok(Pointer {
+            base: self.allocations.len() - 1,
+            offset: 0,
+        })
+    }
+
+    pub fn free(&mut self, ptr: Pointer) -> Result<(), InterpError> {
+        if ptr.offset != 0 {
+            return Err(InterpError::BadFree);
+        }
+        match self.allocations[ptr.base].take() {
+            Some(_) => {
+                self.live -= 1;
+                Ok(())
+            }
+            None => Err(InterpError::DoubleFree),
+        }
+    }
+
+    fn cell(&mut self, ptr: Pointer) -> Result<&mut Option<Value>, InterpError> {
+        let allocation = self.allocations[ptr.base]
+            .as_mut()
+            .ok_or(InterpError::UseAfterFree)?;
+        usize::try_from(ptr.offset)
+            .ok()
+            .and_then(|offset| allocation.get_mut(offset))
+            .ok_or(InterpError::OutOfBounds)
+    }
+
+    pub fn load(&mut self, ptr: Pointer) -> Result<Value, InterpError> {
+        self.cell(ptr)?.ok_or(InterpError::UninitializedLoad)
+    }
+
+    pub fn store(&mut self, ptr: Pointer, value: Value) -> Result<(), InterpError> {
+        *self.cell(ptr)? = Some(value);
+        Ok(())
+    }
+
+    // number of allocations that haven't been freed
+    pub fn live(&self) -> usize {
+        self.live
+    }
+}
@@ -0,0 +1,332 @@
+use std::{collections::HashMap, io::Write};
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Program, ValueOps};
+
+use crate::{
+    error::InterpError,
+    heap::Heap,
+    value::{Pointer, Value},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    // every instruction executed, labels excluded
+    pub total_dyn_inst: u64,
+}
+
+struct FunctionInfo<'p> {
+    function: &'p Function,
+    // label -> its index in instrs
+    labels: HashMap<&'p str, usize>,
+}
+
+type Env<'p> = HashMap<&'p str, Value>;
+
+fn get(env: &Env, var: &str) -> Result<Value, InterpError> {
+    env.get(var)
+        .copied()
+        .ok_or_else(|| InterpError::UndefinedVariable(var.to_string()))
+}
+
+fn get_int(env: &Env, var: &str) -> Result<i64, InterpError> {
+    match get(env, var)? {
+        Value::Int(value) => Ok(value),
+        _ => Err(InterpError::BadOperand(var.to_string())),
+    }
+}
+
+fn get_bool(env: &Env, var: &str) -> Result<bool, InterpError> {
+    match get(env, var)? {
+        Value::Bool(value) => Ok(value),
+        _ => Err(InterpError::BadOperand(var.to_string())),
+    }
+}
+
+fn get_float(env: &Env, var: &str) -> Result<f64, InterpError> {
+    match get(env, var)? {
+        Value::Float(value) => Ok(value),
+        _ => Err(InterpError::BadOperand(var.to_string())),
+    }
+}
+
+fn get_char(env: &Env, var: &str) -> Result<char, InterpError> {
+    match get(env, var)? {
+        Value::Char(value) => Ok(value),
+        _ => Err(InterpError::BadOperand(var.to_string())),
+    }
+}
+
+fn get_pointer(env: &Env, var: &str) -> Result<Pointer, InterpError> {
+    match get(env, var)? {
+        Value::Pointer(value) => Ok(value),
+        _ => Err(InterpError::BadOperand(var.to_string())),
+    }
+}
+
+// ops that only read their arguments and the heap
+fn eval(op: ValueOps, args: &[String], env: &Env, heap: &mut Heap) -> Result<Value, InterpError> {
+    let ints = || Ok::<_, InterpError>((get_int(env, &args[0])?, get_int(env, &args[1])?));
+    let bools = || Ok::<_, InterpError>((get_bool(env, &args[0])?, get_bool(env, &args[1])?));
+    let floats = || Ok::<_, InterpError>((get_float(env, &args[0])?, get_float(env, &args[1])?));
+    let chars = || Ok::<_, InterpError>((get_char(env, &args[0])?, get_char(env, &args[1])?));
+    let value = match op {
+        ValueOps::Add => ints().map(|(a, b)| Value::Int(a.wrapping_add(b)))?,
+        ValueOps::Sub => ints().map(|(a, b)| Value::Int(a.wrapping_sub(b)))?,
+        ValueOps::Mul => ints().map(|(a, b)| Value::Int(a.wrapping_mul(b)))?,
+        ValueOps::Div => {
+            let (a, b) = ints()?;
+            if b == 0 {
+                return Err(InterpError::DivisionByZero);
+            }
+            Value::Int(a.wrapping_div(b))
+        }
+        ValueOps::Eq => ints().map(|(a, b)| Value::Bool(a == b))?,
+        ValueOps::Lt => ints().map(|(a, b)| Value::Bool(a < b))?,
+        ValueOps::Gt => ints().map(|(a, b)| Value::Bool(a > b))?,
+        ValueOps::Le => ints().map(|(a, b)| Value::Bool(a <= b))?,
+        ValueOps::Ge => ints().map(|(a, b)| Value::Bool(a >= b))?,
+        ValueOps::Not => Value::Bool(!get_bool(env, &args[0])?),
+        ValueOps::And => bools().map(|(a, b)| Value::Bool(a && b))?,
+        ValueOps::Or => bools().map(|(a, b)| Value::Bool(a || b))?,
+        ValueOps::Id => get(env, &args[0])?,
+        ValueOps::Fadd => floats().map(|(a, b)| Value::Float(a + b))?,
+        ValueOps::Fsub => floats().map(|(a, b)| Value::Float(a - b))?,
+        ValueOps::Fmul => floats().map(|(a, b)| Value::Float(a * b))?,
+        ValueOps::Fdiv => floats().map(|(a, b)| Value::Float(a / b))?,
+        ValueOps::Feq => floats().map(|(a, b)| Value::Bool(a == b))?,
+        ValueOps::Flt => floats().map(|(a, b)| Value::Bool(a < b))?,
+        ValueOps::Fgt => floats().map(|(a, b)| Value::Bool(a > b))?,
+        ValueOps::Fle => floats().map(|(a, b)| Value::Bool(a <= b))?,
+        ValueOps::Fge => floats().map(|(a, b)| Value::Bool(a >= b))?,
+        ValueOps::Ceq => chars().map(|(a, b)| Value::Bool(a == b))?,
+        ValueOps::Clt => chars().map(|(a, b)| Value::Bool(a < b))?,
+        ValueOps::Cgt => chars().map(|(a, b)| Value::Bool(a > b))?,
+        ValueOps::Cle => chars().map(|(a, b)| Value::Bool(a <= b))?,
+        ValueOps::Cge => chars().map(|(a, b)| Value::Bool(a >= b))?,
+        ValueOps::Char2int => Value::Int(get_char(env, &args[0])? as i64),
+        ValueOps::Int2char => {
+            let code = get_int(env, &args[0])?;
+            let c = u32::try_from(code).ok().and_then(char::from_u32);
+            Value::Char(c.ok_or(InterpError::BadCharacter(code))?)
+        }
+        ValueOps::Float2Bits => Value::Int(get_float(env, &args[0])?.to_bits() as i64),
+        ValueOps::Bits2Float => Value::Float(f64::from_bits(get_int(env, &args[0])? as u64)),
+        ValueOps::Alloc => Value::Pointer(heap.alloc(get_int(env, &args[0])?)?),
+        ValueOps::Load => heap.load(get_pointer(env, &args[0])?)?,
+        ValueOps::PtrAdd => {
+            let ptr = get_pointer(env, &args[0])?;
+            Value::Pointer(Pointer {
+                offset: ptr.offset.wrapping_add(get_int(env, &args[1])?),
+                ..ptr
+            })
+        }
+        ValueOps::Call | ValueOps::Get | ValueOps::Undef => {
+            unreachable!("{op} should be handled by the caller")
+        }
+    };
+    Ok(value)
+}
+
+struct Interpreter<'p, W: Write> {
+    functions: &'p HashMap<&'p str, FunctionInfo<'p>>,
+    heap: Heap,
+    out: W,
+    profile: Profile,
+    // most instructions to execute before giving up
+    limit: Option<u64>,
+}
+
+impl<'p, W: Write> Interpreter<'p, W> {
+    fn call(&mut self, name: &str, values: Vec<Value>) -> Result<Option<Value>, InterpError> {
+        let info = self
+            .functions
+            .get(name)
+            .ok_or_else(|| InterpError::UnknownFunction(name.to_string()))?;
+        let function = info.function;
+        let jump = |label: &String| {
+            info.labels
+                .get(label.as_str())
+                .copied()
+                .ok_or_else(|| InterpError::UnknownLabel(label.clone()))
+        };
+
+        let mut env: Env<'p> = function
+            .args
+            .iter()
+            .map(|arg| arg.name.as_str())
+            .zip(values)
+            .collect();
+        // the values set passes to get, named by the get's destination
+        let mut shadows: Env<'p> = HashMap::new();
+
+        let mut pc = 0;
+        while pc < function.instrs.len() {
+            let instr = match &function.instrs[pc] {
+                Code::Label { .. } => {
+                    pc += 1;
+                    continue;
+                }
+                Code::Instruction(instr) => instr,
+            };
+            pc += 1;
+            self.profile.total_dyn_inst += 1;
+            if self
+                .limit
+                .is_some_and(|limit| self.profile.total_dyn_inst > limit)
+            {
+                return Err(InterpError::OutOfFuel);
+            }
+
+            match instr {
+                Instruction::Constant {
+                    dest,
+                    const_type,
+                    value,
+                    ..
+                } => {
+                    env.insert(dest, Value::from_literal(value, const_type));
+                }
+                Instruction::Value {
+                    op: ValueOps::Undef,
+                    dest,
+                    ..
+                } => {
+                    env.remove(dest.as_str());
+                }
+                Instruction::Value {
+                    op,
+                    dest,
+                    args,
+                    funcs,
+                    ..
+                } => {
+                    let value = match op {
+                        ValueOps::Get => shadows
+                            .get(dest.as_str())
+                            .copied()
+                            .ok_or_else(|| InterpError::UnsetShadow(dest.clone()))?,
+                        ValueOps::Call => {
+                            let values = args
+                                .iter()
+                                .map(|arg| get(&env, arg))
+                                .collect::<Result<_, _>>()?;
+                            self.call(&funcs[0], values)?
+                                .ok_or_else(|| InterpError::MissingReturn(funcs[0].clone()))?
+                        }
+                        _ => eval(*op, args, &env, &mut self.heap)?,
+                    };
+                    env.insert(dest, value);
+                }
+                Instruction::Effect {
+                    op,
+                    args,
+                    funcs,
+                    labels,
+                    ..
+                } => match op {
+                    EffectOps::Jump => pc = jump(&labels[0])?,
+                    EffectOps::Branch => {
+                        let taken = if get_bool(&env, &args[0])? { 0 } else { 1 };
+                        pc = jump(&labels[taken])?;
+                    }
+                    EffectOps::Return => {
+                        return args.first().map(|arg| get(&env, arg)).transpose();
+                    }
+                    EffectOps::Call => {
+                        let values = args
+                            .iter()
+                            .map(|arg| get(&env, arg))
+                            .collect::<Result<_, _>>()?;
+                        self.call(&funcs[0], values)?;
+                    }
+                    EffectOps::Print => {
+                        let values = args
+                            .iter()
+                            .map(|arg| get(&env, arg).map(|value| value.to_string()))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        writeln!(self.out, "{}", values.join(" "))
+                            .expect("output should be writable");
+                    }
+                    EffectOps::Nop => {}
+                    EffectOps::Store => {
+                        let ptr = get_pointer(&env, &args[0])?;
+                        self.heap.store(ptr, get(&env, &args[1])?)?;
+                    }
+                    EffectOps::Free => self.heap.free(get_pointer(&env, &args[0])?)?,
+                    EffectOps::Set => {
+                        shadows.insert(&args[0], get(&env, &args[1])?);
+                    }
+                    EffectOps::Speculate | EffectOps::Commit | EffectOps::Guard => {
+                        return Err(InterpError::Unsupported(op.to_string()));
+                    }
+                },
+            }
+        }
+        Ok(None)
+    }
+}
+
+// runs main with the given command-line args, printing to out
+pub fn run_program<W: Write>(
+    program: &Program,
+    args: &[String],
+    out: W,
+) -> Result<Profile, InterpError> {
+    run_program_with_limit(program, args, out, None)
+}
+
+// like run_program, but stops with an error after limit instructions
+pub fn run_program_with_limit<W: Write>(
+    program: &Program,
+    args: &[String],
+    out: W,
+    limit: Option<u64>,
+) -> Result<Profile, InterpError> {
+    let functions: HashMap<&str, FunctionInfo> = program
+        .functions
+        .iter()
+        .map(|function| {
+            let labels = function
+                .instrs
+                .iter()
+                .enumerate()
+                .filter_map(|(i, code)| match code {
+                    Code::Label { label, .. } => Some((label.as_str(), i)),
+                    Code::Instruction(_) => None,
+                })
+                .collect();
+            (function.name.as_str(), FunctionInfo { function, labels })
+        })
+        .collect();
+
+    let main = functions
+        .get("main")
+        .ok_or_else(|| InterpError::UnknownFunction("main".to_string()))?
+        .function;
+    if main.args.len() != args.len() {
+        return Err(InterpError::ArgumentArity {
+            expected: main.args.len(),
+            got: args.len(),
+        });
+    }
+    let values = main
+        .args
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| Value::parse(arg, &param.arg_type))
+        .collect::<Result<_, _>>()?;
+
+    let mut interpreter = Interpreter {
+        functions: &functions,
+        heap: Heap::default(),
+        out,
+        profile: Profile::default(),
+        limit,
+    };
+    interpreter.call("main", values)?;
+    interpreter.out.flush().expect("output should be writable");
+    match interpreter.heap.live() {
+        0 => Ok(interpreter.profile),
+        live => Err(InterpError::Leak(live)),
+    }
+}
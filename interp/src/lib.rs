@@ -0,0 +1,7 @@
+pub mod diff;
+pub mod error;
+pub mod heap;
+pub mod interp;
+pub mod parse;
+pub mod turnt;
+pub mod value;
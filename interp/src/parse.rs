@@ -0,0 +1,344 @@
+use std::{fmt::Display, iter::Peekable, str::Chars};
+
+use bril_rs::{
+    Argument, Code, ColRow, ConstOps, EffectOps, Function, Instruction, Literal, Position, Program,
+    Type, ValueOps,
+};
+
+// reads the text format bril2json accepts, so programs can run without it
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub row: u64,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.row, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Char(char),
+    Str(String),
+    Punct(char),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{word}"),
+            Token::Char(c) => write!(f, "'{c}'"),
+            Token::Str(s) => write!(f, "\"{s}\""),
+            Token::Punct(c) => write!(f, "{c}"),
+        }
+    }
+}
+
+const PUNCTUATION: &str = "(){}:=;,<>";
+
+fn read_quoted(chars: &mut Peekable<Chars>, quote: char) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        match chars.next()? {
+            c if c == quote => return Some(text),
+            '\\' => text.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                c => c,
+            }),
+            c => text.push(c),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, ColRow)>, ParseError> {
+    let mut tokens = vec![];
+    for (row, line) in text.lines().enumerate() {
+        let row = row as u64 + 1;
+        let length = line.chars().count();
+        let mut chars = line.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            let col = (length - chars.clone().count()) as u64 + 1;
+            let pos = ColRow { col, row };
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '#' {
+                break;
+            } else if PUNCTUATION.contains(c) {
+                chars.next();
+                tokens.push((Token::Punct(c), pos));
+            } else if c == '\'' || c == '"' {
+                chars.next();
+                let quoted = read_quoted(&mut chars, c).ok_or_else(|| ParseError {
+                    row,
+                    message: "unterminated quote".to_string(),
+                })?;
+                let token = if c == '"' {
+                    Token::Str(quoted)
+                } else {
+                    let mut quoted_chars = quoted.chars();
+                    match (quoted_chars.next(), quoted_chars.next()) {
+                        (Some(c), None) => Token::Char(c),
+                        _ => {
+                            return Err(ParseError {
+                                row,
+                                message: format!("'{quoted}' should be a single character"),
+                            });
+                        }
+                    }
+                };
+                tokens.push((token, pos));
+            } else {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '#' || PUNCTUATION.contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push((Token::Word(word), pos));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// args, funcs and labels
+type Operands = (Vec<String>, Vec<String>, Vec<String>);
+
+struct Parser {
+    tokens: Vec<(Token, ColRow)>,
+    next: usize,
+}
+
+impl Parser {
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        let row = match self.tokens.get(self.next).or(self.tokens.last()) {
+            Some((_, pos)) => pos.row,
+            None => 1,
+        };
+        Err(ParseError { row, message })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn pos(&self) -> Option<Position> {
+        self.tokens.get(self.next).map(|(_, pos)| Position {
+            pos: *pos,
+            pos_end: None,
+            src: None,
+        })
+    }
+
+    fn advance(&mut self) -> Result<Token, ParseError> {
+        match self.tokens.get(self.next) {
+            Some((token, _)) => {
+                self.next += 1;
+                Ok(token.clone())
+            }
+            None => self.error("unexpected end of input".to_string()),
+        }
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        if self.peek() == Some(&Token::Punct(punct)) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), ParseError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(token) => self.error(format!("expected {punct}, found {token}")),
+                None => self.error(format!("expected {punct}")),
+            }
+        }
+    }
+
+    fn word(&mut self) -> Result<String, ParseError> {
+        match self.advance()? {
+            Token::Word(word) => Ok(word),
+            token => {
+                self.next -= 1;
+                self.error(format!("expected a name, found {token}"))
+            }
+        }
+    }
+
+    fn tipe(&mut self) -> Result<Type, ParseError> {
+        let name = self.word()?;
+        match name.as_str() {
+            "int" => Ok(Type::Int),
+            "bool" => Ok(Type::Bool),
+            "float" => Ok(Type::Float),
+            "char" => Ok(Type::Char),
+            "ptr" => {
+                self.expect('<')?;
+                let pointee = self.tipe()?;
+                self.expect('>')?;
+                Ok(Type::Pointer(Box::new(pointee)))
+            }
+            _ => self.error(format!("unknown type {name}")),
+        }
+    }
+
+    fn literal(&mut self, tipe: &Type) -> Result<Literal, ParseError> {
+        let token = self.advance()?;
+        let literal = match (&token, tipe) {
+            (Token::Char(c), Type::Char) => Some(Literal::Char(*c)),
+            (Token::Word(word), Type::Int) => word.parse().ok().map(Literal::Int),
+            (Token::Word(word), Type::Bool) => word.parse().ok().map(Literal::Bool),
+            (Token::Word(word), Type::Float) => word.parse().ok().map(Literal::Float),
+            _ => None,
+        };
+        match literal {
+            Some(literal) => Ok(literal),
+            None => self.error(format!("{token} isn't a {tipe} literal")),
+        }
+    }
+
+    // everything up to the semicolon, sorted into functions, labels and variables
+    fn operands(&mut self) -> Result<Operands, ParseError> {
+        let (mut args, mut funcs, mut labels) = (vec![], vec![], vec![]);
+        while !self.eat(';') {
+            let word = self.word()?;
+            if let Some(func) = word.strip_prefix('@') {
+                funcs.push(func.to_string());
+            } else if let Some(label) = word.strip_prefix('.') {
+                labels.push(label.to_string());
+            } else {
+                args.push(word);
+            }
+        }
+        Ok((args, funcs, labels))
+    }
+
+    fn code(&mut self) -> Result<Code, ParseError> {
+        let pos = self.pos();
+        let first = self.word()?;
+        if let Some(label) = first.strip_prefix('.') {
+            self.expect(':')?;
+            return Ok(Code::Label {
+                label: label.to_string(),
+                pos,
+            });
+        }
+
+        if !self.eat(':') {
+            let op = serde_json::from_value::<EffectOps>(first.clone().into());
+            let Ok(op) = op else {
+                return self.error(format!("unknown operation {first}"));
+            };
+            let (args, funcs, labels) = self.operands()?;
+            return Ok(Code::Instruction(Instruction::Effect {
+                args,
+                funcs,
+                labels,
+                op,
+                pos,
+            }));
+        }
+
+        let tipe = self.tipe()?;
+        self.expect('=')?;
+        let op = self.word()?;
+        if op == "const" {
+            let value = self.literal(&tipe)?;
+            self.expect(';')?;
+            return Ok(Code::Instruction(Instruction::Constant {
+                dest: first,
+                op: ConstOps::Const,
+                pos,
+                const_type: tipe,
+                value,
+            }));
+        }
+        let Ok(op) = serde_json::from_value::<ValueOps>(op.clone().into()) else {
+            return self.error(format!("unknown operation {op}"));
+        };
+        let (args, funcs, labels) = self.operands()?;
+        Ok(Code::Instruction(Instruction::Value {
+            args,
+            dest: first,
+            funcs,
+            labels,
+            op,
+            pos,
+            op_type: tipe,
+        }))
+    }
+
+    fn function(&mut self) -> Result<Function, ParseError> {
+        let pos = self.pos();
+        let word = self.word()?;
+        let Some(name) = word.strip_prefix('@') else {
+            return self.error(format!("expected a function, found {word}"));
+        };
+
+        let mut args = vec![];
+        if self.eat('(') {
+            while !self.eat(')') {
+                if !args.is_empty() {
+                    self.expect(',')?;
+                }
+                let name = self.word()?;
+                self.expect(':')?;
+                args.push(Argument {
+                    name,
+                    arg_type: self.tipe()?,
+                });
+            }
+        }
+        let return_type = if self.eat(':') {
+            Some(self.tipe()?)
+        } else {
+            None
+        };
+
+        self.expect('{')?;
+        let mut instrs = vec![];
+        while !self.eat('}') {
+            instrs.push(self.code()?);
+        }
+        Ok(Function {
+            args,
+            instrs,
+            name: name.to_string(),
+            pos,
+            return_type,
+        })
+    }
+}
+
+// imports aren't resolved, so programs have to be self-contained
+pub fn parse_program(text: &str) -> Result<Program, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        next: 0,
+    };
+    let mut functions = vec![];
+    while let Some(token) = parser.peek() {
+        if *token == Token::Word("from".to_string()) {
+            return parser.error("imports aren't supported".to_string());
+        }
+        functions.push(parser.function()?);
+    }
+    Ok(Program {
+        functions,
+        imports: vec![],
+    })
+}
@@ -0,0 +1,333 @@
+use std::{
+    fs,
+    io::Write,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+
+use bril_rs::{Code, Instruction, Program};
+
+use crate::parse::parse_program;
+
+// runs turnt snapshot suites without turnt or the JavaScript Bril tools
+// the command from turnt.toml or a `# CMD:` line is read as a small shell pipeline, where
+// bril2json and bril2txt are done in process and `.../target/release/NAME` runs NAME from bin_dir
+
+#[derive(Debug, Default)]
+pub struct SuiteReport {
+    pub passed: usize,
+    // a program and how its output differed
+    pub failures: Vec<String>,
+    // programs whose command needs more than the Bril tools and the binaries, like sed or cc
+    pub skipped: Vec<String>,
+}
+
+impl SuiteReport {
+    fn extend(&mut self, other: SuiteReport) {
+        self.passed += other.passed;
+        self.failures.extend(other.failures);
+        self.skipped.extend(other.skipped);
+    }
+}
+
+enum RunError {
+    Unsupported(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Pipe,
+    Semicolon,
+}
+
+// words with quotes removed, plus the operators that split a command into pipelines
+fn tokenize(command: &str) -> Result<Vec<Token>, RunError> {
+    if command.contains("&&") || command.contains("||") {
+        return Err(RunError::Unsupported(command.to_string()));
+    }
+    let mut tokens = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '|' | ';' => {
+                tokens.extend(word.take().map(Token::Word));
+                tokens.push(if c == '|' {
+                    Token::Pipe
+                } else {
+                    Token::Semicolon
+                });
+            }
+            '\'' | '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(q) => word.push(q),
+                        None => return Err(RunError::Unsupported(command.to_string())),
+                    }
+                }
+            }
+            c if c.is_whitespace() => tokens.extend(word.take().map(Token::Word)),
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    tokens.extend(word.take().map(Token::Word));
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stderr {
+    Dropped,
+    // `2>&1`, approximated as all of stdout followed by all of stderr
+    Merged,
+    // `2>&1 >/dev/null`
+    Only,
+}
+
+enum Stage {
+    // `bril2json < file`, where `-p` keeps source positions
+    ToJson {
+        file: String,
+        positions: bool,
+    },
+    ToText,
+    Binary {
+        path: PathBuf,
+        args: Vec<String>,
+        stderr: Stderr,
+    },
+}
+
+fn parse_stage(words: &[String], bin_dir: &Path) -> Result<Stage, RunError> {
+    let unsupported = || RunError::Unsupported(words.join(" "));
+    let (words, stderr) = match words {
+        [words @ .., redirect, null] if redirect == "2>&1" && null == ">/dev/null" => {
+            (words, Stderr::Only)
+        }
+        [words @ .., redirect] if redirect == "2>&1" => (words, Stderr::Merged),
+        words => (words, Stderr::Dropped),
+    };
+    let Some((program, args)) = words.split_first() else {
+        return Err(unsupported());
+    };
+    match (program.as_str(), args) {
+        ("bril2json", [redirect, file]) if redirect == "<" => Ok(Stage::ToJson {
+            file: file.clone(),
+            positions: false,
+        }),
+        ("bril2json", [flag, redirect, file]) if flag == "-p" && redirect == "<" => {
+            Ok(Stage::ToJson {
+                file: file.clone(),
+                positions: true,
+            })
+        }
+        ("bril2txt", []) => Ok(Stage::ToText),
+        (program, args)
+            if program.contains("target/release/")
+                && !args.iter().any(|arg| arg.contains(['<', '>'])) =>
+        {
+            let name = Path::new(program).file_name().ok_or_else(unsupported)?;
+            Ok(Stage::Binary {
+                path: bin_dir.join(name),
+                args: args.to_vec(),
+                stderr,
+            })
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+fn strip_positions(program: &mut Program) {
+    for function in program.functions.iter_mut() {
+        function.pos = None;
+        for code in function.instrs.iter_mut() {
+            match code {
+                Code::Label { pos, .. }
+                | Code::Instruction(Instruction::Constant { pos, .. })
+                | Code::Instruction(Instruction::Value { pos, .. })
+                | Code::Instruction(Instruction::Effect { pos, .. }) => *pos = None,
+            }
+        }
+    }
+}
+
+// the stage's output and exit status
+fn run_stage(stage: &Stage, input: Vec<u8>, dir: &Path) -> Result<(Vec<u8>, i32), RunError> {
+    match stage {
+        Stage::ToJson { file, positions } => {
+            let text = fs::read_to_string(dir.join(file))
+                .map_err(|error| RunError::Failed(format!("couldn't read {file}: {error}")))?;
+            let mut program = parse_program(&text)
+                .map_err(|error| RunError::Failed(format!("bril2json: {error}")))?;
+            if !positions {
+                strip_positions(&mut program);
+            }
+            let json = serde_json::to_vec(&program).expect("programs should serialize");
+            Ok((json, 0))
+        }
+        Stage::ToText => {
+            let program: Program = serde_json::from_slice(&input)
+                .map_err(|error| RunError::Failed(format!("bril2txt: {error}")))?;
+            Ok((program.to_string().into_bytes(), 0))
+        }
+        Stage::Binary { path, args, stderr } => {
+            let mut child = Command::new(path)
+                .args(args)
+                .current_dir(dir)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|error| {
+                    RunError::Failed(format!("couldn't run {}: {error}", path.display()))
+                })?;
+            // written from another thread so a child that prints before reading can't block us
+            let mut stdin = child.stdin.take().expect("stdin should be piped");
+            let writer = thread::spawn(move || stdin.write_all(&input));
+            let output = child
+                .wait_with_output()
+                .map_err(|error| RunError::Failed(error.to_string()))?;
+            // a binary that exits without reading all of its input is fine
+            let _ = writer.join().expect("writer thread shouldn't panic");
+
+            let mut bytes = match stderr {
+                Stderr::Dropped | Stderr::Merged => output.stdout,
+                Stderr::Only => vec![],
+            };
+            if *stderr != Stderr::Dropped {
+                bytes.extend(output.stderr);
+            }
+            // like the shell, a signal shows up as 128 + its number
+            let status = output
+                .status
+                .code()
+                .unwrap_or_else(|| 128 + output.status.signal().unwrap_or(0));
+            Ok((bytes, status))
+        }
+    }
+}
+
+// what the command prints, with `; echo exit: $?` as the only thing allowed after the pipeline
+fn run_command(command: &str, dir: &Path, bin_dir: &Path) -> Result<String, RunError> {
+    let tokens = tokenize(command)?;
+    let mut commands = tokens.split(|token| *token == Token::Semicolon);
+    let pipeline = commands.next().unwrap_or_default();
+    let echo_status = match commands.next() {
+        None => false,
+        Some([Token::Word(echo), Token::Word(exit), Token::Word(status)])
+            if echo == "echo" && exit == "exit:" && status == "$?" =>
+        {
+            true
+        }
+        Some(_) => return Err(RunError::Unsupported(command.to_string())),
+    };
+    if commands.next().is_some() {
+        return Err(RunError::Unsupported(command.to_string()));
+    }
+
+    let stages = pipeline
+        .split(|token| *token == Token::Pipe)
+        .map(|stage| {
+            let words: Vec<String> = stage
+                .iter()
+                .map(|token| match token {
+                    Token::Word(word) => word.clone(),
+                    _ => unreachable!("stages should only hold words"),
+                })
+                .collect();
+            parse_stage(&words, bin_dir)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut data = vec![];
+    let mut status = 0;
+    for stage in stages.iter() {
+        (data, status) = run_stage(stage, data, dir)?;
+    }
+
+    let mut output = String::from_utf8_lossy(&data).into_owned();
+    if echo_status {
+        output.push_str(&format!("exit: {status}\n"));
+    }
+    Ok(output)
+}
+
+fn command_line<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    text.lines()
+        .find_map(|line| line.strip_prefix(prefix))
+        .map(str::trim)
+}
+
+// checks every .bril program in dir against its .out snapshot
+pub fn check_suite(dir: &Path, bin_dir: &Path) -> SuiteReport {
+    let config =
+        fs::read_to_string(dir.join("turnt.toml")).expect("suite should have a turnt.toml");
+    let base = command_line(&config, "command = ")
+        .and_then(|command| command.strip_prefix('"')?.strip_suffix('"'))
+        .expect("turnt.toml should have a command");
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("suite should be readable")
+        .map(|entry| entry.expect("suite entry should be readable").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bril"))
+        .collect();
+    paths.sort();
+
+    let mut report = SuiteReport::default();
+    for path in paths {
+        let text = fs::read_to_string(&path).expect("test program should be readable");
+        let file = path
+            .file_name()
+            .expect("test program should have a name")
+            .to_string_lossy();
+        let stem = path
+            .file_stem()
+            .expect("test program should have a name")
+            .to_string_lossy();
+        let command = command_line(&text, "# CMD:")
+            .unwrap_or(base)
+            .replace("{filename}", &file)
+            .replace("{base}", &stem)
+            .replace("{args}", command_line(&text, "# ARGS:").unwrap_or(""));
+
+        let name = path.display();
+        match run_command(&command, dir, bin_dir) {
+            Ok(actual) => {
+                let expected = fs::read_to_string(path.with_extension("out")).unwrap_or_default();
+                if actual == expected {
+                    report.passed += 1;
+                } else {
+                    report
+                        .failures
+                        .push(format!("{name}:\nexpected:\n{expected}actual:\n{actual}"));
+                }
+            }
+            Err(RunError::Failed(error)) => report.failures.push(format!("{name}: {error}")),
+            Err(RunError::Unsupported(part)) => {
+                report.skipped.push(format!("{name}: can't run {part}"))
+            }
+        }
+    }
+    report
+}
+
+// checks every suite directly under test_dir, i.e. every directory with a turnt.toml
+pub fn check_suites(test_dir: &Path, bin_dir: &Path) -> SuiteReport {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(test_dir)
+        .expect("test directory should be readable")
+        .map(|entry| entry.expect("test entry should be readable").path())
+        .filter(|path| path.join("turnt.toml").exists())
+        .collect();
+    dirs.sort();
+
+    let mut report = SuiteReport::default();
+    for dir in dirs {
+        report.extend(check_suite(&dir, bin_dir));
+    }
+    report
+}
@@ -0,0 +1,82 @@
+use std::fmt::Display;
+
+use bril_rs::{Literal, Type};
+
+use crate::error::InterpError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pointer {
+    // index of the allocation in the heap
+    pub base: usize,
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    Float(f64),
+    Char(char),
+    Pointer(Pointer),
+}
+
+impl Value {
+    pub fn from_literal(value: &Literal, tipe: &Type) -> Value {
+        match (value, tipe) {
+            // bril2json leaves int literals of float constants as ints
+            (Literal::Int(value), Type::Float) => Value::Float(*value as f64),
+            (Literal::Int(value), _) => Value::Int(*value),
+            (Literal::Bool(value), _) => Value::Bool(*value),
+            (Literal::Float(value), _) => Value::Float(*value),
+            (Literal::Char(value), _) => Value::Char(*value),
+        }
+    }
+
+    // a command-line argument to main
+    pub fn parse(arg: &str, tipe: &Type) -> Result<Value, InterpError> {
+        let bad = || InterpError::BadArgument(arg.to_string());
+        match tipe {
+            Type::Int => arg.parse().map(Value::Int).map_err(|_| bad()),
+            Type::Bool => match arg {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => Err(bad()),
+            },
+            Type::Float => arg.parse().map(Value::Float).map_err(|_| bad()),
+            Type::Char => {
+                let mut chars = arg.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(Value::Char(c)),
+                    _ => Err(bad()),
+                }
+            }
+            Type::Pointer(_) => Err(bad()),
+        }
+    }
+}
+
+fn format_float(x: f64) -> String {
+    if x.is_nan() {
+        "NaN".to_string()
+    } else if x.is_infinite() {
+        if x > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if x.abs() >= 1e21 {
+        // toFixed falls back to the shortest exponential form, with an explicit sign
+        format!("{x:e}").replace('e', "e+")
+    } else {
+        format!("{x:.17}")
+    }
+}
+
+// the same format brili prints in
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{value}"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Float(value) => write!(f, "{}", format_float(*value)),
+            Value::Char(value) => write!(f, "{value}"),
+            Value::Pointer(_) => write!(f, "[object Object]"),
+        }
+    }
+}
@@ -0,0 +1,3 @@
+#!/bin/sh
+# a deliberately broken pass that turns every add into a sub
+sed 's/"op":"add"/"op":"sub"/g'
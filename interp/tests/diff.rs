@@ -0,0 +1,54 @@
+use std::{fs, path::Path, process::Command};
+
+use bril_rs::Program;
+use interp::{
+    diff::{Pass, observe},
+    parse::parse_program,
+};
+
+fn load(name: &str) -> Program {
+    let text = fs::read_to_string(format!("test/difftest/{name}"))
+        .expect("test program should be readable");
+    parse_program(&text).expect("test program should parse")
+}
+
+#[test]
+fn mutating_pass_changes_behavior() {
+    let program = load("mutant.bril");
+    // anything with a slash is run as is, from the crate's directory here
+    let pass = Pass::parse("test/difftest/mutate-add.sh", Path::new("unused"));
+    let mutated = pass.run(&program).expect("the pass should print a program");
+
+    let args = ["3".to_string()];
+    let before = observe(&program, &args, None);
+    let after = observe(&mutated, &args, None);
+    assert_eq!(before.output, "4\n");
+    assert_eq!(after.output, "2\n");
+    assert!(!before.same_behavior(&after));
+}
+
+#[test]
+fn missing_pass_fails() {
+    let pass = Pass::parse("missing-pass", Path::new("test/difftest"));
+    let error = pass
+        .run(&load("crash.bril"))
+        .expect_err("a missing pass shouldn't run");
+    assert!(error.starts_with("couldn't start"), "{error}");
+}
+
+// what the difftest snapshots check, without the task3 passes they run first
+#[test]
+fn difftest_reports_mutating_pass() {
+    let output = Command::new(env!("CARGO_BIN_EXE_difftest"))
+        .args(["-p", "./mutate-add.sh", "mutant.bril"])
+        .current_dir("test/difftest")
+        .output()
+        .expect("difftest should run");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{stdout}");
+    assert!(
+        stdout
+            .starts_with("FAIL mutant.bril: `./mutate-add.sh` (pass 1 of 1) changed the behavior"),
+        "{stdout}"
+    );
+}
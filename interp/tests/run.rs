@@ -0,0 +1,48 @@
+use std::{fmt::Write, fs, path::Path};
+
+use interp::{interp::run_program, parse::parse_program};
+
+// what `brili -p {args} 2>&1` prints, the format the turnt snapshots are saved in
+fn run(path: &Path) -> String {
+    let text = fs::read_to_string(path).expect("test program should be readable");
+    let args: Vec<String> = text
+        .lines()
+        .find_map(|line| line.strip_prefix("# ARGS:"))
+        .map(|args| args.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+    let program = parse_program(&text).expect("test program should parse");
+
+    let mut out = vec![];
+    let result = run_program(&program, &args, &mut out);
+    let mut out = String::from_utf8(out).expect("output should be UTF-8");
+    match result {
+        Ok(profile) => writeln!(out, "total_dyn_inst: {}", profile.total_dyn_inst),
+        Err(error) => writeln!(out, "error: {error}"),
+    }
+    .expect("writing to a string should succeed");
+    out
+}
+
+#[test]
+fn programs_match_snapshots() {
+    let mut failures = vec![];
+    let mut count = 0;
+    for entry in fs::read_dir("test/run").expect("test/run should exist") {
+        let path = entry.expect("test entry should be readable").path();
+        if path.extension().is_none_or(|ext| ext != "bril") {
+            continue;
+        }
+        count += 1;
+        let expected = fs::read_to_string(path.with_extension("out"))
+            .expect("every test program should have a snapshot");
+        let actual = run(&path);
+        if actual != expected {
+            failures.push(format!(
+                "{}:\nexpected:\n{expected}actual:\n{actual}",
+                path.display()
+            ));
+        }
+    }
+    assert!(count > 0, "test/run should have programs");
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,100 @@
+use std::{
+    env::args,
+    fs,
+    io::{Read, stdin},
+    process::exit,
+    time::Duration,
+};
+
+use bril_rs::{Program, output_program};
+use interp::parse::parse_program;
+use opt::pipeline::{count_instrs, parse_pipeline, run_pipeline};
+
+const FLAGS_WITH_VALUES: [&str; 1] = ["-p"];
+
+fn flag_value(flag: &str) -> Option<String> {
+    args().skip_while(|arg| arg != flag).nth(1)
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {message}");
+    exit(1);
+}
+
+// the file named on the command line, or stdin if there's none
+fn read_input() -> String {
+    let mut args = args().skip(1);
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        if FLAGS_WITH_VALUES.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            path = Some(arg);
+        }
+    }
+    match path {
+        Some(path) => fs::read_to_string(&path)
+            .unwrap_or_else(|error| fail(&format!("couldn't read {path}: {error}"))),
+        None => {
+            let mut text = String::new();
+            stdin()
+                .read_to_string(&mut text)
+                .unwrap_or_else(|error| fail(&format!("couldn't read stdin: {error}")));
+            text
+        }
+    }
+}
+
+// JSON starts with an object, while Bril text starts with a function or a comment
+fn load(text: &str) -> Program {
+    if text.trim_start().starts_with('{') {
+        serde_json::from_str(text).unwrap_or_else(|error| fail(&format!("bad JSON: {error}")))
+    } else {
+        parse_program(text).unwrap_or_else(|error| fail(&error.to_string()))
+    }
+}
+
+fn milliseconds(elapsed: Duration) -> String {
+    format!("{:.3}ms", elapsed.as_secs_f64() * 1000.0)
+}
+
+// runs a pipeline of passes on a JSON or text program from a file or stdin
+// `-p` sets the pipeline, like `lvn(fold),fix(dce-global,dce-local)`, where fix(...) repeats its
+// passes until they stop changing the program
+// `-t` prints the result as text instead of JSON, `-s` prints the time each pass took and how
+// many instructions it removed
+fn main() {
+    let pipeline = flag_value("-p").unwrap_or_else(|| fail("-p should give a pipeline"));
+    let steps = parse_pipeline(&pipeline).unwrap_or_else(|error| fail(&error));
+    let mut program = load(&read_input());
+
+    let before = count_instrs(&program);
+    let reports = run_pipeline(&mut program, &steps);
+
+    if args().any(|arg| arg == "-s") {
+        for report in reports.iter() {
+            let delta = report.after as i64 - report.before as i64;
+            eprintln!(
+                "{:<28} {:>10} {:>6} -> {:<6} ({delta:+})",
+                report.pass,
+                milliseconds(report.elapsed),
+                report.before,
+                report.after
+            );
+        }
+        let after = count_instrs(&program);
+        let total = reports.iter().map(|report| report.elapsed).sum();
+        eprintln!(
+            "{:<28} {:>10} {before:>6} -> {after:<6} ({:+})",
+            "total",
+            milliseconds(total),
+            after as i64 - before as i64
+        );
+    }
+
+    if args().any(|arg| arg == "-t") {
+        print!("{program}");
+    } else {
+        output_program(&program);
+    }
+}
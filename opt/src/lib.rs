@@ -0,0 +1,2 @@
+pub mod passes;
+pub mod pipeline;
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use bril_rs::{Function, Program};
+use task3::{
+    dce::{global_dce_pass, locally_killed_pass},
+    flatten, get_basic_blocks,
+    inline::{InlineConfig, inline_calls},
+    lvn::lvn,
+    purity::{Purity, analyze_purity},
+    tail_recursion::eliminate_tail_recursion,
+};
+use task6::{
+    const_prop::propagate_constants,
+    cse::eliminate_common_subexprs,
+    induction::reduce_strength,
+    lcm::lazy_code_motion,
+    mem_opt::optimize_memory,
+    unroll::{UnrollConfig, unroll_loops},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    Lvn { fold: bool },
+    DceGlobal,
+    DceLocal,
+    Tre,
+    ConstProp,
+    Gcse,
+    Lcm,
+    MemOpt,
+    Strength,
+    Unroll { factor: usize, limit: usize },
+    // the only pass that works on the whole program at once
+    Inline { threshold: usize, depth: usize },
+}
+
+// a pass's name and the pass with its default options
+type Entry = (&'static str, fn() -> Pass);
+
+const PASSES: [Entry; 11] = [
+    ("lvn", || Pass::Lvn { fold: false }),
+    ("dce-global", || Pass::DceGlobal),
+    ("dce-local", || Pass::DceLocal),
+    ("tre", || Pass::Tre),
+    ("const-prop", || Pass::ConstProp),
+    ("gcse", || Pass::Gcse),
+    ("lcm", || Pass::Lcm),
+    ("mem-opt", || Pass::MemOpt),
+    ("strength", || Pass::Strength),
+    ("unroll", || {
+        let config = UnrollConfig::default();
+        Pass::Unroll {
+            factor: config.factor,
+            limit: config.size_limit,
+        }
+    }),
+    ("inline", || {
+        let config = InlineConfig::default();
+        Pass::Inline {
+            threshold: config.size_threshold,
+            depth: config.max_depth,
+        }
+    }),
+];
+
+fn number(name: &str, value: Option<&str>) -> Result<usize, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("option {name} should be given a number, like {name}=2"))
+}
+
+impl Pass {
+    // options are flags like `fold` or settings like `factor=2`
+    pub fn parse(name: &str, options: &[String]) -> Result<Pass, String> {
+        let mut pass = PASSES
+            .iter()
+            .find(|(pass_name, _)| *pass_name == name)
+            .map(|(_, pass)| pass())
+            .ok_or_else(|| format!("unknown pass {name}"))?;
+        for option in options {
+            let (key, value) = match option.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (option.as_str(), None),
+            };
+            match (&mut pass, key) {
+                (Pass::Lvn { fold }, "fold") if value.is_none() => *fold = true,
+                (Pass::Unroll { factor, .. }, "factor") => *factor = number(key, value)?,
+                (Pass::Unroll { limit, .. }, "limit") => *limit = number(key, value)?,
+                (Pass::Inline { threshold, .. }, "threshold") => *threshold = number(key, value)?,
+                (Pass::Inline { depth, .. }, "depth") => *depth = number(key, value)?,
+                _ => return Err(format!("{name} has no option {option}")),
+            }
+        }
+        Ok(pass)
+    }
+
+    fn run_function(&self, function: &mut Function, purity: &HashMap<String, Purity>) {
+        match *self {
+            Pass::Lvn { fold } => {
+                let mut blocks = get_basic_blocks(function);
+                for block in blocks.iter_mut() {
+                    lvn(block, fold, purity);
+                }
+                function.instrs = flatten(blocks);
+            }
+            Pass::DceGlobal => {
+                global_dce_pass(function, purity);
+            }
+            Pass::DceLocal => {
+                locally_killed_pass(function, purity);
+            }
+            Pass::Tre => {
+                eliminate_tail_recursion(function);
+            }
+            Pass::ConstProp => {
+                propagate_constants(function);
+            }
+            Pass::Gcse => {
+                eliminate_common_subexprs(function);
+            }
+            Pass::Lcm => {
+                lazy_code_motion(function);
+            }
+            Pass::MemOpt => {
+                optimize_memory(function);
+            }
+            Pass::Strength => {
+                reduce_strength(function);
+            }
+            Pass::Unroll { factor, limit } => {
+                let config = UnrollConfig {
+                    factor,
+                    size_limit: limit,
+                };
+                unroll_loops(function, &config);
+            }
+            Pass::Inline { .. } => unreachable!("inline should run on the whole program"),
+        }
+    }
+
+    pub fn run(&self, program: &mut Program) {
+        if let Pass::Inline { threshold, depth } = *self {
+            let config = InlineConfig {
+                size_threshold: threshold,
+                max_depth: depth,
+            };
+            inline_calls(program, &config);
+            return;
+        }
+        let purity = analyze_purity(program);
+        for function in program.functions.iter_mut() {
+            self.run_function(function, &purity);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    iter::Peekable,
+    str::Chars,
+    time::{Duration, Instant},
+};
+
+use bril_rs::{Code, Program};
+
+use crate::passes::Pass;
+
+// fixpoint groups give up after this many rounds, since a pass that keeps renaming never settles
+pub const MAX_ROUNDS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    // the pass and how it was written in the pipeline
+    Pass(String, Pass),
+    // runs its steps again and again until a round leaves the program unchanged
+    Fixpoint(Vec<Step>),
+}
+
+struct PipelineParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl PipelineParser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn word(&mut self) -> Result<String, String> {
+        self.peek();
+        let mut word = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '='))
+        {
+            word.push(c);
+        }
+        match self.peek() {
+            _ if !word.is_empty() => Ok(word),
+            Some(c) => Err(format!("expected a pass, found {c}")),
+            None => Err("expected a pass".to_string()),
+        }
+    }
+
+    fn steps(&mut self) -> Result<Vec<Step>, String> {
+        let mut steps = vec![self.step()?];
+        while self.eat(',') {
+            steps.push(self.step()?);
+        }
+        Ok(steps)
+    }
+
+    fn step(&mut self) -> Result<Step, String> {
+        let name = self.word()?;
+        if name == "fix" {
+            if !self.eat('(') {
+                return Err("fix should be followed by the passes to repeat".to_string());
+            }
+            let steps = self.steps()?;
+            if !self.eat(')') {
+                return Err("fix( is missing its )".to_string());
+            }
+            return Ok(Step::Fixpoint(steps));
+        }
+
+        let mut options = vec![];
+        if self.eat('(') {
+            options.push(self.word()?);
+            while self.eat(',') {
+                options.push(self.word()?);
+            }
+            if !self.eat(')') {
+                return Err(format!("{name}( is missing its )"));
+            }
+        }
+        let pass = Pass::parse(&name, &options)?;
+        let text = if options.is_empty() {
+            name
+        } else {
+            format!("{name}({})", options.join(","))
+        };
+        Ok(Step::Pass(text, pass))
+    }
+}
+
+// a comma-separated list of passes like `lvn(fold),fix(dce-global,dce-local)`
+pub fn parse_pipeline(text: &str) -> Result<Vec<Step>, String> {
+    let mut parser = PipelineParser {
+        chars: text.chars().peekable(),
+    };
+    let steps = parser.steps()?;
+    match parser.peek() {
+        None => Ok(steps),
+        Some(c) => Err(format!("unexpected {c} in the pipeline")),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassReport {
+    // the pass, after the rounds of the fixpoint groups it's in, like `[2] dce-global`
+    pub pass: String,
+    pub elapsed: Duration,
+    pub before: usize,
+    pub after: usize,
+}
+
+pub fn count_instrs(program: &Program) -> usize {
+    program
+        .functions
+        .iter()
+        .flat_map(|function| function.instrs.iter())
+        .filter(|code| matches!(code, Code::Instruction(_)))
+        .count()
+}
+
+fn run_steps(program: &mut Program, steps: &[Step], prefix: &str, reports: &mut Vec<PassReport>) {
+    for step in steps {
+        match step {
+            Step::Pass(text, pass) => {
+                let before = count_instrs(program);
+                let start = Instant::now();
+                pass.run(program);
+                reports.push(PassReport {
+                    pass: format!("{prefix}{text}"),
+                    elapsed: start.elapsed(),
+                    before,
+                    after: count_instrs(program),
+                });
+            }
+            Step::Fixpoint(steps) => {
+                for round in 1..=MAX_ROUNDS {
+                    let before = program.clone();
+                    run_steps(program, steps, &format!("{prefix}[{round}] "), reports);
+                    if *program == before {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// runs the steps in order, with a report for every pass that ran
+pub fn run_pipeline(program: &mut Program, steps: &[Step]) -> Vec<PassReport> {
+    let mut reports = vec![];
+    run_steps(program, steps, "", &mut reports);
+    reports
+}
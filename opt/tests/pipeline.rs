@@ -0,0 +1,45 @@
+use opt::{
+    passes::Pass,
+    pipeline::{Step, parse_pipeline},
+};
+
+#[test]
+fn parses_options_and_fixpoint_groups() {
+    let steps = parse_pipeline("lvn(fold), fix(dce-global,dce-local), unroll(factor=2)")
+        .expect("pipeline should parse");
+    assert_eq!(
+        steps,
+        vec![
+            Step::Pass("lvn(fold)".to_string(), Pass::Lvn { fold: true }),
+            Step::Fixpoint(vec![
+                Step::Pass("dce-global".to_string(), Pass::DceGlobal),
+                Step::Pass("dce-local".to_string(), Pass::DceLocal),
+            ]),
+            Step::Pass(
+                "unroll(factor=2)".to_string(),
+                Pass::Unroll {
+                    factor: 2,
+                    limit: 64
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn rejects_bad_pipelines() {
+    for pipeline in [
+        "",
+        "lvn,",
+        "nope",
+        "lvn(factor=2)",
+        "fix(lvn",
+        "fix",
+        "dce-global)",
+    ] {
+        assert!(
+            parse_pipeline(pipeline).is_err(),
+            "{pipeline:?} should be rejected"
+        );
+    }
+}
@@ -0,0 +1,17 @@
+use std::path::Path;
+
+use interp::turnt::check_suites;
+
+// the turnt suites in test/, run without the JavaScript Bril tools
+#[test]
+fn suites_match_snapshots() {
+    let bin_dir = Path::new(env!("CARGO_BIN_EXE_bril-opt"))
+        .parent()
+        .expect("binaries should be in a directory");
+    let report = check_suites(Path::new("test"), bin_dir);
+    for skipped in report.skipped.iter() {
+        eprintln!("skipped {skipped}");
+    }
+    assert!(report.passed > 0, "test/ should have snapshot tests");
+    assert!(report.failures.is_empty(), "{}", report.failures.join("\n"));
+}
@@ -1,15 +1,19 @@
 use bril_rs::{load_program, output_program};
-use task3::dce::{global_dce_pass, locally_killed_pass};
+use task3::{
+    dce::{global_dce_pass, locally_killed_pass},
+    purity::analyze_purity,
+};
 
 fn main() {
     let mut program = load_program();
+    let purity = analyze_purity(&program);
 
     let mut changing = true;
     while changing {
         changing = false;
         for function in program.functions.iter_mut() {
-            changing |= global_dce_pass(function);
-            changing |= locally_killed_pass(function);
+            changing |= global_dce_pass(function, &purity);
+            changing |= locally_killed_pass(function, &purity);
         }
     }
 
@@ -0,0 +1,25 @@
+use std::env::args;
+
+use bril_rs::{load_program, output_program};
+use task3::inline::{InlineConfig, inline_calls};
+
+fn flag_value(flag: &str) -> Option<usize> {
+    args()
+        .skip_while(|arg| arg != flag)
+        .nth(1)
+        .map(|value| value.parse().expect("flag value should be a number"))
+}
+
+fn main() {
+    let mut program = load_program();
+
+    let default = InlineConfig::default();
+    let config = InlineConfig {
+        size_threshold: flag_value("-t").unwrap_or(default.size_threshold),
+        max_depth: flag_value("-d").unwrap_or(default.max_depth),
+    };
+
+    inline_calls(&mut program, &config);
+
+    output_program(&program);
+}
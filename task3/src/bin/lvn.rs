@@ -1,10 +1,11 @@
 use std::env::args;
 
 use bril_rs::{load_program, output_program};
-use task3::{flatten, get_basic_blocks, lvn::lvn};
+use task3::{flatten, get_basic_blocks, lvn::lvn, purity::analyze_purity};
 
 fn main() {
     let mut program = load_program();
+    let purity = analyze_purity(&program);
 
     let constant_folding = args().any(|arg| arg == "-f");
 
@@ -12,7 +13,7 @@ fn main() {
         let mut basic_blocks = get_basic_blocks(function);
 
         for block in basic_blocks.iter_mut() {
-            lvn(block, constant_folding);
+            lvn(block, constant_folding, &purity);
         }
 
         function.instrs = flatten(basic_blocks);
@@ -0,0 +1,12 @@
+use bril_rs::{load_program, output_program};
+use task3::tail_recursion::eliminate_tail_recursion;
+
+fn main() {
+    let mut program = load_program();
+
+    for function in program.functions.iter_mut() {
+        eliminate_tail_recursion(function);
+    }
+
+    output_program(&program);
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use bril_rs::{Code, EffectOps, Function, Instruction, ValueOps};
+
+pub fn get_callees(function: &Function) -> Vec<String> {
+    let mut callees = Vec::new();
+
+    for code in function.instrs.iter() {
+        if let Code::Instruction(
+            Instruction::Value {
+                op: ValueOps::Call,
+                funcs,
+                ..
+            }
+            | Instruction::Effect {
+                op: EffectOps::Call,
+                funcs,
+                ..
+            },
+        ) = code
+        {
+            for func in funcs {
+                if !callees.contains(func) {
+                    callees.push(func.clone());
+                }
+            }
+        }
+    }
+
+    callees
+}
+
+// function index -> callee indices, calls to functions outside of `functions` are dropped
+pub fn form_call_graph(functions: &[Function]) -> Vec<Vec<usize>> {
+    let name2idx: HashMap<&str, usize> = functions
+        .iter()
+        .enumerate()
+        .map(|(i, function)| (function.name.as_str(), i))
+        .collect();
+
+    functions
+        .iter()
+        .map(|function| {
+            get_callees(function)
+                .iter()
+                .filter_map(|callee| name2idx.get(callee.as_str()).copied())
+                .collect()
+        })
+        .collect()
+}
+
+struct Tarjan<'a> {
+    graph: &'a Vec<Vec<usize>>,
+    index: Vec<Option<usize>>,
+    lowlink: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    counter: usize,
+    sccs: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, u: usize) {
+        self.index[u] = Some(self.counter);
+        self.lowlink[u] = self.counter;
+        self.counter += 1;
+        self.stack.push(u);
+        self.on_stack[u] = true;
+
+        for &v in self.graph[u].iter() {
+            match self.index[v] {
+                None => {
+                    self.visit(v);
+                    self.lowlink[u] = self.lowlink[u].min(self.lowlink[v]);
+                }
+                Some(idx) if self.on_stack[v] => {
+                    self.lowlink[u] = self.lowlink[u].min(idx);
+                }
+                _ => {}
+            }
+        }
+
+        if Some(self.lowlink[u]) == self.index[u] {
+            let mut scc = Vec::new();
+            while let Some(v) = self.stack.pop() {
+                self.on_stack[v] = false;
+                scc.push(v);
+                if v == u {
+                    break;
+                }
+            }
+            scc.sort();
+            self.sccs.push(scc);
+        }
+    }
+}
+
+// strongly connected components in reverse topological order, i.e. callees come before callers
+pub fn find_sccs(graph: &Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+    let n = graph.len();
+    let mut tarjan = Tarjan {
+        graph,
+        index: vec![None; n],
+        lowlink: vec![0; n],
+        on_stack: vec![false; n],
+        stack: Vec::new(),
+        counter: 0,
+        sccs: Vec::new(),
+    };
+
+    for u in 0..n {
+        if tarjan.index[u].is_none() {
+            tarjan.visit(u);
+        }
+    }
+
+    tarjan.sccs
+}
+
+pub fn is_recursive(scc: &[usize], graph: &[Vec<usize>]) -> bool {
+    scc.len() > 1 || graph[scc[0]].contains(&scc[0])
+}
@@ -1,9 +1,12 @@
-use crate::{flatten, get_basic_blocks};
+use crate::{
+    flatten, get_basic_blocks,
+    purity::{Purity, is_side_effect_free},
+};
 use std::collections::{HashMap, HashSet};
 
 use bril_rs::{Code, Function, Instruction};
 
-fn locally_killed_block(block: &mut Vec<Code>) -> bool {
+fn locally_killed_block(block: &mut Vec<Code>, purity: &HashMap<String, Purity>) -> bool {
     let original_len = block.len();
     let mut drop_indices = Vec::new();
     // var -> index
@@ -24,14 +27,17 @@ fn locally_killed_block(block: &mut Vec<Code>) -> bool {
                         last_assignment.remove(arg);
                     }
                 }
-                // can either drop / commit
+                // can either drop / commit, calls with side effects are never dropped
                 Instruction::Value { args, dest, .. } => {
                     for arg in args.iter() {
                         last_assignment.remove(arg);
                     }
-                    if let Some(prev_index) = last_assignment.insert(dest.clone(), i) {
+                    if let Some(prev_index) = last_assignment.remove(dest) {
                         drop_indices.push(prev_index)
                     }
+                    if is_side_effect_free(instr, purity) {
+                        last_assignment.insert(dest.clone(), i);
+                    }
                 }
             }
         }
@@ -47,12 +53,12 @@ fn locally_killed_block(block: &mut Vec<Code>) -> bool {
     block.len() != original_len
 }
 
-pub fn locally_killed_pass(function: &mut Function) -> bool {
+pub fn locally_killed_pass(function: &mut Function, purity: &HashMap<String, Purity>) -> bool {
     let mut changing = false;
 
     let mut blocks = get_basic_blocks(&function);
     for block in blocks.iter_mut() {
-        changing |= locally_killed_block(block);
+        changing |= locally_killed_block(block, purity);
     }
 
     function.instrs = flatten(blocks);
@@ -60,7 +66,7 @@ pub fn locally_killed_pass(function: &mut Function) -> bool {
     changing
 }
 
-pub fn global_dce_pass(function: &mut Function) -> bool {
+pub fn global_dce_pass(function: &mut Function, purity: &HashMap<String, Purity>) -> bool {
     let original_len = function.instrs.len();
     let mut used = HashSet::new();
 
@@ -75,11 +81,7 @@ pub fn global_dce_pass(function: &mut Function) -> bool {
     function.instrs.retain(|code| {
         if let Code::Instruction(instr) = code {
             if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = instr {
-                if used.contains(dest) {
-                    return true;
-                } else {
-                    return false;
-                }
+                return used.contains(dest) || !is_side_effect_free(instr, purity);
             }
         }
         return true;
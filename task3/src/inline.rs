@@ -0,0 +1,231 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::AtomicUsize,
+};
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Program, ValueOps};
+
+use crate::call_graph::{find_sccs, form_call_graph, is_recursive};
+
+pub struct InlineConfig {
+    // callees with more instructions than this are never inlined
+    pub size_threshold: usize,
+    // how many levels of calls inside an inlined body are inlined as well
+    pub max_depth: usize,
+}
+
+impl Default for InlineConfig {
+    fn default() -> Self {
+        InlineConfig {
+            size_threshold: 50,
+            max_depth: 3,
+        }
+    }
+}
+
+fn function_size(function: &Function) -> usize {
+    function
+        .instrs
+        .iter()
+        .filter(|code| matches!(code, Code::Instruction(_)))
+        .count()
+}
+
+// a name like `callee.3` that's unique to one inlined call site
+fn generate_stem(callee: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "{}.{}",
+        callee,
+        COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    )
+}
+
+fn rename(names: &[String], prefix: &str) -> Vec<String> {
+    names.iter().map(|name| format!("{prefix}{name}")).collect()
+}
+
+// callee body with every variable and label prefixed, and `ret` replaced by a copy into `dest`
+// plus a jump to the continuation label
+fn instantiate(
+    callee: &Function,
+    args: &[String],
+    dest: Option<&String>,
+    cont: &str,
+    prefix: &str,
+) -> Vec<Code> {
+    let mut instrs = Vec::new();
+
+    // bind arguments to parameters
+    for (param, arg) in callee.args.iter().zip(args) {
+        instrs.push(Code::Instruction(Instruction::Value {
+            args: vec![arg.clone()],
+            dest: format!("{prefix}{}", param.name),
+            funcs: vec![],
+            labels: vec![],
+            op: ValueOps::Id,
+            pos: None,
+            op_type: param.arg_type.clone(),
+        }));
+    }
+
+    let mut jumps_to_cont = false;
+    for (i, code) in callee.instrs.iter().enumerate() {
+        let is_last = i == callee.instrs.len() - 1;
+
+        match code {
+            Code::Label { label, pos } => instrs.push(Code::Label {
+                label: format!("{prefix}{label}"),
+                pos: pos.clone(),
+            }),
+            Code::Instruction(Instruction::Effect {
+                op: EffectOps::Return,
+                args: ret_args,
+                pos,
+                ..
+            }) => {
+                if let (Some(dest), Some(ret_arg), Some(ret_type)) =
+                    (dest, ret_args.first(), &callee.return_type)
+                {
+                    instrs.push(Code::Instruction(Instruction::Value {
+                        args: vec![format!("{prefix}{ret_arg}")],
+                        dest: dest.clone(),
+                        funcs: vec![],
+                        labels: vec![],
+                        op: ValueOps::Id,
+                        pos: pos.clone(),
+                        op_type: ret_type.clone(),
+                    }));
+                }
+                // a `ret` at the very end simply falls through into the continuation
+                if !is_last {
+                    jumps_to_cont = true;
+                    instrs.push(Code::Instruction(Instruction::Effect {
+                        args: vec![],
+                        funcs: vec![],
+                        labels: vec![cont.to_string()],
+                        op: EffectOps::Jump,
+                        pos: pos.clone(),
+                    }));
+                }
+            }
+            Code::Instruction(instr) => {
+                let mut instr = instr.clone();
+                match &mut instr {
+                    Instruction::Constant { dest, .. } => {
+                        *dest = format!("{prefix}{dest}");
+                    }
+                    Instruction::Value {
+                        args, dest, labels, ..
+                    } => {
+                        *args = rename(args, prefix);
+                        *dest = format!("{prefix}{dest}");
+                        *labels = rename(labels, prefix);
+                    }
+                    Instruction::Effect { args, labels, .. } => {
+                        *args = rename(args, prefix);
+                        *labels = rename(labels, prefix);
+                    }
+                }
+                instrs.push(Code::Instruction(instr));
+            }
+        }
+    }
+
+    if jumps_to_cont {
+        instrs.push(Code::Label {
+            label: cont.to_string(),
+            pos: None,
+        });
+    }
+
+    instrs
+}
+
+struct Inliner<'a> {
+    functions: HashMap<&'a str, &'a Function>,
+    // callees that are small enough and not part of a recursive SCC
+    inlinable: HashSet<&'a str>,
+    config: &'a InlineConfig,
+    inlined: usize,
+}
+
+impl Inliner<'_> {
+    fn inline_instrs(&mut self, instrs: &[Code], depth: usize) -> Vec<Code> {
+        let mut output = Vec::new();
+
+        for code in instrs {
+            let call = match code {
+                Code::Instruction(Instruction::Value {
+                    op: ValueOps::Call,
+                    args,
+                    dest,
+                    funcs,
+                    ..
+                }) => Some((funcs, args, Some(dest))),
+                Code::Instruction(Instruction::Effect {
+                    op: EffectOps::Call,
+                    args,
+                    funcs,
+                    ..
+                }) => Some((funcs, args, None)),
+                _ => None,
+            };
+
+            match call {
+                Some((funcs, args, dest))
+                    if depth < self.config.max_depth
+                        && funcs.len() == 1
+                        && self.inlinable.contains(funcs[0].as_str()) =>
+                {
+                    let callee = self.functions[funcs[0].as_str()];
+                    let stem = generate_stem(&callee.name);
+                    let prefix = format!("{stem}.");
+                    // renamed callee labels always have a `.` after the stem, so this can't
+                    // clash with a callee label like `.ret`
+                    let cont = format!("{stem}_ret");
+
+                    let body = instantiate(callee, args, dest, &cont, &prefix);
+                    self.inlined += 1;
+
+                    output.extend(self.inline_instrs(&body, depth + 1));
+                }
+                _ => output.push(code.clone()),
+            }
+        }
+
+        output
+    }
+}
+
+// returns the number of inlined call sites
+pub fn inline_calls(program: &mut Program, config: &InlineConfig) -> usize {
+    let original = program.functions.clone();
+    let call_graph = form_call_graph(&original);
+
+    let mut inlinable = HashSet::new();
+    for scc in find_sccs(&call_graph) {
+        if !is_recursive(&scc, &call_graph) {
+            let function = &original[scc[0]];
+            if function_size(function) <= config.size_threshold {
+                inlinable.insert(function.name.as_str());
+            }
+        }
+    }
+
+    let mut inliner = Inliner {
+        functions: original
+            .iter()
+            .map(|function| (function.name.as_str(), function))
+            .collect(),
+        inlinable,
+        config,
+        inlined: 0,
+    };
+
+    for function in program.functions.iter_mut() {
+        function.instrs = inliner.inline_instrs(&function.instrs, 0);
+    }
+
+    inliner.inlined
+}
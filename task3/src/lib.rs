@@ -1,7 +1,11 @@
 use bril_rs::{Code, EffectOps, Function, Instruction};
 
+pub mod call_graph;
 pub mod dce;
+pub mod inline;
 pub mod lvn;
+pub mod purity;
+pub mod tail_recursion;
 
 pub fn flatten(blocks: Vec<Vec<Code>>) -> Vec<Code> {
     let mut instrs = Vec::new();
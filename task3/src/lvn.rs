@@ -5,19 +5,14 @@ use std::{
 
 use bril_rs::{Code, ConstOps, Instruction, Literal, Type, ValueOps};
 
+use crate::purity::{Purity, can_reuse};
+
 #[derive(PartialEq, Clone, Debug)]
 enum Value {
     Const(Type, Literal),
     External(String),
     ValueOp(ValueOps, Vec<usize>),
-}
-
-fn can_reuse(op: ValueOps) -> bool {
-    use ValueOps::*;
-    match op {
-        Call | Get | Alloc | Load | PtrAdd => false,
-        _ => true,
-    }
+    Call(Vec<String>, Vec<usize>),
 }
 
 fn generate_var_name(original_name: &str) -> String {
@@ -122,7 +117,7 @@ fn fold_constant(
     }
 }
 
-pub fn lvn(block: &mut Vec<Code>, constant_folding: bool) {
+pub fn lvn(block: &mut Vec<Code>, constant_folding: bool, purity: &HashMap<String, Purity>) {
     let mut table: Vec<(Value, String)> = Vec::new();
     let mut var2idx: HashMap<String, usize> = HashMap::new();
 
@@ -192,21 +187,23 @@ pub fn lvn(block: &mut Vec<Code>, constant_folding: bool) {
                 Instruction::Value {
                     args,
                     dest,
+                    funcs,
                     op,
                     op_type,
                     ..
                 } => {
+                    let args_idx = args
+                        .iter()
+                        .map(|arg| *var2idx.get(arg).unwrap())
+                        .collect::<Vec<_>>();
                     let instr_value = if let Some(val) = fold_constant(*op, args, &var2idx, &table)
                         && constant_folding
                     {
                         val
+                    } else if *op == ValueOps::Call {
+                        Value::Call(funcs.clone(), args_idx)
                     } else {
-                        Value::ValueOp(
-                            op.clone(),
-                            args.iter()
-                                .map(|arg| var2idx.get(arg).unwrap().clone())
-                                .collect(),
-                        )
+                        Value::ValueOp(op.clone(), args_idx)
                     };
 
                     if let Value::Const(_, literal) = &instr_value {
@@ -228,7 +225,8 @@ pub fn lvn(block: &mut Vec<Code>, constant_folding: bool) {
                         .iter()
                         .enumerate()
                         .find(|(_, (val, _))| *val == instr_value)
-                        && (can_reuse(*op) || matches!(instr_value, Value::Const(..)))
+                        && (can_reuse(*op, funcs, purity)
+                            || matches!(instr_value, Value::Const(..)))
                     {
                         updates.push((
                             idx_instr,
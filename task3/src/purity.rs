@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Program, ValueOps};
+
+use crate::call_graph::{find_sccs, form_call_graph, is_recursive};
+
+// ordered from the most to the least optimizable, so combining two classifications is `max`
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Purity {
+    // no print, store, alloc, free or load, and always terminates
+    Pure,
+    // like `Pure`, but may load from memory
+    ReadOnly,
+    Effectful,
+}
+
+// a backward jump may form a loop, so we can't tell whether the function terminates
+fn may_loop(function: &Function) -> bool {
+    let mut label_idx = HashMap::new();
+    for (i, code) in function.instrs.iter().enumerate() {
+        if let Code::Label { label, .. } = code {
+            label_idx.insert(label.as_str(), i);
+        }
+    }
+
+    function.instrs.iter().enumerate().any(|(i, code)| {
+        if let Code::Instruction(Instruction::Effect {
+            op: EffectOps::Jump | EffectOps::Branch,
+            labels,
+            ..
+        }) = code
+        {
+            labels
+                .iter()
+                .any(|label| label_idx.get(label.as_str()).is_none_or(|&j| j <= i))
+        } else {
+            false
+        }
+    })
+}
+
+fn callee_purity(funcs: &[String], purity: &HashMap<String, Purity>) -> Purity {
+    funcs
+        .iter()
+        .map(|func| *purity.get(func).unwrap_or(&Purity::Effectful))
+        .max()
+        .unwrap_or(Purity::Effectful)
+}
+
+// purity of the function body, assuming `purity` already classifies every callee
+fn function_purity(function: &Function, purity: &HashMap<String, Purity>) -> Purity {
+    if may_loop(function) {
+        return Purity::Effectful;
+    }
+
+    let mut result = Purity::Pure;
+
+    for code in function.instrs.iter() {
+        if let Code::Instruction(instr) = code {
+            let instr_purity = match instr {
+                Instruction::Constant { .. } => Purity::Pure,
+                Instruction::Value { op, funcs, .. } => match op {
+                    ValueOps::Call => callee_purity(funcs, purity),
+                    ValueOps::Load => Purity::ReadOnly,
+                    ValueOps::Alloc => Purity::Effectful,
+                    _ => Purity::Pure,
+                },
+                Instruction::Effect { op, funcs, .. } => match op {
+                    EffectOps::Call => callee_purity(funcs, purity),
+                    EffectOps::Jump
+                    | EffectOps::Branch
+                    | EffectOps::Return
+                    | EffectOps::Nop
+                    | EffectOps::Set => Purity::Pure,
+                    _ => Purity::Effectful,
+                },
+            };
+
+            result = result.max(instr_purity);
+        }
+    }
+
+    result
+}
+
+// bottom-up over the call graph, functions in a recursive SCC may not terminate
+pub fn analyze_purity(program: &Program) -> HashMap<String, Purity> {
+    let call_graph = form_call_graph(&program.functions);
+    let mut purity = HashMap::new();
+
+    for scc in find_sccs(&call_graph) {
+        if is_recursive(&scc, &call_graph) {
+            for f in scc {
+                purity.insert(program.functions[f].name.clone(), Purity::Effectful);
+            }
+        } else {
+            let function = &program.functions[scc[0]];
+            let function_purity = function_purity(function, &purity);
+            purity.insert(function.name.clone(), function_purity);
+        }
+    }
+
+    purity
+}
+
+// whether an earlier result of the same operation on the same values can stand in for this one,
+// calls only qualify when every callee is pure
+// `undef` never does, since a copy of an undefined variable is an error
+pub fn can_reuse(op: ValueOps, funcs: &[String], purity: &HashMap<String, Purity>) -> bool {
+    use ValueOps::*;
+    match op {
+        Call => funcs
+            .iter()
+            .all(|func| purity.get(func) == Some(&Purity::Pure)),
+        Get | Alloc | Load | PtrAdd | Undef => false,
+        _ => true,
+    }
+}
+
+// whether an instruction whose result is never used can be dropped
+pub fn is_side_effect_free(instr: &Instruction, purity: &HashMap<String, Purity>) -> bool {
+    match instr {
+        Instruction::Constant { .. } => true,
+        Instruction::Value {
+            op: ValueOps::Call,
+            funcs,
+            ..
+        } => callee_purity(funcs, purity) != Purity::Effectful,
+        Instruction::Value { .. } => true,
+        Instruction::Effect { .. } => false,
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+
+use bril_rs::{Code, EffectOps, Function, Instruction, ValueOps};
+
+fn fresh_name(base: &str, used: &mut HashSet<String>) -> String {
+    let mut name = base.to_string();
+    let mut i = 0;
+    while used.contains(&name) {
+        name = format!("{base}.{i}");
+        i += 1;
+    }
+    used.insert(name.clone());
+    name
+}
+
+fn get_names(function: &Function) -> (HashSet<String>, HashSet<String>) {
+    let mut vars: HashSet<String> = function.args.iter().map(|arg| arg.name.clone()).collect();
+    let mut labels = HashSet::new();
+
+    for code in function.instrs.iter() {
+        match code {
+            Code::Label { label, .. } => {
+                labels.insert(label.clone());
+            }
+            Code::Instruction(Instruction::Constant { dest, .. }) => {
+                vars.insert(dest.clone());
+            }
+            Code::Instruction(Instruction::Value { args, dest, .. }) => {
+                vars.insert(dest.clone());
+                vars.extend(args.iter().cloned());
+            }
+            Code::Instruction(Instruction::Effect { args, .. }) => {
+                vars.extend(args.iter().cloned());
+            }
+        }
+    }
+
+    (vars, labels)
+}
+
+// arguments of a self call at `idx` that is immediately followed by a `ret` of its result
+fn tail_call_args(function: &Function, idx: usize) -> Option<&Vec<String>> {
+    let next = function.instrs.get(idx + 1)?;
+
+    match (&function.instrs[idx], next) {
+        (
+            Code::Instruction(Instruction::Value {
+                op: ValueOps::Call,
+                args,
+                dest,
+                funcs,
+                ..
+            }),
+            Code::Instruction(Instruction::Effect {
+                op: EffectOps::Return,
+                args: ret_args,
+                ..
+            }),
+        ) if funcs == std::slice::from_ref(&function.name)
+            && ret_args == std::slice::from_ref(dest) =>
+        {
+            Some(args)
+        }
+        (
+            Code::Instruction(Instruction::Effect {
+                op: EffectOps::Call,
+                args,
+                funcs,
+                ..
+            }),
+            Code::Instruction(Instruction::Effect {
+                op: EffectOps::Return,
+                args: ret_args,
+                ..
+            }),
+        ) if funcs == std::slice::from_ref(&function.name) && ret_args.is_empty() => Some(args),
+        _ => None,
+    }
+}
+
+// rewrites self calls in tail position into parameter reassignment plus a jump back to a loop
+// header placed right after the entry
+pub fn eliminate_tail_recursion(function: &mut Function) -> bool {
+    let tail_calls: Vec<usize> = (0..function.instrs.len())
+        .filter(|&i| tail_call_args(function, i).is_some())
+        .collect();
+
+    if tail_calls.is_empty() {
+        return false;
+    }
+
+    let (mut vars, mut labels) = get_names(function);
+    // a separate entry block keeps the entry free of predecessors
+    let entry = fresh_name(&format!("{}.entry", function.name), &mut labels);
+    let header = fresh_name(&format!("{}.tail", function.name), &mut labels);
+
+    let mut instrs = vec![
+        Code::Label {
+            label: entry,
+            pos: None,
+        },
+        Code::Label {
+            label: header.clone(),
+            pos: None,
+        },
+    ];
+
+    let mut i = 0;
+    while i < function.instrs.len() {
+        if !tail_calls.contains(&i) {
+            instrs.push(function.instrs[i].clone());
+            i += 1;
+            continue;
+        }
+
+        let args = tail_call_args(function, i).expect("i should be a tail call");
+        let pos = match &function.instrs[i] {
+            Code::Instruction(instr) => instr.get_pos(),
+            Code::Label { .. } => None,
+        };
+
+        // arguments may refer to parameters, so copy all of them before reassigning any
+        let temps: Vec<String> = function
+            .args
+            .iter()
+            .map(|param| fresh_name(&format!("{}.tail", param.name), &mut vars))
+            .collect();
+
+        for ((param, arg), temp) in function.args.iter().zip(args).zip(temps.iter()) {
+            instrs.push(Code::Instruction(Instruction::Value {
+                args: vec![arg.clone()],
+                dest: temp.clone(),
+                funcs: vec![],
+                labels: vec![],
+                op: ValueOps::Id,
+                pos: pos.clone(),
+                op_type: param.arg_type.clone(),
+            }));
+        }
+        for (param, temp) in function.args.iter().zip(temps) {
+            instrs.push(Code::Instruction(Instruction::Value {
+                args: vec![temp],
+                dest: param.name.clone(),
+                funcs: vec![],
+                labels: vec![],
+                op: ValueOps::Id,
+                pos: pos.clone(),
+                op_type: param.arg_type.clone(),
+            }));
+        }
+        instrs.push(Code::Instruction(Instruction::Effect {
+            args: vec![],
+            funcs: vec![],
+            labels: vec![header.clone()],
+            op: EffectOps::Jump,
+            pos,
+        }));
+
+        // skip the call and its `ret`
+        i += 2;
+    }
+
+    function.instrs = instrs;
+
+    true
+}
@@ -0,0 +1,53 @@
+use std::env::args;
+
+use bril_rs::{load_program, output_program};
+use task4::regalloc::{AllocationError, allocate_registers};
+
+fn flag_value(flag: &str) -> Option<usize> {
+    args()
+        .skip_while(|arg| arg != flag)
+        .nth(1)
+        .map(|value| value.parse().expect("flag value should be a number"))
+}
+
+// allocates the variables of every function to `-k` registers, 8 by default
+// with `-r`, prints a report of each function instead of the program
+fn main() {
+    let mut program = load_program();
+
+    let k = flag_value("-k").unwrap_or(8);
+    let show_report = args().any(|arg| arg == "-r");
+
+    for function in program.functions.iter_mut() {
+        let report = allocate_registers(function, k);
+        if !show_report {
+            continue;
+        }
+        println!("@{}", function.name);
+        let report = match report {
+            Ok(report) => report,
+            Err(AllocationError::UsesShadows) => {
+                println!("  not allocated: uses get and set");
+                continue;
+            }
+            Err(AllocationError::TooFewRegisters) => {
+                println!("  not allocated: an instruction needs more than {k} registers");
+                continue;
+            }
+        };
+        println!("  variables: {}", report.variables);
+        println!("  max pressure: {}", report.max_pressure);
+        println!("  registers used: {} of {k}", report.used);
+        println!("  copies coalesced: {}", report.coalesced);
+        if report.spilled.is_empty() {
+            println!("  spilled: none");
+        } else {
+            println!("  spilled: {}", report.spilled.join(", "));
+        }
+        println!("  spill loads: {}, stores: {}", report.loads, report.stores);
+    }
+
+    if !show_report {
+        output_program(&program);
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::Hash,
+    time::Instant,
+};
+
+use crate::{Solution, SolverStats, solve_order};
+
+// task4/src/bitvec.rs and task6/src/bitvec.rs are deliberately the same file, since the crates
+// don't share code; they only differ in the CFG type, a map of block -> neighbours in task4 and
+// a vec in task6
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BitSet {
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new(len: usize) -> Self {
+        BitSet {
+            len,
+            words: vec![0; len.div_ceil(WORD_BITS)],
+        }
+    }
+
+    pub fn full(len: usize) -> Self {
+        let mut set = BitSet {
+            len,
+            words: vec![u64::MAX; len.div_ceil(WORD_BITS)],
+        };
+        // keep the unused high bits clear so that equality stays word-wise
+        if !len.is_multiple_of(WORD_BITS) {
+            *set.words.last_mut().unwrap() = (1 << (len % WORD_BITS)) - 1;
+        }
+        set
+    }
+
+    pub fn insert(&mut self, i: usize) {
+        self.words[i / WORD_BITS] |= 1 << (i % WORD_BITS);
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        self.words[i / WORD_BITS] &= !(1 << (i % WORD_BITS));
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.words[i / WORD_BITS] & (1 << (i % WORD_BITS)) != 0
+    }
+
+    pub fn union_with(&mut self, other: &BitSet) {
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a |= b;
+        }
+    }
+
+    pub fn intersect_with(&mut self, other: &BitSet) {
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= b;
+        }
+    }
+
+    pub fn subtract(&mut self, other: &BitSet) {
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= !b;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(|&i| self.contains(i))
+    }
+}
+
+// numbers values densely in order of first appearance
+pub struct Interner<T> {
+    values: Vec<T>,
+    ids: HashMap<T, usize>,
+}
+
+impl<T: Eq + Hash + Clone> Interner<T> {
+    pub fn new() -> Self {
+        Interner {
+            values: Vec::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    pub fn intern(&mut self, value: &T) -> usize {
+        if let Some(&id) = self.ids.get(value) {
+            return id;
+        }
+        self.values.push(value.clone());
+        self.ids.insert(value.clone(), self.values.len() - 1);
+        self.values.len() - 1
+    }
+
+    pub fn get(&self, value: &T) -> Option<usize> {
+        self.ids.get(value).copied()
+    }
+
+    pub fn value(&self, id: usize) -> &T {
+        &self.values[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<T: Eq + Hash + Clone> Default for Interner<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// out = gen U (in - kill) for forward problems, in = gen U (out - kill) for backward ones
+pub struct BitVectorProblem {
+    pub forward: bool,
+    // merge with union if true, with intersection otherwise
+    pub may: bool,
+    pub width: usize,
+    pub gens: Vec<BitSet>,
+    pub kills: Vec<BitSet>,
+    // value flowing into the entry block (forward) or out of the exit blocks (backward)
+    pub boundary: BitSet,
+}
+
+impl BitVectorProblem {
+    fn merge(&self, inputs: &[&BitSet]) -> BitSet {
+        let mut merged = if self.may {
+            BitSet::new(self.width)
+        } else {
+            BitSet::full(self.width)
+        };
+        for input in inputs {
+            if self.may {
+                merged.union_with(input);
+            } else {
+                merged.intersect_with(input);
+            }
+        }
+        merged
+    }
+
+    fn transfer(&self, b: usize, input: &BitSet) -> BitSet {
+        let mut output = input.clone();
+        output.subtract(&self.kills[b]);
+        output.union_with(&self.gens[b]);
+        output
+    }
+
+    // returns in, out
+    pub fn solve(
+        &self,
+        pred: &HashMap<usize, Vec<usize>>,
+        succ: &HashMap<usize, Vec<usize>>,
+    ) -> (Solution<BitSet>, SolverStats) {
+        let start = Instant::now();
+        let mut stats = SolverStats::default();
+
+        let n = self.gens.len();
+        let initial = if self.may {
+            BitSet::new(self.width)
+        } else {
+            BitSet::full(self.width)
+        };
+        let mut in_ = vec![initial.clone(); n];
+        let mut out = vec![initial; n];
+
+        let order = solve_order(succ, self.forward);
+        let mut priority = vec![0; n];
+        for (p, &b) in order.iter().enumerate() {
+            priority[b] = p;
+        }
+        let mut worklist: BTreeSet<usize> = (0..n).collect();
+        let mut last = None;
+
+        while let Some(p) = worklist.pop_first() {
+            let b = order[p];
+            if last.is_none_or(|last| p <= last) {
+                stats.iterations += 1;
+            }
+            last = Some(p);
+            stats.transfer_calls += 1;
+
+            let (sources, targets, before, after) = if self.forward {
+                (&pred[&b], &succ[&b], &mut in_, &mut out)
+            } else {
+                (&succ[&b], &pred[&b], &mut out, &mut in_)
+            };
+
+            let mut inputs: Vec<&BitSet> = sources.iter().map(|s| &after[*s]).collect();
+            if (self.forward && b == 0) || (!self.forward && sources.is_empty()) {
+                inputs.push(&self.boundary);
+            }
+            before[b] = self.merge(&inputs);
+
+            let result = self.transfer(b, &before[b]);
+            if after[b] != result {
+                worklist.extend(targets.iter().map(|t| priority[*t]));
+            }
+            after[b] = result;
+        }
+
+        stats.time = start.elapsed();
+        ((in_, out), stats)
+    }
+}
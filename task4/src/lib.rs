@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::{Duration, Instant},
+};
 
 use bril_rs::{Code, EffectOps, Function, Instruction};
 
+use crate::lattice::Lattice;
+
 fn find_block_ids_with_labels(blocks: &Vec<Vec<Code>>, labels: &Vec<String>) -> Vec<usize> {
     blocks
         .iter()
@@ -98,64 +103,253 @@ pub fn get_basic_blocks(function: &Function) -> Vec<Vec<Code>> {
     basic_blocks
 }
 
+fn postorder(
+    u: usize,
+    graph: &HashMap<usize, Vec<usize>>,
+    visited: &mut Vec<bool>,
+    order: &mut Vec<usize>,
+) {
+    visited[u] = true;
+    for v in graph.get(&u).expect("u should be in graph").iter() {
+        if !visited[*v] {
+            postorder(*v, graph, visited, order);
+        }
+    }
+    order.push(u);
+}
+
+// reverse postorder for forward analyses, postorder for backward ones, unreachable blocks last
+pub(crate) fn solve_order(succ: &HashMap<usize, Vec<usize>>, forward: bool) -> Vec<usize> {
+    let mut order = vec![];
+    let mut visited = vec![false; succ.len()];
+    for u in 0..succ.len() {
+        if !visited[u] {
+            let mut component = vec![];
+            postorder(u, succ, &mut visited, &mut component);
+            if forward {
+                component.reverse();
+            }
+            order.extend(component);
+        }
+    }
+    order
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct SolverStats {
+    // sweeps over the blocks in priority order
+    pub iterations: usize,
+    pub transfer_calls: usize,
+    pub time: Duration,
+}
+
+// in and out states of every block
+pub type Solution<S> = (Vec<S>, Vec<S>);
+
 pub trait DataFlowAnalysis {
     const FORWARD: bool;
-    type State: Clone + PartialEq;
+    // may-analyses merge with join and rise from bottom,
+    // must-analyses merge with meet and fall from top
+    const MAY: bool = true;
+    type State: Lattice;
 
-    fn merge(inputs: &Vec<&Self::State>) -> Self::State;
+    fn merge(inputs: &Vec<&Self::State>) -> Self::State {
+        if Self::MAY {
+            inputs
+                .iter()
+                .fold(Self::State::bottom(), |accum, input| accum.join(input))
+        } else {
+            inputs
+                .iter()
+                .fold(Self::State::top(), |accum, input| accum.meet(input))
+        }
+    }
     fn transfer(block: &Vec<Code>, input: &Self::State) -> Self::State;
-    fn inital_state() -> Self::State;
+    // transfer over a single instruction, by default the block transfer of a one-instruction block
+    fn transfer_instr(code: &Code, input: &Self::State) -> Self::State {
+        Self::transfer(&vec![code.clone()], input)
+    }
+    // value flowing into the entry block (forward) or out of the exit blocks (backward)
+    fn boundary_state() -> Self::State;
+    // value every block starts from before the first iteration
+    fn initial_state() -> Self::State {
+        if Self::MAY {
+            Self::State::bottom()
+        } else {
+            Self::State::top()
+        }
+    }
     fn workman(
         blocks: &Vec<Vec<Code>>,
         pred: &HashMap<usize, Vec<usize>>,
         succ: &HashMap<usize, Vec<usize>>,
-    ) -> (Vec<Self::State>, Vec<Self::State>) {
-        let mut in_: Vec<Self::State> = vec![Self::inital_state(); blocks.len()];
-        let mut out: Vec<Self::State> = vec![Self::inital_state(); blocks.len()];
-        let mut worklist: Vec<_> = (0..blocks.len()).collect();
+    ) -> Solution<Self::State> {
+        Self::workman_with_stats(blocks, pred, succ).0
+    }
+    fn workman_with_stats(
+        blocks: &Vec<Vec<Code>>,
+        pred: &HashMap<usize, Vec<usize>>,
+        succ: &HashMap<usize, Vec<usize>>,
+    ) -> (Solution<Self::State>, SolverStats) {
+        let start = Instant::now();
+        let mut stats = SolverStats::default();
 
-        while !worklist.is_empty() {
-            let b = worklist.pop().expect("worklist shouldn't be empty");
+        let mut in_: Vec<Self::State> = vec![Self::initial_state(); blocks.len()];
+        let mut out: Vec<Self::State> = vec![Self::initial_state(); blocks.len()];
+        let boundary = Self::boundary_state();
+
+        // the worklist holds priorities, so every block is queued at most once and the block
+        // earliest in the order is always visited first
+        let order = solve_order(succ, Self::FORWARD);
+        let mut priority = vec![0; blocks.len()];
+        for (p, &b) in order.iter().enumerate() {
+            priority[b] = p;
+        }
+        let mut worklist: BTreeSet<usize> = (0..blocks.len()).collect();
+        let mut last = None;
+
+        while let Some(p) = worklist.pop_first() {
+            let b = order[p];
+            if last.is_none_or(|last| p <= last) {
+                stats.iterations += 1;
+            }
+            last = Some(p);
+            stats.transfer_calls += 1;
 
             if Self::FORWARD {
-                let out_preds = pred
+                let mut out_preds = pred
                     .get(&b)
                     .expect("b should be in pred")
                     .iter()
                     .map(|s| out.get(*s).expect("s should be in out"))
                     .collect::<Vec<_>>();
+                if b == 0 {
+                    out_preds.push(&boundary);
+                }
                 in_[b] = Self::merge(&out_preds);
 
                 let out_b = Self::transfer(
                     blocks.get(b).expect("b should be in blocks"),
                     in_.get(b).expect("b should be in in_"),
                 );
+                debug_assert_monotone::<Self>(&out[b], &out_b);
                 if out[b] != out_b {
-                    worklist.extend(succ.get(&b).expect("b should be in succ"));
+                    worklist.extend(
+                        succ.get(&b)
+                            .expect("b should be in succ")
+                            .iter()
+                            .map(|s| priority[*s]),
+                    );
                 }
                 out[b] = out_b;
             } else {
-                let in_succs = succ
+                let mut in_succs = succ
                     .get(&b)
                     .expect("b should be in succ")
                     .iter()
                     .map(|s| in_.get(*s).expect("s should be in in_"))
                     .collect::<Vec<_>>();
+                if in_succs.is_empty() {
+                    in_succs.push(&boundary);
+                }
                 out[b] = Self::merge(&in_succs);
 
                 let in_b = Self::transfer(
                     blocks.get(b).expect("b should be in blocks"),
                     out.get(b).expect("b should be in out"),
                 );
+                debug_assert_monotone::<Self>(&in_[b], &in_b);
                 if in_[b] != in_b {
-                    worklist.extend(pred.get(&b).expect("b should be in pred"));
+                    worklist.extend(
+                        pred.get(&b)
+                            .expect("b should be in pred")
+                            .iter()
+                            .map(|p| priority[*p]),
+                    );
                 }
                 in_[b] = in_b;
             }
         }
 
-        (in_, out)
+        stats.time = start.elapsed();
+        ((in_, out), stats)
+    }
+
+    // facts at every program point, computed from the solved in/out by replaying transfer_instr
+    // block -> point i is right before instruction i, the last point is right after the block
+    fn program_points(
+        blocks: &Vec<Vec<Code>>,
+        in_: &Vec<Self::State>,
+        out: &Vec<Self::State>,
+    ) -> Vec<Vec<Self::State>> {
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(b, block)| {
+                if Self::FORWARD {
+                    let mut points = vec![in_[b].clone()];
+                    for code in block {
+                        let after = Self::transfer_instr(code, points.last().unwrap());
+                        points.push(after);
+                    }
+                    points
+                } else {
+                    let mut points = vec![out[b].clone()];
+                    for code in block.iter().rev() {
+                        let before = Self::transfer_instr(code, points.last().unwrap());
+                        points.push(before);
+                    }
+                    points.reverse();
+                    points
+                }
+            })
+            .collect()
+    }
+}
+
+// prints the blocks with the fact computed by each instruction as a trailing comment,
+// i.e. the fact after it for forward analyses and before it for backward ones
+pub fn print_annotated<A: DataFlowAnalysis>(
+    blocks: &[Vec<Code>],
+    points: &[Vec<A::State>],
+    fmt: impl Fn(&A::State) -> String,
+) {
+    for (block, points) in blocks.iter().zip(points) {
+        let mut codes = block.iter().enumerate().peekable();
+
+        if let Some((_, label @ Code::Label { .. })) = codes.peek() {
+            println!("{label}");
+            codes.next();
+        }
+        if A::FORWARD {
+            println!("  # in: {}", fmt(&points[0]));
+        }
+
+        for (i, code) in codes {
+            let fact = if A::FORWARD {
+                &points[i + 1]
+            } else {
+                &points[i]
+            };
+            println!("{code}  # {}", fmt(fact));
+        }
+
+        if !A::FORWARD {
+            println!("  # out: {}", fmt(points.last().unwrap()));
+        }
     }
 }
 
+// starting from the initial state, a monotone transfer only ever moves a block's result in the
+// direction of the merge
+fn debug_assert_monotone<A: DataFlowAnalysis + ?Sized>(old: &A::State, new: &A::State) {
+    debug_assert!(
+        if A::MAY { old.leq(new) } else { new.leq(old) },
+        "transfer function should be monotone"
+    );
+}
+
+pub mod bitvec;
+pub mod lattice;
 pub mod live_variables;
+pub mod regalloc;
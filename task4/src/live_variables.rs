@@ -1,23 +1,20 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use bril_rs::{Code, Instruction};
 
-use crate::DataFlowAnalysis;
+use crate::{
+    DataFlowAnalysis, Solution, SolverStats,
+    bitvec::{BitSet, BitVectorProblem, Interner},
+    lattice::{Lattice, Powerset},
+};
 
 pub struct LiveVariables;
 
 impl DataFlowAnalysis for LiveVariables {
     const FORWARD: bool = false;
 
-    type State = HashSet<String>;
-
-    fn merge(inputs: &Vec<&Self::State>) -> Self::State {
-        let mut merged = HashSet::new();
-        for i in inputs {
-            merged.extend(i.iter().cloned());
-        }
-        merged
-    }
+    // merge = union
+    type State = Powerset<String>;
 
     fn transfer(block: &Vec<Code>, out: &Self::State) -> Self::State {
         let mut in_ = out.clone();
@@ -42,7 +39,89 @@ impl DataFlowAnalysis for LiveVariables {
         in_
     }
 
-    fn inital_state() -> Self::State {
-        HashSet::new()
+    fn boundary_state() -> Self::State {
+        Self::State::bottom()
+    }
+}
+
+// bit-vector version of LiveVariables, same results
+pub fn live_variables(
+    blocks: &Vec<Vec<Code>>,
+    pred: &HashMap<usize, Vec<usize>>,
+    succ: &HashMap<usize, Vec<usize>>,
+) -> (Solution<Powerset<String>>, SolverStats) {
+    let mut vars = Interner::new();
+    for block in blocks {
+        for code in block {
+            if let Code::Instruction(instr) = code {
+                match instr {
+                    Instruction::Constant { dest, .. } => {
+                        vars.intern(dest);
+                    }
+                    Instruction::Effect { args, .. } => {
+                        args.iter().for_each(|arg| {
+                            vars.intern(arg);
+                        });
+                    }
+                    Instruction::Value { args, dest, .. } => {
+                        vars.intern(dest);
+                        args.iter().for_each(|arg| {
+                            vars.intern(arg);
+                        });
+                    }
+                }
+            }
+        }
     }
+
+    let mut gens = Vec::new();
+    let mut kills = Vec::new();
+    for block in blocks {
+        // gen = uses not preceded by a definition in the block, kill = definitions
+        let mut gen_ = BitSet::new(vars.len());
+        let mut kill = BitSet::new(vars.len());
+        for code in block.iter().rev() {
+            if let Code::Instruction(instr) = code {
+                match instr {
+                    Instruction::Constant { dest, .. } => {
+                        gen_.remove(vars.intern(dest));
+                        kill.insert(vars.intern(dest));
+                    }
+                    Instruction::Effect { args, .. } => {
+                        for arg in args {
+                            gen_.insert(vars.intern(arg));
+                        }
+                    }
+                    Instruction::Value { args, dest, .. } => {
+                        gen_.remove(vars.intern(dest));
+                        kill.insert(vars.intern(dest));
+                        for arg in args {
+                            gen_.insert(vars.intern(arg));
+                        }
+                    }
+                }
+            }
+        }
+        gens.push(gen_);
+        kills.push(kill);
+    }
+
+    let problem = BitVectorProblem {
+        forward: false,
+        may: true,
+        width: vars.len(),
+        gens,
+        kills,
+        boundary: BitSet::new(vars.len()),
+    };
+    let ((in_, out), stats) = problem.solve(pred, succ);
+
+    let to_powerset = |set: &BitSet| set.iter().map(|v| vars.value(v).clone()).collect();
+    (
+        (
+            in_.iter().map(to_powerset).collect(),
+            out.iter().map(to_powerset).collect(),
+        ),
+        stats,
+    )
 }
@@ -1,20 +1,46 @@
+use std::env::args;
+
 use bril_rs::load_program;
-use task4::{DataFlowAnalysis, form_cfg, get_basic_blocks, live_variables::LiveVariables};
+use task4::{
+    DataFlowAnalysis, form_cfg, get_basic_blocks,
+    live_variables::{LiveVariables, live_variables},
+    print_annotated,
+};
 
 fn main() {
     let program = load_program();
 
+    let annotate = args().any(|arg| arg == "-a");
+    let show_stats = args().any(|arg| arg == "-s");
+
     for function in program.functions.iter() {
         let fn_basic_blocks = get_basic_blocks(&function);
 
         let (pred, succ) = form_cfg(&fn_basic_blocks);
-        let (in_, out) = LiveVariables::workman(&fn_basic_blocks, &pred, &succ);
+        let ((in_, out), stats) = live_variables(&fn_basic_blocks, &pred, &succ);
+
+        if show_stats {
+            eprintln!(
+                "{}: {} iterations, {} transfer calls, {:?}",
+                function.name, stats.iterations, stats.transfer_calls, stats.time
+            );
+        }
 
         println!("fn {}:", function.name);
 
+        if annotate {
+            let points = LiveVariables::program_points(&fn_basic_blocks, &in_, &out);
+            print_annotated::<LiveVariables>(&fn_basic_blocks, &points, |live| {
+                let mut live: Vec<_> = live.iter().cloned().collect();
+                live.sort();
+                live.join(", ")
+            });
+            continue;
+        }
+
         for (i, (b_in, b_out)) in in_.into_iter().zip(out.into_iter()).enumerate() {
-            let mut b_in: Vec<_> = b_in.into_iter().collect();
-            let mut b_out: Vec<_> = b_out.into_iter().collect();
+            let mut b_in: Vec<_> = b_in.iter().cloned().collect();
+            let mut b_out: Vec<_> = b_out.iter().cloned().collect();
             b_in.sort();
             b_out.sort();
             println!(
@@ -0,0 +1,524 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Type, ValueOps};
+
+use crate::{
+    DataFlowAnalysis, form_cfg, get_basic_blocks,
+    live_variables::{LiveVariables, live_variables},
+};
+
+#[derive(Default, Debug, Clone)]
+pub struct AllocationReport {
+    // variables in the function before allocation, arguments included
+    pub variables: usize,
+    // most variables live at once before allocation
+    pub max_pressure: usize,
+    // registers given out, at most K
+    pub used: usize,
+    // copies removed because both sides got the same register
+    pub coalesced: usize,
+    // variables kept in spill slots, sorted
+    pub spilled: Vec<String>,
+    // instructions added to move spilled values in and out of their slots
+    pub loads: usize,
+    pub stores: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AllocationError {
+    // get and set pass values through shadow variables named after their destinations
+    UsesShadows,
+    // some instruction reads more variables at once than there are registers
+    TooFewRegisters,
+}
+
+fn get_dest(instr: &Instruction) -> Option<&String> {
+    match instr {
+        Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => Some(dest),
+        Instruction::Effect { .. } => None,
+    }
+}
+
+fn get_args(instr: &Instruction) -> &[String] {
+    match instr {
+        Instruction::Value { args, .. } | Instruction::Effect { args, .. } => args,
+        Instruction::Constant { .. } => &[],
+    }
+}
+
+fn copy(dest: &str, src: &str, op_type: Type) -> Code {
+    Code::Instruction(Instruction::Value {
+        args: vec![src.to_string()],
+        dest: dest.to_string(),
+        funcs: vec![],
+        labels: vec![],
+        op: ValueOps::Id,
+        pos: None,
+        op_type,
+    })
+}
+
+fn fresh_name(base: &str, used: &mut HashSet<String>) -> String {
+    let mut name = base.to_string();
+    let mut i = 0;
+    while used.contains(&name) {
+        name = format!("{base}.{i}");
+        i += 1;
+    }
+    used.insert(name.clone());
+    name
+}
+
+// variables live before each instruction of each block, and after the block's last one
+fn find_live_points(function: &Function) -> Vec<Vec<HashSet<String>>> {
+    let blocks = get_basic_blocks(function);
+    let (pred, succ) = form_cfg(&blocks);
+    let ((in_, out), _) = live_variables(&blocks, &pred, &succ);
+    LiveVariables::program_points(&blocks, &in_, &out)
+        .into_iter()
+        .map(|points| {
+            points
+                .into_iter()
+                .map(|live| live.iter().cloned().collect())
+                .collect()
+        })
+        .collect()
+}
+
+struct InterferenceGraph {
+    nodes: Vec<String>,
+    ids: HashMap<String, usize>,
+    adj: Vec<HashSet<usize>>,
+    // pairs joined by `id`, as (dest, src)
+    moves: Vec<(usize, usize)>,
+    // how often each variable is defined or used, the cost of spilling it
+    uses: Vec<usize>,
+}
+
+impl InterferenceGraph {
+    fn node(&mut self, var: &String) -> usize {
+        if let Some(&id) = self.ids.get(var) {
+            return id;
+        }
+        self.nodes.push(var.clone());
+        self.adj.push(HashSet::new());
+        self.uses.push(0);
+        self.ids.insert(var.clone(), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn interfere(&mut self, a: usize, b: usize) {
+        if a != b {
+            self.adj[a].insert(b);
+            self.adj[b].insert(a);
+        }
+    }
+
+    // variables in slots live in memory, so they're left out
+    fn build(function: &Function, slots: &HashSet<String>) -> Self {
+        let mut graph = InterferenceGraph {
+            nodes: vec![],
+            ids: HashMap::new(),
+            adj: vec![],
+            moves: vec![],
+            uses: vec![],
+        };
+        let blocks = get_basic_blocks(function);
+        let points = find_live_points(function);
+
+        // everything live on entry is defined there at once, the arguments in particular
+        let mut entry: Vec<usize> = function
+            .args
+            .iter()
+            .filter(|arg| !slots.contains(&arg.name))
+            .map(|arg| graph.node(&arg.name))
+            .collect();
+        if let Some(live) = points.first().and_then(|points| points.first()) {
+            entry.extend(
+                live.iter()
+                    .filter(|var| !slots.contains(*var))
+                    .map(|var| graph.node(var)),
+            );
+        }
+        for &a in entry.iter() {
+            for &b in entry.iter() {
+                graph.interfere(a, b);
+            }
+        }
+
+        for (block, points) in blocks.iter().zip(points.iter()) {
+            for (i, code) in block.iter().enumerate() {
+                let Code::Instruction(instr) = code else {
+                    continue;
+                };
+                for arg in get_args(instr).iter().filter(|arg| !slots.contains(*arg)) {
+                    let arg = graph.node(arg);
+                    graph.uses[arg] += 1;
+                }
+                let Some(dest) = get_dest(instr).filter(|dest| !slots.contains(*dest)) else {
+                    continue;
+                };
+                let dest = graph.node(dest);
+                graph.uses[dest] += 1;
+
+                // a copy's destination may share a register with its source
+                let src = match instr {
+                    Instruction::Value {
+                        op: ValueOps::Id,
+                        args,
+                        ..
+                    } if !slots.contains(&args[0]) => {
+                        let src = graph.node(&args[0]);
+                        graph.moves.push((dest, src));
+                        Some(src)
+                    }
+                    _ => None,
+                };
+                for var in points[i + 1].iter().filter(|var| !slots.contains(*var)) {
+                    let var = graph.node(var);
+                    if Some(var) != src {
+                        graph.interfere(dest, var);
+                    }
+                }
+            }
+        }
+        graph
+    }
+}
+
+fn find(alias: &mut [usize], v: usize) -> usize {
+    if alias[v] != v {
+        alias[v] = find(alias, alias[v]);
+    }
+    alias[v]
+}
+
+// merges copies whose merged node still has fewer than k neighbors of degree k or more,
+// which can't make the graph any harder to color (Briggs)
+fn coalesce(graph: &mut InterferenceGraph, k: usize) -> Vec<usize> {
+    let mut alias: Vec<usize> = (0..graph.nodes.len()).collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (a, b) in graph.moves.clone() {
+            let (a, b) = (find(&mut alias, a), find(&mut alias, b));
+            if a == b || graph.adj[a].contains(&b) {
+                continue;
+            }
+            let merged: HashSet<usize> = graph.adj[a].union(&graph.adj[b]).copied().collect();
+            let significant = merged
+                .iter()
+                .filter(|&&n| {
+                    // a neighbor of both loses one edge in the merge
+                    let shared = graph.adj[a].contains(&n) && graph.adj[b].contains(&n);
+                    graph.adj[n].len() - usize::from(shared) >= k
+                })
+                .count();
+            if significant >= k {
+                continue;
+            }
+
+            for &n in graph.adj[b].clone().iter() {
+                graph.adj[n].remove(&b);
+                graph.interfere(a, n);
+            }
+            graph.adj[b].clear();
+            graph.uses[a] += graph.uses[b];
+            alias[b] = a;
+            changed = true;
+        }
+    }
+    alias
+}
+
+// colors the merged nodes with k colors, optimistically pushing spill candidates (Briggs)
+// returns the colors, or the nodes that have to be spilled
+fn color(
+    graph: &InterferenceGraph,
+    alias: &mut [usize],
+    k: usize,
+    unspillable: &HashSet<usize>,
+) -> Result<HashMap<usize, usize>, Vec<usize>> {
+    let mut remaining: HashSet<usize> = (0..graph.nodes.len())
+        .filter(|&v| find(alias, v) == v)
+        .collect();
+    let mut degree: HashMap<usize, usize> =
+        remaining.iter().map(|&v| (v, graph.adj[v].len())).collect();
+
+    let mut stack = vec![];
+    while !remaining.is_empty() {
+        let mut candidates: Vec<usize> = remaining.iter().copied().collect();
+        candidates.sort();
+        let next = match candidates.iter().find(|v| degree[*v] < k) {
+            Some(&v) => v,
+            // the cheapest to spill for the edges it takes away
+            None => *candidates
+                .iter()
+                .min_by(|&&a, &&b| {
+                    let cost = |v: usize| {
+                        if unspillable.contains(&v) {
+                            f64::INFINITY
+                        } else {
+                            graph.uses[v] as f64 / degree[&v] as f64
+                        }
+                    };
+                    cost(a).total_cmp(&cost(b))
+                })
+                .expect("remaining nodes should be there"),
+        };
+        remaining.remove(&next);
+        for n in graph.adj[next].iter() {
+            if let Some(degree) = degree.get_mut(n) {
+                *degree -= 1;
+            }
+        }
+        stack.push(next);
+    }
+
+    let mut colors: HashMap<usize, usize> = HashMap::new();
+    let mut spilled = vec![];
+    while let Some(v) = stack.pop() {
+        let taken: HashSet<usize> = graph.adj[v]
+            .iter()
+            .filter_map(|n| colors.get(n).copied())
+            .collect();
+        match (0..k).find(|c| !taken.contains(c)) {
+            Some(c) => {
+                colors.insert(v, c);
+            }
+            None => spilled.push(v),
+        }
+    }
+    if spilled.is_empty() {
+        Ok(colors)
+    } else {
+        Err(spilled)
+    }
+}
+
+// var -> type, from the arguments and definitions
+fn get_types(function: &Function) -> HashMap<String, Type> {
+    let mut types: HashMap<String, Type> = function
+        .args
+        .iter()
+        .map(|arg| (arg.name.clone(), arg.arg_type.clone()))
+        .collect();
+    for code in function.instrs.iter() {
+        match code {
+            Code::Instruction(Instruction::Constant {
+                dest, const_type, ..
+            }) => {
+                types.insert(dest.clone(), const_type.clone());
+            }
+            Code::Instruction(Instruction::Value { dest, op_type, .. }) => {
+                types.insert(dest.clone(), op_type.clone());
+            }
+            _ => {}
+        }
+    }
+    types
+}
+
+// moves every spilled variable into its slot right after it's defined, and out of it into a new
+// short-lived temporary right before it's used
+// returns the temporaries and the number of loads and stores added
+fn insert_spill_code(
+    function: &mut Function,
+    spilled: &HashMap<String, String>,
+    types: &HashMap<String, Type>,
+    used: &mut HashSet<String>,
+) -> (HashSet<String>, usize, usize) {
+    let mut temps = HashSet::new();
+    let (mut loads, mut stores) = (0, 0);
+
+    // spilled arguments are passed straight into their slots
+    for arg in function.args.iter_mut() {
+        if let Some(slot) = spilled.get(&arg.name) {
+            arg.name = slot.clone();
+        }
+    }
+
+    let mut instrs = vec![];
+
+    for code in function.instrs.drain(..) {
+        let Code::Instruction(mut instr) = code else {
+            instrs.push(code);
+            continue;
+        };
+
+        let (args, dest) = match &mut instr {
+            Instruction::Constant { dest, .. } => (None, Some(dest)),
+            Instruction::Value { args, dest, .. } => (Some(args), Some(dest)),
+            Instruction::Effect { args, .. } => (Some(args), None),
+        };
+        let mut loaded: HashMap<String, String> = HashMap::new();
+        for arg in args.into_iter().flatten() {
+            let Some(slot) = spilled.get(arg) else {
+                continue;
+            };
+            let temp = loaded.entry(arg.clone()).or_insert_with(|| {
+                let temp = fresh_name(arg, used);
+                instrs.push(copy(&temp, slot, types[arg].clone()));
+                loads += 1;
+                temps.insert(temp.clone());
+                temp
+            });
+            *arg = temp.clone();
+        }
+        let store = dest.and_then(|dest| {
+            let slot = spilled.get(dest)?;
+            let temp = fresh_name(dest, used);
+            let store = copy(slot, &temp, types[dest].clone());
+            *dest = temp.clone();
+            temps.insert(temp);
+            Some(store)
+        });
+
+        instrs.push(Code::Instruction(instr));
+        if let Some(store) = store {
+            instrs.push(store);
+            stores += 1;
+        }
+    }
+    function.instrs = instrs;
+
+    (temps, loads, stores)
+}
+
+fn rename(var: &mut String, names: &HashMap<String, String>) {
+    if let Some(name) = names.get(var) {
+        *var = name.clone();
+    }
+}
+
+// Chaitin-Briggs allocation of the function's variables to registers r0..r{k-1}, keeping the
+// variables that don't fit in named spill slots s0, s1, ...
+// leaves the function as it was if it can't be allocated
+pub fn allocate_registers(
+    function: &mut Function,
+    k: usize,
+) -> Result<AllocationReport, AllocationError> {
+    let uses_shadows = function.instrs.iter().any(|code| {
+        matches!(
+            code,
+            Code::Instruction(
+                Instruction::Value {
+                    op: ValueOps::Get,
+                    ..
+                } | Instruction::Effect {
+                    op: EffectOps::Set,
+                    ..
+                }
+            )
+        )
+    });
+    if uses_shadows {
+        return Err(AllocationError::UsesShadows);
+    }
+
+    let mut report = AllocationReport::default();
+    let original = InterferenceGraph::build(function, &HashSet::new());
+    report.variables = original.nodes.len();
+    report.max_pressure = find_live_points(function)
+        .iter()
+        .flatten()
+        .map(HashSet::len)
+        .max()
+        .unwrap_or(0);
+
+    let mut allocated = function.clone();
+    let types = get_types(function);
+    let mut used: HashSet<String> = original.nodes.iter().cloned().collect();
+    let mut slots: HashMap<String, String> = HashMap::new();
+    let mut temps: HashSet<String> = HashSet::new();
+    let mut slot_count = 0;
+
+    let (graph, mut alias, colors) = loop {
+        let mut graph = InterferenceGraph::build(&allocated, &slots.values().cloned().collect());
+        let mut alias = coalesce(&mut graph, k);
+        let unspillable: HashSet<usize> = graph
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, var)| temps.contains(*var) || !types.contains_key(*var))
+            .map(|(v, _)| find(&mut alias, v))
+            .collect();
+
+        let spilled = match color(&graph, &mut alias, k, &unspillable) {
+            Ok(colors) => break (graph, alias, colors),
+            Err(spilled) => spilled,
+        };
+        if spilled.iter().any(|v| unspillable.contains(v)) {
+            return Err(AllocationError::TooFewRegisters);
+        }
+
+        // a merged node keeps a single slot, its variables never being live together
+        let mut spills: HashMap<String, String> = HashMap::new();
+        for v in spilled {
+            let slot = loop {
+                let slot = format!("s{slot_count}");
+                slot_count += 1;
+                if used.insert(slot.clone()) {
+                    break slot;
+                }
+            };
+            for var in 0..graph.nodes.len() {
+                if find(&mut alias, var) == v {
+                    spills.insert(graph.nodes[var].clone(), slot.clone());
+                    report.spilled.push(graph.nodes[var].clone());
+                }
+            }
+        }
+        let (new_temps, loads, stores) =
+            insert_spill_code(&mut allocated, &spills, &types, &mut used);
+        temps.extend(new_temps);
+        report.loads += loads;
+        report.stores += stores;
+        slots.extend(spills);
+    };
+
+    let names: HashMap<String, String> = (0..graph.nodes.len())
+        .map(|v| {
+            let color = colors[&find(&mut alias, v)];
+            (graph.nodes[v].clone(), format!("r{color}"))
+        })
+        .collect();
+    report.used = colors.values().collect::<HashSet<_>>().len();
+
+    for arg in allocated.args.iter_mut() {
+        rename(&mut arg.name, &names);
+    }
+    let mut instrs = vec![];
+    for mut code in allocated.instrs.drain(..) {
+        if let Code::Instruction(instr) = &mut code {
+            match instr {
+                Instruction::Constant { dest, .. } => rename(dest, &names),
+                Instruction::Value { args, dest, .. } => {
+                    rename(dest, &names);
+                    args.iter_mut().for_each(|arg| rename(arg, &names));
+                }
+                Instruction::Effect { args, .. } => {
+                    args.iter_mut().for_each(|arg| rename(arg, &names));
+                }
+            }
+            // copies within a register are gone
+            if let Instruction::Value {
+                op: ValueOps::Id,
+                args,
+                dest,
+                ..
+            } = instr
+                && args[0] == *dest
+            {
+                report.coalesced += 1;
+                continue;
+            }
+        }
+        instrs.push(code);
+    }
+    allocated.instrs = instrs;
+
+    report.spilled.sort();
+    *function = allocated;
+    Ok(report)
+}
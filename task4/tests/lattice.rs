@@ -0,0 +1,73 @@
+use bril_rs::Code;
+use interp::parse::parse_program;
+use task4::{
+    DataFlowAnalysis, form_cfg, get_basic_blocks,
+    lattice::{Lattice, Powerset},
+};
+
+fn samples() -> Vec<Powerset<i32>> {
+    let top = Powerset::top();
+    let mut all_but_one = Powerset::top();
+    all_but_one.remove(&1);
+    vec![
+        Powerset::bottom(),
+        [1].into_iter().collect(),
+        [1, 2].into_iter().collect(),
+        [2, 3].into_iter().collect(),
+        all_but_one,
+        top,
+    ]
+}
+
+#[test]
+fn powerset_is_a_lattice() {
+    let samples = samples();
+    for a in samples.iter() {
+        assert!(Powerset::bottom().leq(a) && a.leq(&Powerset::top()));
+        assert_eq!(a.join(a), *a);
+        assert_eq!(a.meet(a), *a);
+        for b in samples.iter() {
+            let join = a.join(b);
+            let meet = a.meet(b);
+            assert_eq!(join, b.join(a));
+            assert_eq!(meet, b.meet(a));
+            assert!(a.leq(&join) && b.leq(&join));
+            assert!(meet.leq(a) && meet.leq(b));
+            // the order and the operations agree
+            assert_eq!(a.leq(b), join == *b);
+            assert_eq!(a.leq(b), meet == *a);
+        }
+    }
+}
+
+// gains x when it didn't have it and loses it when it did, so it never settles
+struct Flipping;
+
+impl DataFlowAnalysis for Flipping {
+    const FORWARD: bool = true;
+    type State = Powerset<String>;
+
+    fn transfer(_block: &Vec<Code>, input: &Self::State) -> Self::State {
+        if input.contains(&"x".to_string()) {
+            Powerset::bottom()
+        } else {
+            ["x".to_string()].into_iter().collect()
+        }
+    }
+
+    fn boundary_state() -> Self::State {
+        Powerset::bottom()
+    }
+}
+
+// the check is a debug_assert, and without it the solver would never stop
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "transfer function should be monotone")]
+fn solver_rejects_non_monotone_transfer() {
+    let program =
+        parse_program("@main {\n.loop:\n  jmp .loop;\n}\n").expect("program should parse");
+    let blocks = get_basic_blocks(&program.functions[0]);
+    let (pred, succ) = form_cfg(&blocks);
+    Flipping::workman(&blocks, &pred, &succ);
+}
@@ -0,0 +1,124 @@
+use std::{fs, path::Path};
+
+use bril_rs::{Function, Program};
+use interp::parse::parse_program;
+use task4::{
+    DataFlowAnalysis, Solution, form_cfg, get_basic_blocks,
+    live_variables::{LiveVariables, live_variables},
+};
+
+// every Bril program in the repository's test directories
+fn programs() -> Vec<(String, Program)> {
+    fn visit(dir: &Path, programs: &mut Vec<(String, Program)>) {
+        for entry in fs::read_dir(dir).expect("directory should be readable") {
+            let path = entry.expect("entry should be readable").path();
+            if path.is_dir() {
+                if path.file_name().is_some_and(|name| name != "target") {
+                    visit(&path, programs);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "bril") {
+                let text = fs::read_to_string(&path).expect("program should be readable");
+                let program = parse_program(&text).expect("program should parse");
+                programs.push((path.display().to_string(), program));
+            }
+        }
+    }
+
+    let mut programs = vec![];
+    visit(Path::new(".."), &mut programs);
+    assert!(
+        !programs.is_empty(),
+        "the repository should have test programs"
+    );
+    programs
+}
+
+// the solver from before the priority worklist: a stack seeded with every block, which pushes
+// neighbours even when they're already queued
+fn stack_solve<A: DataFlowAnalysis>(function: &Function) -> Solution<A::State> {
+    let blocks = get_basic_blocks(function);
+    let (pred, succ) = form_cfg(&blocks);
+    let (sources, targets) = if A::FORWARD {
+        (&pred, &succ)
+    } else {
+        (&succ, &pred)
+    };
+
+    let mut before = vec![A::initial_state(); blocks.len()];
+    let mut after = vec![A::initial_state(); blocks.len()];
+    let boundary = A::boundary_state();
+    let mut worklist: Vec<usize> = (0..blocks.len()).collect();
+
+    while let Some(b) = worklist.pop() {
+        let mut inputs: Vec<_> = sources[&b].iter().map(|s| &after[*s]).collect();
+        if (A::FORWARD && b == 0) || (!A::FORWARD && inputs.is_empty()) {
+            inputs.push(&boundary);
+        }
+        before[b] = A::merge(&inputs);
+
+        let after_b = A::transfer(&blocks[b], &before[b]);
+        if after[b] != after_b {
+            worklist.extend(&targets[&b]);
+        }
+        after[b] = after_b;
+    }
+
+    if A::FORWARD {
+        (before, after)
+    } else {
+        (after, before)
+    }
+}
+
+#[test]
+fn priority_worklist_matches_stack_worklist() {
+    for (path, program) in programs() {
+        for function in program.functions.iter() {
+            let blocks = get_basic_blocks(function);
+            let (pred, succ) = form_cfg(&blocks);
+            assert!(
+                LiveVariables::workman(&blocks, &pred, &succ)
+                    == stack_solve::<LiveVariables>(function),
+                "live variables differ in {path} @{}",
+                function.name
+            );
+        }
+    }
+}
+
+// without back edges every block is ready the first time it's visited
+#[test]
+fn acyclic_functions_take_one_iteration() {
+    for (path, program) in programs() {
+        for function in program.functions.iter() {
+            let blocks = get_basic_blocks(function);
+            let (pred, succ) = form_cfg(&blocks);
+            let (_, stats) = LiveVariables::workman_with_stats(&blocks, &pred, &succ);
+            if (0..blocks.len()).all(|b| succ[&b].iter().all(|s| *s > b)) {
+                assert_eq!(stats.iterations, 1, "{path} @{}", function.name);
+                assert_eq!(
+                    stats.transfer_calls,
+                    blocks.len(),
+                    "{path} @{}",
+                    function.name
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn bit_vectors_match_generic_solver() {
+    for (path, program) in programs() {
+        for function in program.functions.iter() {
+            let blocks = get_basic_blocks(function);
+            let (pred, succ) = form_cfg(&blocks);
+            assert!(
+                live_variables(&blocks, &pred, &succ).0
+                    == LiveVariables::workman(&blocks, &pred, &succ),
+                "live variables differ in {path} @{}",
+                function.name
+            );
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::{collections::HashMap, fmt::Display};
+
+use bril_rs::{Code, Instruction, ValueOps};
+use task3::purity::can_reuse;
+
+use crate::{df::DataFlowAnalysis, lattice::Powerset};
+
+// an operation over variables, commutative operands are sorted so `add a b` and `add b a` match
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Expr {
+    pub op: ValueOps,
+    pub args: Vec<String>,
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.op)?;
+        for arg in self.args.iter() {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Expr {
+    pub fn uses(&self, var: &str) -> bool {
+        self.args.iter().any(|arg| arg == var)
+    }
+}
+
+fn is_commutative(op: ValueOps) -> bool {
+    use ValueOps::*;
+    matches!(op, Add | Mul | Eq | And | Or | Fadd | Fmul | Feq | Ceq)
+}
+
+// the expression an instruction computes, if recomputing it is always redundant
+pub fn get_expr(instr: &Instruction) -> Option<Expr> {
+    match instr {
+        // copies are left to copy propagation
+        // lvn's reuse rule without purity information, since transfer functions only see the
+        // function being analyzed, so calls are never reused
+        Instruction::Value {
+            op, args, funcs, ..
+        } if *op != ValueOps::Id && can_reuse(*op, funcs, &HashMap::new()) => {
+            let mut args = args.clone();
+            if is_commutative(*op) {
+                args.sort();
+            }
+            Some(Expr { op: *op, args })
+        }
+        _ => None,
+    }
+}
+
+pub struct AvailableExpressions;
+
+impl DataFlowAnalysis for AvailableExpressions {
+    const FORWARD: bool = true;
+    const MAY: bool = false;
+
+    // expressions computed on every path and not killed since
+    type State = Powerset<Expr>;
+
+    fn transfer(block: &Vec<Code>, _block_id: usize, in_: &Self::State) -> Self::State {
+        // only unreachable blocks still see the whole universe, which can't be enumerated to kill
+        let Powerset::Finite(exprs) = in_ else {
+            return in_.clone();
+        };
+        let mut out = exprs.clone();
+
+        for code in block {
+            if let Code::Instruction(instr) = code {
+                if let Some(expr) = get_expr(instr) {
+                    out.insert(expr);
+                }
+                // a redefined operand kills the expression, even the one just computed
+                if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = instr
+                {
+                    out.retain(|expr| !expr.uses(dest));
+                }
+            }
+        }
+
+        Powerset::Finite(out)
+    }
+
+    // nothing has been computed at the entry
+    fn boundary_state() -> Self::State {
+        Powerset::new()
+    }
+}
@@ -0,0 +1,8 @@
+use bril_rs::load_program;
+use task6::c_backend::emit_c;
+
+// translates a program into a single C file, written to stdout
+fn main() {
+    let program = load_program();
+    print!("{}", emit_c(&program));
+}
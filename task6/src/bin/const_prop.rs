@@ -0,0 +1,12 @@
+use bril_rs::{load_program, output_program};
+use task6::const_prop::propagate_constants;
+
+fn main() {
+    let mut program = load_program();
+
+    for function in program.functions.iter_mut() {
+        propagate_constants(function);
+    }
+
+    output_program(&program);
+}
@@ -0,0 +1,12 @@
+use bril_rs::{load_program, output_program};
+use task6::cse::eliminate_common_subexprs;
+
+fn main() {
+    let mut program = load_program();
+
+    for function in program.functions.iter_mut() {
+        eliminate_common_subexprs(function);
+    }
+
+    output_program(&program);
+}
@@ -0,0 +1,62 @@
+use std::env::args;
+
+use bril_rs::{load_program, output_program};
+use task6::{
+    cfg::{form_cfg, get_basic_blocks, get_label},
+    df::{DataFlowAnalysis, print_annotated},
+    dom::rev_graph,
+    intervals::{IntervalAnalysis, fold_branches, format_ranges, ranges_to_json},
+};
+
+// folds branches by default, -a prints the ranges at every instruction instead and -j prints the
+// ranges at the start and end of every block as JSON
+fn main() {
+    let mut program = load_program();
+
+    let annotate = args().any(|arg| arg == "-a");
+    let json = args().any(|arg| arg == "-j");
+
+    if !annotate && !json {
+        for function in program.functions.iter_mut() {
+            fold_branches(function);
+        }
+        output_program(&program);
+        return;
+    }
+
+    let mut functions = vec![];
+    for function in program.functions {
+        let blocks = get_basic_blocks(&function);
+        let succ = form_cfg(&blocks);
+        let pred = rev_graph(&succ);
+
+        let (in_, out) = IntervalAnalysis::find(&blocks, &pred, &succ);
+
+        if annotate {
+            println!("==== Function: {} ====", function.name);
+            let points = IntervalAnalysis::program_points(&blocks, &in_, &out);
+            print_annotated::<IntervalAnalysis>(&blocks, &points, format_ranges);
+            continue;
+        }
+
+        let blocks_json: Vec<String> = (0..blocks.len())
+            .map(|b| {
+                format!(
+                    "{{\"label\": \"{}\", \"in\": {}, \"out\": {}}}",
+                    get_label(&blocks, b),
+                    ranges_to_json(&in_[b]),
+                    ranges_to_json(&out[b])
+                )
+            })
+            .collect();
+        functions.push(format!(
+            "{{\"name\": \"{}\", \"blocks\": [{}]}}",
+            function.name,
+            blocks_json.join(", ")
+        ));
+    }
+
+    if json {
+        println!("{{\"functions\": [{}]}}", functions.join(", "));
+    }
+}
@@ -0,0 +1,22 @@
+use std::env::args;
+
+use bril_rs::{load_program, output_program};
+use task6::lcm::lazy_code_motion;
+
+fn main() {
+    let mut program = load_program();
+
+    let show_stats = args().any(|arg| arg == "-s");
+
+    for function in program.functions.iter_mut() {
+        let stats = lazy_code_motion(function);
+        if show_stats {
+            eprintln!(
+                "{}: {} inserted, {} deleted",
+                function.name, stats.inserted, stats.deleted
+            );
+        }
+    }
+
+    output_program(&program);
+}
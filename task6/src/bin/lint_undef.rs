@@ -0,0 +1,34 @@
+use std::process::exit;
+
+use bril_rs::load_program;
+use task6::undef::find_undefined_uses;
+
+// reports reads of variables that are undefined on some path, exits with 1 if there are any
+fn main() {
+    let program = load_program();
+
+    let mut found = false;
+    for function in program.functions.iter() {
+        for diagnostic in find_undefined_uses(function) {
+            found = true;
+
+            let location = match &diagnostic.pos {
+                Some(pos) => format!("{}:{}", pos.pos.row, pos.pos.col),
+                None => format!("@{}", function.name),
+            };
+            let problem = if diagnostic.definitely {
+                "is undefined"
+            } else {
+                "may be undefined"
+            };
+            println!(
+                "{location}: `{}` {problem} in `{}`",
+                diagnostic.var, diagnostic.instr
+            );
+        }
+    }
+
+    if found {
+        exit(1);
+    }
+}
@@ -0,0 +1,22 @@
+use std::env::args;
+
+use bril_rs::{load_program, output_program};
+use task6::mem_opt::optimize_memory;
+
+fn main() {
+    let mut program = load_program();
+
+    let show_stats = args().any(|arg| arg == "-s");
+
+    for function in program.functions.iter_mut() {
+        let stats = optimize_memory(function);
+        if show_stats {
+            eprintln!(
+                "{}: {} loads forwarded, {} stores removed",
+                function.name, stats.forwarded, stats.removed
+            );
+        }
+    }
+
+    output_program(&program);
+}
@@ -0,0 +1,34 @@
+use std::process::exit;
+
+use bril_rs::load_program;
+use task6::memcheck::{Problem, check_memory};
+
+// reports leaks, double frees and uses of freed memory, exits with 1 if there are any
+fn main() {
+    let program = load_program();
+
+    let mut found = false;
+    for function in program.functions.iter() {
+        for diagnostic in check_memory(function) {
+            found = true;
+
+            let location = match &diagnostic.pos {
+                Some(pos) => format!("{}:{}", pos.pos.row, pos.pos.col),
+                None => format!("@{}", function.name),
+            };
+            let problem = match (diagnostic.problem, diagnostic.definitely) {
+                (Problem::Leak, true) => "is never freed",
+                (Problem::Leak, false) => "may not be freed",
+                (Problem::DoubleFree, true) => "frees memory that is already freed",
+                (Problem::DoubleFree, false) => "may free memory that is already freed",
+                (Problem::UseAfterFree, true) => "accesses freed memory",
+                (Problem::UseAfterFree, false) => "may access freed memory",
+            };
+            println!("{location}: `{}` {problem}", diagnostic.instr);
+        }
+    }
+
+    if found {
+        exit(1);
+    }
+}
@@ -0,0 +1,53 @@
+use std::env::args;
+
+use bril_rs::load_program;
+use task6::points_to::PointsTo;
+
+fn format_sites(points_to: &PointsTo, sites: impl IntoIterator<Item = usize>) -> String {
+    let names: Vec<&str> = sites
+        .into_iter()
+        .map(|site| points_to.sites()[site].name.as_str())
+        .collect();
+    format!("{{{}}}", names.join(", "))
+}
+
+// prints what each pointer variable and allocated object may point to
+// with `-a`, also lists the pairs of pointers in each function that may alias
+fn main() {
+    let program = load_program();
+    let aliases = args().any(|a| a == "-a");
+
+    let points_to = PointsTo::analyze(&program);
+
+    for function in program.functions.iter() {
+        println!("@{}", function.name);
+        let pointers = points_to.pointers(&function.name);
+        for var in pointers.iter() {
+            println!(
+                "  {var} -> {}",
+                format_sites(&points_to, points_to.points_to(&function.name, var))
+            );
+        }
+
+        if aliases {
+            for (i, a) in pointers.iter().enumerate() {
+                for b in pointers[i + 1..].iter() {
+                    if points_to.may_alias(&function.name, a, b) {
+                        println!("  {a} ~ {b}");
+                    }
+                }
+            }
+        }
+    }
+
+    for (site, alloc) in points_to.sites().iter().enumerate() {
+        let contents = points_to.contents_of(site);
+        if !contents.is_empty() {
+            println!(
+                "*{} -> {}",
+                alloc.name,
+                format_sites(&points_to, contents.iter().copied())
+            );
+        }
+    }
+}
@@ -1,17 +1,22 @@
 // use std::fs::File;
+use std::env::args;
 
 use bril_rs::load_program;
 // use bril_rs::load_program_from_read;
 use task6::{
     cfg::{form_cfg, get_basic_blocks, get_label},
-    df::{DataFlowAnalysis, ReachingDefs},
+    df::{DataFlowAnalysis, DefSite, ReachingDefs, print_annotated, reaching_defs},
     dom::rev_graph,
+    lattice::Powerset,
 };
 
 fn main() {
     let program = load_program();
     // let program = load_program_from_read(File::open("loop.json").unwrap());
 
+    let annotate = args().any(|arg| arg == "-a");
+    let show_stats = args().any(|arg| arg == "-s");
+
     for function in program.functions {
         println!("==== Function: {} ====", function.name);
         let blocks = get_basic_blocks(&function);
@@ -19,23 +24,48 @@ fn main() {
         let succ = form_cfg(&blocks);
         let pred = rev_graph(&succ);
 
-        let (in_, out) = ReachingDefs::find(&blocks, &pred, &succ);
+        let ((in_, out), stats) = reaching_defs(&function.args, &blocks, &pred, &succ);
+
+        // instructions as label[index], arguments by name
+        let fmt_sites = |sites: &Powerset<DefSite>| {
+            let mut sites = sites.iter().copied().collect::<Vec<_>>();
+            sites.sort();
+            sites
+                .into_iter()
+                .map(|site| match site {
+                    DefSite::Arg(i) => format!("arg {}", function.args[i].name),
+                    DefSite::Instr(b, i) => format!("{}[{i}]", get_label(&blocks, b)),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        if show_stats {
+            eprintln!(
+                "{}: {} iterations, {} transfer calls, {:?}",
+                function.name, stats.iterations, stats.transfer_calls, stats.time
+            );
+        }
+
+        if annotate {
+            let points = ReachingDefs::program_points(&blocks, &in_, &out);
+            print_annotated::<ReachingDefs>(&blocks, &points, |defs| {
+                let mut defs = defs
+                    .iter()
+                    .map(|(var, sites)| format!("{var} -> {}", fmt_sites(sites)))
+                    .collect::<Vec<_>>();
+                defs.sort();
+                defs.join("; ")
+            });
+            continue;
+        }
 
         // block -> var
         println!("===IN===");
         for (block, defs) in in_.iter().enumerate() {
             println!("- .{}", get_label(&blocks, block));
-            for (var, def_blocks) in defs.iter() {
-                let mut def_blocks = def_blocks.iter().collect::<Vec<_>>();
-                def_blocks.sort();
-                println!(
-                    "\t{var} -> {}",
-                    def_blocks
-                        .into_iter()
-                        .map(|&b| { get_label(&blocks, b) })
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                );
+            for (var, sites) in defs.iter() {
+                println!("\t{var} -> {}", fmt_sites(sites));
             }
             println!();
         }
@@ -43,17 +73,8 @@ fn main() {
         println!("===OUT===");
         for (block, defs) in out.iter().enumerate() {
             println!("- .{}", get_label(&blocks, block));
-            for (var, def_blocks) in defs.iter() {
-                let mut def_blocks = def_blocks.iter().collect::<Vec<_>>();
-                def_blocks.sort();
-                println!(
-                    "\t{var} -> {}",
-                    def_blocks
-                        .into_iter()
-                        .map(|&b| { get_label(&blocks, b) })
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                );
+            for (var, sites) in defs.iter() {
+                println!("\t{var} -> {}", fmt_sites(sites));
             }
             println!();
         }
@@ -0,0 +1,55 @@
+use std::env::args;
+
+use bril_rs::{load_program, output_program};
+use task6::{
+    cfg::{form_cfg, get_basic_blocks, get_label},
+    dom::rev_graph,
+    induction::{find_induction_vars, reduce_strength},
+    loops::find_natural_loops,
+};
+
+// strength-reduces multiplications by induction variables
+// with `-i`, prints the induction variables of each loop instead
+fn main() {
+    let mut program = load_program();
+
+    let show_vars = args().any(|arg| arg == "-i");
+    let show_stats = args().any(|arg| arg == "-s");
+
+    if show_vars {
+        for function in program.functions.iter() {
+            println!("@{}", function.name);
+            let blocks = get_basic_blocks(function);
+            let succ = form_cfg(&blocks);
+            let pred = rev_graph(&succ);
+            for natural_loop in find_natural_loops(&pred, &succ) {
+                let body: Vec<String> = natural_loop
+                    .body
+                    .iter()
+                    .map(|b| format!(".{}", get_label(&blocks, *b)))
+                    .collect();
+                println!("  loop {}", body.join(" "));
+                let vars = find_induction_vars(&blocks, &natural_loop);
+                for basic_var in vars.basic.iter() {
+                    println!("    basic {basic_var}");
+                }
+                for derived_var in vars.derived.iter() {
+                    println!("    derived {derived_var}");
+                }
+            }
+        }
+        return;
+    }
+
+    for function in program.functions.iter_mut() {
+        let stats = reduce_strength(function);
+        if show_stats {
+            eprintln!(
+                "{}: {} reduced, {} tests replaced",
+                function.name, stats.reduced, stats.tests_replaced
+            );
+        }
+    }
+
+    output_program(&program);
+}
@@ -0,0 +1,35 @@
+use std::{env::args, process::exit};
+
+use bril_rs::load_program;
+use task6::typecheck::{check_program, infer_types};
+
+// reports every type error, exits with 1 if there are any
+// with `-t`, also prints the type of every variable
+fn main() {
+    let program = load_program();
+    let show_types = args().any(|a| a == "-t");
+
+    if show_types {
+        for function in program.functions.iter() {
+            println!("@{}", function.name);
+            let mut types: Vec<_> = infer_types(function).into_iter().collect();
+            types.sort_by(|a, b| a.0.cmp(&b.0));
+            for (var, tipe) in types {
+                println!("  {var}: {tipe}");
+            }
+        }
+    }
+
+    let errors = check_program(&program);
+    for error in errors.iter() {
+        let location = match &error.pos {
+            Some(pos) => format!("{}:{}", pos.pos.row, pos.pos.col),
+            None => format!("@{}", error.func),
+        };
+        println!("{location}: {}", error.message);
+    }
+
+    if !errors.is_empty() {
+        exit(1);
+    }
+}
@@ -0,0 +1,36 @@
+use std::env::args;
+
+use bril_rs::{load_program, output_program};
+use task6::unroll::{UnrollConfig, unroll_loops};
+
+fn flag_value(flag: &str) -> Option<usize> {
+    args()
+        .skip_while(|arg| arg != flag)
+        .nth(1)
+        .map(|value| value.parse().expect("flag value should be a number"))
+}
+
+// unrolls innermost loops, fully if their trip count is known and small enough
+// `-n N` sets the unroll factor, `-l N` the size limit for full unrolling
+fn main() {
+    let mut program = load_program();
+
+    let default = UnrollConfig::default();
+    let config = UnrollConfig {
+        factor: flag_value("-n").unwrap_or(default.factor),
+        size_limit: flag_value("-l").unwrap_or(default.size_limit),
+    };
+    let show_stats = args().any(|arg| arg == "-s");
+
+    for function in program.functions.iter_mut() {
+        let stats = unroll_loops(function, &config);
+        if show_stats {
+            eprintln!(
+                "{}: {} fully unrolled, {} partially unrolled",
+                function.name, stats.fully, stats.partially
+            );
+        }
+    }
+
+    output_program(&program);
+}
@@ -0,0 +1,62 @@
+use std::env::args;
+
+use bril_rs::load_program;
+use task6::{
+    cfg::{form_cfg, get_basic_blocks},
+    chains::{build_chains, describe_site, get_pos},
+};
+
+// usage: use_def ROW[:COL] < program.json
+// lists the definitions reaching each variable used by the instructions at that position
+fn main() {
+    let query = args().nth(1).expect("a position ROW[:COL] should be given");
+    let (row, col) = match query.split_once(':') {
+        Some((row, col)) => (row, Some(col)),
+        None => (query.as_str(), None),
+    };
+    let row: u64 = row.parse().expect("row should be a number");
+    let col: Option<u64> = col.map(|col| col.parse().expect("col should be a number"));
+
+    let program = load_program();
+
+    let mut found = false;
+    for function in program.functions {
+        let blocks = get_basic_blocks(&function);
+        let succ = form_cfg(&blocks);
+        let chains = build_chains(&function, &blocks, &succ);
+
+        for (b, block) in blocks.iter().enumerate() {
+            for (i, code) in block.iter().enumerate() {
+                let Some(pos) = get_pos(code) else {
+                    continue;
+                };
+                if pos.pos.row != row || col.is_some_and(|col| pos.pos.col != col) {
+                    continue;
+                }
+
+                let mut uses = chains
+                    .use_def
+                    .iter()
+                    .filter(|(((use_b, use_i), _), _)| (*use_b, *use_i) == (b, i))
+                    .collect::<Vec<_>>();
+                uses.sort_by(|a, b| a.0.1.cmp(&b.0.1));
+
+                found = true;
+                println!("{}", code.to_string().trim());
+                for ((_, var), defs) in uses {
+                    if defs.is_empty() {
+                        println!("  {var} <- (never defined)");
+                    }
+                    for def in defs {
+                        println!("  {var} <- {}", describe_site(&function, &blocks, *def));
+                    }
+                }
+            }
+        }
+    }
+
+    if !found {
+        eprintln!("no instruction at {query}");
+        std::process::exit(1);
+    }
+}
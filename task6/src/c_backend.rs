@@ -0,0 +1,468 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use bril_rs::{Code, ConstOps, EffectOps, Function, Instruction, Literal, Program, Type, ValueOps};
+
+// helpers every translated program uses, printing the way brili does
+const PRELUDE: &str = r#"#include <inttypes.h>
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static inline void bril_error(const char *message) {
+  fflush(stdout);
+  fprintf(stderr, "error: %s\n", message);
+  exit(2);
+}
+
+static inline int64_t bril_div(int64_t a, int64_t b) {
+  if (b == 0) bril_error("division by zero");
+  // the only quotient that doesn't fit wraps around
+  if (a == INT64_MIN && b == -1) return INT64_MIN;
+  return a / b;
+}
+
+static inline void *bril_alloc(int64_t count, size_t size) {
+  if (count <= 0) bril_error("must allocate a positive amount of memory");
+  return malloc((size_t)count * size);
+}
+
+static inline int64_t bril_float2bits(double x) {
+  int64_t bits;
+  memcpy(&bits, &x, sizeof bits);
+  return bits;
+}
+
+static inline double bril_bits2float(int64_t bits) {
+  double x;
+  memcpy(&x, &bits, sizeof x);
+  return x;
+}
+
+static inline void bril_print_int(int64_t x) { printf("%" PRId64, x); }
+
+static inline void bril_print_bool(bool x) { fputs(x ? "true" : "false", stdout); }
+
+static inline void bril_print_float(double x) {
+  if (isnan(x)) {
+    fputs("NaN", stdout);
+  } else if (isinf(x)) {
+    fputs(x > 0 ? "Infinity" : "-Infinity", stdout);
+  } else if (fabs(x) >= 1e21) {
+    // toFixed falls back to the shortest exponential form that reads back the same
+    char buf[32];
+    for (int digits = 0; digits <= 16; digits++) {
+      snprintf(buf, sizeof buf, "%.*e", digits, x);
+      if (strtod(buf, NULL) == x) break;
+    }
+    char *e = strchr(buf, 'e');
+    *e = '\0';
+    int exponent = atoi(e + 1);
+    printf("%se%c%d", buf, exponent < 0 ? '-' : '+', abs(exponent));
+  } else {
+    printf("%.17f", x);
+  }
+}
+
+static inline void bril_print_char(uint32_t c) {
+  char buf[4];
+  int n;
+  if (c < 0x80) {
+    buf[0] = (char)c;
+    n = 1;
+  } else if (c < 0x800) {
+    buf[0] = (char)(0xc0 | (c >> 6));
+    buf[1] = (char)(0x80 | (c & 0x3f));
+    n = 2;
+  } else if (c < 0x10000) {
+    buf[0] = (char)(0xe0 | (c >> 12));
+    buf[1] = (char)(0x80 | ((c >> 6) & 0x3f));
+    buf[2] = (char)(0x80 | (c & 0x3f));
+    n = 3;
+  } else {
+    buf[0] = (char)(0xf0 | (c >> 18));
+    buf[1] = (char)(0x80 | ((c >> 12) & 0x3f));
+    buf[2] = (char)(0x80 | ((c >> 6) & 0x3f));
+    buf[3] = (char)(0x80 | (c & 0x3f));
+    n = 4;
+  }
+  fwrite(buf, 1, n, stdout);
+}
+
+static inline int64_t bril_parse_int(const char *s) { return strtoll(s, NULL, 10); }
+
+static inline bool bril_parse_bool(const char *s) { return strcmp(s, "true") == 0; }
+
+static inline double bril_parse_float(const char *s) { return strtod(s, NULL); }
+
+static inline uint32_t bril_parse_char(const char *s) {
+  const unsigned char *u = (const unsigned char *)s;
+  if (u[0] < 0x80) return u[0];
+  if (u[0] < 0xe0) return ((u[0] & 0x1f) << 6) | (u[1] & 0x3f);
+  if (u[0] < 0xf0) return ((u[0] & 0x0f) << 12) | ((u[1] & 0x3f) << 6) | (u[2] & 0x3f);
+  return ((u[0] & 0x07) << 18) | ((u[1] & 0x3f) << 12) | ((u[2] & 0x3f) << 6) | (u[3] & 0x3f);
+}
+"#;
+
+// a C identifier for a Bril name, with everything but letters and digits escaped so different
+// names stay different
+fn mangle(prefix: &str, name: &str) -> String {
+    let mut mangled = prefix.to_string();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            mangled.push(c);
+        } else {
+            write!(mangled, "_{:x}_", c as u32).expect("writing to a string should succeed");
+        }
+    }
+    mangled
+}
+
+fn var(name: &str) -> String {
+    mangle("v_", name)
+}
+
+// chars are kept as code points, since a C char only holds a byte
+fn c_type(tipe: &Type) -> String {
+    match tipe {
+        Type::Int => "int64_t".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::Float => "double".to_string(),
+        Type::Char => "uint32_t".to_string(),
+        Type::Pointer(tipe) => format!("{}*", c_type(tipe)),
+    }
+}
+
+fn literal(value: &Literal, tipe: &Type) -> String {
+    match (value, tipe) {
+        (Literal::Int(i64::MIN), Type::Int) => "INT64_MIN".to_string(),
+        (Literal::Int(value), Type::Int) => format!("INT64_C({value})"),
+        // bril2json leaves int literals of float constants as ints
+        (Literal::Int(value), Type::Float) => literal(&Literal::Float(*value as f64), tipe),
+        (Literal::Bool(value), _) => value.to_string(),
+        (Literal::Float(value), _) if value.is_nan() => "NAN".to_string(),
+        (Literal::Float(value), _) if value.is_infinite() => {
+            let sign = if *value < 0.0 { "-" } else { "" };
+            format!("{sign}INFINITY")
+        }
+        (Literal::Float(value), _) => format!("{value:e}"),
+        (Literal::Char(value), _) => format!("UINT32_C({})", *value as u32),
+        (value, _) => unreachable!("{value:?} should fit its {tipe} constant"),
+    }
+}
+
+fn signature(function: &Function) -> String {
+    let return_type = function
+        .return_type
+        .as_ref()
+        .map_or("void".to_string(), c_type);
+    let args: Vec<String> = function
+        .args
+        .iter()
+        .map(|arg| format!("{} {}", c_type(&arg.arg_type), var(&arg.name)))
+        .collect();
+    let args = if args.is_empty() {
+        "void".to_string()
+    } else {
+        args.join(", ")
+    };
+    format!("{return_type} {}({args})", mangle("f_", &function.name))
+}
+
+// var -> type, from the arguments and definitions, so every variable should keep a single type
+fn get_types(function: &Function) -> HashMap<&String, &Type> {
+    let mut types: HashMap<&String, &Type> = function
+        .args
+        .iter()
+        .map(|arg| (&arg.name, &arg.arg_type))
+        .collect();
+    for code in function.instrs.iter() {
+        if let Code::Instruction(
+            Instruction::Constant {
+                dest,
+                const_type: tipe,
+                ..
+            }
+            | Instruction::Value {
+                dest,
+                op_type: tipe,
+                ..
+            },
+        ) = code
+        {
+            types.entry(dest).or_insert(tipe);
+        }
+    }
+    // a variable that's used but never defined can only be read as an error, but it still needs
+    // a declaration
+    for code in function.instrs.iter() {
+        if let Code::Instruction(
+            Instruction::Value { args, .. } | Instruction::Effect { args, .. },
+        ) = code
+        {
+            for arg in args.iter() {
+                types.entry(arg).or_insert(&Type::Int);
+            }
+        }
+    }
+    types
+}
+
+fn value_expr(op: ValueOps, args: &[String], funcs: &[String], tipe: &Type) -> String {
+    let arg = |i: usize| var(&args[i]);
+    let binary = |operator: &str| format!("{} {operator} {}", arg(0), arg(1));
+    // signed overflow is undefined in C, so ints wrap through unsigned arithmetic
+    let wrapping = |operator: &str| {
+        format!(
+            "(int64_t)((uint64_t){} {operator} (uint64_t){})",
+            arg(0),
+            arg(1)
+        )
+    };
+    match op {
+        ValueOps::Add => wrapping("+"),
+        ValueOps::Sub => wrapping("-"),
+        ValueOps::Mul => wrapping("*"),
+        ValueOps::Div => format!("bril_div({}, {})", arg(0), arg(1)),
+        ValueOps::Eq | ValueOps::Feq | ValueOps::Ceq => binary("=="),
+        ValueOps::Lt | ValueOps::Flt | ValueOps::Clt => binary("<"),
+        ValueOps::Gt | ValueOps::Fgt | ValueOps::Cgt => binary(">"),
+        ValueOps::Le | ValueOps::Fle | ValueOps::Cle => binary("<="),
+        ValueOps::Ge | ValueOps::Fge | ValueOps::Cge => binary(">="),
+        ValueOps::Not => format!("!{}", arg(0)),
+        ValueOps::And => binary("&&"),
+        ValueOps::Or => binary("||"),
+        ValueOps::Fadd => binary("+"),
+        ValueOps::Fsub => binary("-"),
+        ValueOps::Fmul => binary("*"),
+        ValueOps::Fdiv => binary("/"),
+        ValueOps::Id => arg(0),
+        ValueOps::Call => {
+            let args: Vec<String> = args.iter().map(|arg| var(arg)).collect();
+            format!("{}({})", mangle("f_", &funcs[0]), args.join(", "))
+        }
+        ValueOps::Char2int => format!("(int64_t){}", arg(0)),
+        ValueOps::Int2char => format!("(uint32_t){}", arg(0)),
+        ValueOps::Float2Bits => format!("bril_float2bits({})", arg(0)),
+        ValueOps::Bits2Float => format!("bril_bits2float({})", arg(0)),
+        ValueOps::Alloc => {
+            let Type::Pointer(pointee) = tipe else {
+                unreachable!("alloc should produce a pointer");
+            };
+            format!("bril_alloc({}, sizeof({}))", arg(0), c_type(pointee))
+        }
+        ValueOps::Load => format!("*{}", arg(0)),
+        ValueOps::PtrAdd => binary("+"),
+        ValueOps::Get => mangle("s_", &args[0]),
+        ValueOps::Undef => unreachable!("undef doesn't compute a value"),
+    }
+}
+
+fn emit_function(out: &mut String, function: &Function) -> std::fmt::Result {
+    let types = get_types(function);
+    writeln!(out, "{} {{", signature(function))?;
+
+    let mut locals: Vec<(&String, &Type)> = types
+        .iter()
+        .filter(|(name, _)| !function.args.iter().any(|arg| arg.name == name.as_str()))
+        .map(|(name, tipe)| (*name, *tipe))
+        .collect();
+    locals.sort_by_key(|(name, _)| *name);
+    // a Bril variable may be assigned and never read, which is fine but makes cc -Wall warn
+    for (name, tipe) in locals.iter() {
+        writeln!(
+            out,
+            "  {} {} __attribute__((unused));",
+            c_type(tipe),
+            var(name)
+        )?;
+    }
+    // the shadow variables get and set go through
+    let mut shadows: Vec<(&String, &Type)> = function
+        .instrs
+        .iter()
+        .filter_map(|code| match code {
+            Code::Instruction(Instruction::Value {
+                op: ValueOps::Get,
+                dest,
+                op_type,
+                ..
+            }) => Some((dest, op_type)),
+            _ => None,
+        })
+        .collect();
+    shadows.sort_by_key(|(name, _)| *name);
+    shadows.dedup_by_key(|(name, _)| *name);
+    for (name, tipe) in shadows.iter() {
+        writeln!(
+            out,
+            "  {} {} __attribute__((unused));",
+            c_type(tipe),
+            mangle("s_", name)
+        )?;
+    }
+
+    // labels nothing jumps to are left out, since unused C labels are warned about
+    let targets: HashSet<&String> = function
+        .instrs
+        .iter()
+        .flat_map(|code| match code {
+            Code::Instruction(Instruction::Effect { labels, .. }) => labels.iter(),
+            _ => [].iter(),
+        })
+        .collect();
+
+    for code in function.instrs.iter() {
+        let instr = match code {
+            Code::Label { label, .. } => {
+                if targets.contains(label) {
+                    writeln!(out, "{}:;", mangle("l_", label))?;
+                }
+                continue;
+            }
+            Code::Instruction(instr) => instr,
+        };
+        match instr {
+            Instruction::Constant {
+                dest,
+                op: ConstOps::Const,
+                const_type,
+                value,
+                ..
+            } => writeln!(out, "  {} = {};", var(dest), literal(value, const_type))?,
+            Instruction::Value {
+                op: ValueOps::Undef,
+                ..
+            } => {}
+            Instruction::Value {
+                op: ValueOps::Get,
+                dest,
+                ..
+            } => writeln!(out, "  {} = {};", var(dest), mangle("s_", dest))?,
+            Instruction::Value {
+                op,
+                dest,
+                args,
+                funcs,
+                op_type,
+                ..
+            } => writeln!(
+                out,
+                "  {} = {};",
+                var(dest),
+                value_expr(*op, args, funcs, op_type)
+            )?,
+            Instruction::Effect {
+                op,
+                args,
+                funcs,
+                labels,
+                ..
+            } => match op {
+                EffectOps::Jump => writeln!(out, "  goto {};", mangle("l_", &labels[0]))?,
+                EffectOps::Branch => writeln!(
+                    out,
+                    "  if ({}) goto {}; else goto {};",
+                    var(&args[0]),
+                    mangle("l_", &labels[0]),
+                    mangle("l_", &labels[1])
+                )?,
+                EffectOps::Call => {
+                    let args: Vec<String> = args.iter().map(|arg| var(arg)).collect();
+                    writeln!(out, "  {}({});", mangle("f_", &funcs[0]), args.join(", "))?
+                }
+                EffectOps::Return => match args.first() {
+                    Some(arg) => writeln!(out, "  return {};", var(arg))?,
+                    None => writeln!(out, "  return;")?,
+                },
+                EffectOps::Print => {
+                    for (i, arg) in args.iter().enumerate() {
+                        if i > 0 {
+                            writeln!(out, "  putchar(' ');")?;
+                        }
+                        let print = match types.get(arg) {
+                            Some(Type::Bool) => "bril_print_bool",
+                            Some(Type::Float) => "bril_print_float",
+                            Some(Type::Char) => "bril_print_char",
+                            // what brili prints for a pointer
+                            Some(Type::Pointer(_)) => {
+                                writeln!(out, "  fputs(\"[object Object]\", stdout);")?;
+                                continue;
+                            }
+                            Some(Type::Int) | None => "bril_print_int",
+                        };
+                        writeln!(out, "  {print}({});", var(arg))?;
+                    }
+                    writeln!(out, "  putchar('\\n');")?;
+                }
+                EffectOps::Nop => {}
+                EffectOps::Store => writeln!(out, "  *{} = {};", var(&args[0]), var(&args[1]))?,
+                EffectOps::Free => writeln!(out, "  free({});", var(&args[0]))?,
+                EffectOps::Set => {
+                    writeln!(out, "  {} = {};", mangle("s_", &args[0]), var(&args[1]))?
+                }
+                EffectOps::Speculate | EffectOps::Commit | EffectOps::Guard => {
+                    writeln!(out, "  bril_error(\"speculation is not supported\");")?
+                }
+            },
+        }
+    }
+    writeln!(out, "}}")
+}
+
+// parses the command line into main's arguments, like brili does
+fn emit_main(out: &mut String, main: &Function) -> std::fmt::Result {
+    writeln!(out, "int main(int argc, char **argv) {{")?;
+    if main.args.is_empty() {
+        writeln!(out, "  (void)argv;")?;
+    }
+    writeln!(out, "  if (argc != {}) {{", main.args.len() + 1)?;
+    writeln!(
+        out,
+        "    bril_error(\"mismatched main argument arity: expected {}\");",
+        main.args.len()
+    )?;
+    writeln!(out, "  }}")?;
+    let mut args = vec![];
+    for (i, arg) in main.args.iter().enumerate() {
+        let parse = match arg.arg_type {
+            Type::Int => "bril_parse_int",
+            Type::Bool => "bril_parse_bool",
+            Type::Float => "bril_parse_float",
+            Type::Char => "bril_parse_char",
+            Type::Pointer(_) => unreachable!("main shouldn't take pointers"),
+        };
+        args.push(format!("{parse}(argv[{}])", i + 1));
+    }
+    writeln!(out, "  {}({});", mangle("f_", &main.name), args.join(", "))?;
+    writeln!(out, "  return 0;")?;
+    writeln!(out, "}}")
+}
+
+// the whole program as a single C file
+pub fn emit_c(program: &Program) -> String {
+    let mut out = PRELUDE.to_string();
+    let write = |out: &mut String| -> std::fmt::Result {
+        writeln!(out)?;
+        // prototypes first, so functions can call each other in any order
+        for function in program.functions.iter() {
+            writeln!(out, "{};", signature(function))?;
+        }
+        for function in program.functions.iter() {
+            writeln!(out)?;
+            emit_function(out, function)?;
+        }
+        if let Some(main) = program.functions.iter().find(|f| f.name == "main") {
+            writeln!(out)?;
+            emit_main(out, main)?;
+        }
+        Ok(())
+    };
+    write(&mut out).expect("writing to a string should succeed");
+    out
+}
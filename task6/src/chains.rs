@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use bril_rs::{Code, Function, Instruction, Position};
+
+use crate::{
+    cfg::get_label,
+    df::{DataFlowAnalysis, DefSite, ReachingDefs, reaching_defs},
+    dom::rev_graph,
+};
+
+// block id, instruction index within the block
+pub type Site = (usize, usize);
+
+pub struct Chains {
+    // (use, var) -> definitions of var reaching the use, empty if none does, i.e. var is
+    // undefined on every path to the use
+    pub use_def: HashMap<(Site, String), Vec<DefSite>>,
+    // definition -> uses it reaches
+    pub def_use: HashMap<DefSite, Vec<Site>>,
+}
+
+fn get_uses(code: &Code) -> Vec<String> {
+    let mut uses = match code {
+        Code::Instruction(Instruction::Value { args, .. } | Instruction::Effect { args, .. }) => {
+            args.clone()
+        }
+        _ => vec![],
+    };
+    uses.sort();
+    uses.dedup();
+    uses
+}
+
+pub fn build_chains(
+    function: &Function,
+    blocks: &Vec<Vec<Code>>,
+    succ: &Vec<Vec<usize>>,
+) -> Chains {
+    let pred = rev_graph(succ);
+    let ((in_, out), _) = reaching_defs(&function.args, blocks, &pred, succ);
+    let points = ReachingDefs::program_points(blocks, &in_, &out);
+
+    let mut chains = Chains {
+        use_def: HashMap::new(),
+        def_use: HashMap::new(),
+    };
+    for (b, (block, points)) in blocks.iter().zip(points).enumerate() {
+        for (i, code) in block.iter().enumerate() {
+            for var in get_uses(code) {
+                let mut defs: Vec<DefSite> = points[i].get(&var).iter().copied().collect();
+                defs.sort();
+                for def in defs.iter() {
+                    chains.def_use.entry(*def).or_default().push((b, i));
+                }
+                chains.use_def.insert(((b, i), var), defs);
+            }
+        }
+    }
+
+    chains
+}
+
+pub fn get_pos(code: &Code) -> Option<Position> {
+    match code {
+        Code::Label { pos, .. } => pos.clone(),
+        Code::Instruction(instr) => instr.get_pos(),
+    }
+}
+
+// "row:col instr" for instructions, "argument name" for arguments
+pub fn describe_site(function: &Function, blocks: &Vec<Vec<Code>>, site: DefSite) -> String {
+    match site {
+        DefSite::Arg(i) => format!("argument {}", function.args[i].name),
+        DefSite::Instr(b, i) => {
+            let code = &blocks[b][i];
+            match get_pos(code) {
+                Some(pos) => format!(
+                    "{}:{} {}",
+                    pos.pos.row,
+                    pos.pos.col,
+                    code.to_string().trim()
+                ),
+                None => format!(".{}[{i}] {}", get_label(blocks, b), code.to_string().trim()),
+            }
+        }
+    }
+}
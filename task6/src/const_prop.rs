@@ -0,0 +1,249 @@
+use bril_rs::{Code, ConstOps, EffectOps, Function, Instruction, Literal, ValueOps};
+
+use crate::{
+    cfg::{form_cfg, get_basic_blocks},
+    df::DataFlowAnalysis,
+    dom::rev_graph,
+    lattice::{Lattice, MapLattice},
+};
+
+// undefined < constant < varying
+#[derive(Clone, Debug)]
+pub enum ConstValue {
+    Undefined,
+    Const(Literal),
+    Varying,
+}
+
+impl PartialEq for ConstValue {
+    fn eq(&self, other: &Self) -> bool {
+        use ConstValue::*;
+        match (self, other) {
+            (Undefined, Undefined) | (Varying, Varying) => true,
+            // compare floats bitwise, otherwise a NaN constant would never reach a fixed point
+            (Const(Literal::Float(a)), Const(Literal::Float(b))) => a.to_bits() == b.to_bits(),
+            (Const(a), Const(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Lattice for ConstValue {
+    fn top() -> Self {
+        ConstValue::Varying
+    }
+
+    fn bottom() -> Self {
+        ConstValue::Undefined
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        use ConstValue::*;
+        match (self, other) {
+            (Varying, x) | (x, Varying) => x.clone(),
+            (Const(_), Const(_)) if self == other => self.clone(),
+            _ => Undefined,
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        use ConstValue::*;
+        match (self, other) {
+            (Undefined, x) | (x, Undefined) => x.clone(),
+            (Const(_), Const(_)) if self == other => self.clone(),
+            _ => Varying,
+        }
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        use ConstValue::*;
+        match (self, other) {
+            (Undefined, _) | (_, Varying) => true,
+            (Const(_), Const(_)) => self == other,
+            _ => false,
+        }
+    }
+}
+
+// None when the operation isn't foldable, e.g. division by zero or calls
+pub fn eval(op: ValueOps, args: &[&Literal]) -> Option<Literal> {
+    use Literal::*;
+    use ValueOps::*;
+
+    let result = match (op, args) {
+        (Id, [a]) => (*a).clone(),
+
+        (Add, [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+        (Sub, [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+        (Mul, [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+        (Div, [Int(a), Int(b)]) if *b != 0 => Int(a.wrapping_div(*b)),
+        (Eq, [Int(a), Int(b)]) => Bool(a == b),
+        (Lt, [Int(a), Int(b)]) => Bool(a < b),
+        (Gt, [Int(a), Int(b)]) => Bool(a > b),
+        (Le, [Int(a), Int(b)]) => Bool(a <= b),
+        (Ge, [Int(a), Int(b)]) => Bool(a >= b),
+
+        (Not, [Bool(a)]) => Bool(!a),
+        (And, [Bool(a), Bool(b)]) => Bool(*a && *b),
+        (Or, [Bool(a), Bool(b)]) => Bool(*a || *b),
+
+        (Fadd, [Float(a), Float(b)]) => Float(a + b),
+        (Fsub, [Float(a), Float(b)]) => Float(a - b),
+        (Fmul, [Float(a), Float(b)]) => Float(a * b),
+        (Fdiv, [Float(a), Float(b)]) => Float(a / b),
+        (Feq, [Float(a), Float(b)]) => Bool(a == b),
+        (Flt, [Float(a), Float(b)]) => Bool(a < b),
+        (Fgt, [Float(a), Float(b)]) => Bool(a > b),
+        (Fle, [Float(a), Float(b)]) => Bool(a <= b),
+        (Fge, [Float(a), Float(b)]) => Bool(a >= b),
+
+        (Ceq, [Char(a), Char(b)]) => Bool(a == b),
+        (Clt, [Char(a), Char(b)]) => Bool(a < b),
+        (Cgt, [Char(a), Char(b)]) => Bool(a > b),
+        (Cle, [Char(a), Char(b)]) => Bool(a <= b),
+        (Cge, [Char(a), Char(b)]) => Bool(a >= b),
+        (Char2int, [Char(a)]) => Int(*a as i64),
+        (Int2char, [Int(a)]) => Char(char::from_u32(u32::try_from(*a).ok()?)?),
+
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn eval_value(op: ValueOps, args: &[String], state: &MapLattice<String, ConstValue>) -> ConstValue {
+    let values: Vec<_> = args.iter().map(|arg| state.get(arg)).collect();
+
+    // short circuits are constant no matter what the other operand is
+    match (op, values.as_slice()) {
+        (ValueOps::And, [ConstValue::Const(Literal::Bool(false)), _])
+        | (ValueOps::And, [_, ConstValue::Const(Literal::Bool(false))]) => {
+            return ConstValue::Const(Literal::Bool(false));
+        }
+        (ValueOps::Or, [ConstValue::Const(Literal::Bool(true)), _])
+        | (ValueOps::Or, [_, ConstValue::Const(Literal::Bool(true))]) => {
+            return ConstValue::Const(Literal::Bool(true));
+        }
+        _ => {}
+    }
+
+    if values
+        .iter()
+        .any(|value| matches!(value, ConstValue::Undefined))
+    {
+        return ConstValue::Undefined;
+    }
+
+    let literals: Option<Vec<&Literal>> = values
+        .iter()
+        .map(|value| match value {
+            ConstValue::Const(literal) => Some(literal),
+            _ => None,
+        })
+        .collect();
+
+    match literals.and_then(|literals| eval(op, &literals)) {
+        Some(literal) => ConstValue::Const(literal),
+        None => ConstValue::Varying,
+    }
+}
+
+pub struct ConstantPropagation;
+
+impl DataFlowAnalysis for ConstantPropagation {
+    const FORWARD: bool = true;
+
+    // var -> value, vars never assigned on a path are undefined there
+    type State = MapLattice<String, ConstValue>;
+
+    fn transfer(block: &Vec<Code>, _block_id: usize, in_: &Self::State) -> Self::State {
+        let mut out = in_.clone();
+
+        for code in block {
+            if let Code::Instruction(instr) = code {
+                match instr {
+                    Instruction::Constant { dest, value, .. } => {
+                        out.insert(dest.clone(), ConstValue::Const(value.clone()));
+                    }
+                    Instruction::Value { args, dest, op, .. } => {
+                        let value = eval_value(*op, args, &out);
+                        out.insert(dest.clone(), value);
+                    }
+                    Instruction::Effect { .. } => {}
+                }
+            }
+        }
+
+        out
+    }
+
+    // arguments and anything else we know nothing about at the entry vary
+    fn boundary_state() -> Self::State {
+        Self::State::top()
+    }
+}
+
+// replaces instructions that always compute the same constant with `const`, and branches on a
+// constant condition with jumps
+pub fn propagate_constants(function: &mut Function) -> bool {
+    let mut blocks = get_basic_blocks(function);
+    let succ = form_cfg(&blocks);
+    let pred = rev_graph(&succ);
+
+    let (in_, out) = ConstantPropagation::find(&blocks, &pred, &succ);
+    let points = ConstantPropagation::program_points(&blocks, &in_, &out);
+
+    let mut changed = false;
+    for (block, points) in blocks.iter_mut().zip(points) {
+        for (i, code) in block.iter_mut().enumerate() {
+            match code {
+                Code::Instruction(Instruction::Value {
+                    dest,
+                    op,
+                    op_type,
+                    pos,
+                    ..
+                }) if *op != ValueOps::Call => {
+                    if let ConstValue::Const(value) = points[i + 1].get(dest) {
+                        *code = Code::Instruction(Instruction::Constant {
+                            dest: dest.clone(),
+                            op: ConstOps::Const,
+                            pos: pos.clone(),
+                            const_type: op_type.clone(),
+                            value: value.clone(),
+                        });
+                        changed = true;
+                    }
+                }
+                Code::Instruction(Instruction::Effect {
+                    op: EffectOps::Branch,
+                    args,
+                    labels,
+                    pos,
+                    ..
+                }) => {
+                    if let ConstValue::Const(Literal::Bool(cond)) = points[i].get(&args[0]) {
+                        let target = if *cond {
+                            labels[0].clone()
+                        } else {
+                            labels[1].clone()
+                        };
+                        *code = Code::Instruction(Instruction::Effect {
+                            args: vec![],
+                            funcs: vec![],
+                            labels: vec![target],
+                            op: EffectOps::Jump,
+                            pos: pos.clone(),
+                        });
+                        changed = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    function.instrs = blocks.into_iter().flatten().collect();
+
+    changed
+}
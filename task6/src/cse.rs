@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::{Code, Function, Instruction, ValueOps};
+
+use crate::{
+    avail::{AvailableExpressions, Expr, get_expr},
+    cfg::{form_cfg, get_basic_blocks},
+    df::DataFlowAnalysis,
+    dom::rev_graph,
+};
+
+pub(crate) fn fresh_name(base: &str, used: &mut HashSet<String>) -> String {
+    let mut name = base.to_string();
+    let mut i = 0;
+    while used.contains(&name) {
+        name = format!("{base}.{i}");
+        i += 1;
+    }
+    used.insert(name.clone());
+    name
+}
+
+pub(crate) fn get_vars(function: &Function) -> HashSet<String> {
+    let mut vars: HashSet<String> = function.args.iter().map(|arg| arg.name.clone()).collect();
+    for code in function.instrs.iter() {
+        match code {
+            Code::Instruction(Instruction::Constant { dest, .. }) => {
+                vars.insert(dest.clone());
+            }
+            Code::Instruction(Instruction::Value { args, dest, .. }) => {
+                vars.insert(dest.clone());
+                vars.extend(args.iter().cloned());
+            }
+            Code::Instruction(Instruction::Effect { args, .. }) => {
+                vars.extend(args.iter().cloned());
+            }
+            Code::Label { .. } => {}
+        }
+    }
+    vars
+}
+
+fn copy(dest: String, src: String, instr: &Instruction) -> Code {
+    let (op_type, pos) = match instr {
+        Instruction::Value { op_type, pos, .. } => (op_type.clone(), pos.clone()),
+        _ => unreachable!("only value instructions compute expressions"),
+    };
+    Code::Instruction(Instruction::Value {
+        args: vec![src],
+        dest,
+        funcs: vec![],
+        labels: vec![],
+        op: ValueOps::Id,
+        pos,
+        op_type,
+    })
+}
+
+// global common subexpression elimination: every computation of an expression that is already
+// available becomes a copy from a temporary, which each earlier computation sets
+// returns the number of replaced computations
+pub fn eliminate_common_subexprs(function: &mut Function) -> usize {
+    let blocks = get_basic_blocks(function);
+    let succ = form_cfg(&blocks);
+    let pred = rev_graph(&succ);
+
+    let (in_, out) = AvailableExpressions::find(&blocks, &pred, &succ);
+    let points = AvailableExpressions::program_points(&blocks, &in_, &out);
+
+    let mut redundant: Vec<Expr> = vec![];
+    for (block, points) in blocks.iter().zip(points.iter()) {
+        for (i, code) in block.iter().enumerate() {
+            if let Code::Instruction(instr) = code
+                && let Some(expr) = get_expr(instr)
+                && points[i].contains(&expr)
+                && !redundant.contains(&expr)
+            {
+                redundant.push(expr);
+            }
+        }
+    }
+
+    if redundant.is_empty() {
+        return 0;
+    }
+
+    let mut vars = get_vars(function);
+    let temps: HashMap<Expr, String> = redundant
+        .into_iter()
+        .map(|expr| {
+            let temp = fresh_name(&format!("cse.{}", expr.op), &mut vars);
+            (expr, temp)
+        })
+        .collect();
+
+    let mut replaced = 0;
+    let mut instrs = vec![];
+    for (block, points) in blocks.into_iter().zip(points) {
+        for (i, code) in block.into_iter().enumerate() {
+            let Code::Instruction(instr) = &code else {
+                instrs.push(code);
+                continue;
+            };
+            let Some((expr, temp)) = get_expr(instr).and_then(|expr| temps.get_key_value(&expr))
+            else {
+                instrs.push(code);
+                continue;
+            };
+            let Instruction::Value { dest, .. } = instr else {
+                unreachable!("only value instructions compute expressions");
+            };
+
+            if points[i].contains(expr) {
+                instrs.push(copy(dest.clone(), temp.clone(), instr));
+                replaced += 1;
+            } else if !expr.uses(dest) {
+                let save = copy(temp.clone(), dest.clone(), instr);
+                instrs.push(code);
+                instrs.push(save);
+            } else {
+                // the expression is killed right away, so no later computation can reuse it
+                instrs.push(code);
+            }
+        }
+    }
+
+    function.instrs = instrs;
+
+    replaced
+}
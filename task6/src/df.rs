@@ -1,119 +1,500 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::{Duration, Instant},
+};
 
-use bril_rs::{Code, Instruction};
+use bril_rs::{Argument, Code, Instruction};
+
+use crate::{
+    bitvec::{BitSet, BitVectorProblem, Interner},
+    dom::postorder,
+    lattice::{Lattice, MapLattice, Powerset},
+};
+
+#[derive(Default, Debug, Clone)]
+pub struct SolverStats {
+    // sweeps over the blocks in priority order
+    pub iterations: usize,
+    pub transfer_calls: usize,
+    pub time: Duration,
+}
+
+// in and out states of every block
+pub type Solution<S> = (Vec<S>, Vec<S>);
+
+// reverse postorder for forward analyses, postorder for backward ones, unreachable blocks last
+pub(crate) fn solve_order(succ: &Vec<Vec<usize>>, forward: bool) -> Vec<usize> {
+    let mut order = vec![];
+    let mut visited = vec![false; succ.len()];
+    for u in 0..succ.len() {
+        if !visited[u] {
+            let mut component = vec![];
+            postorder(u, succ, &mut visited, &mut component);
+            if forward {
+                component.reverse();
+            }
+            order.extend(component);
+        }
+    }
+    order
+}
 
 pub trait DataFlowAnalysis {
     const FORWARD: bool;
-    type State: Clone + PartialEq;
+    // may-analyses merge with join and rise from bottom,
+    // must-analyses merge with meet and fall from top
+    const MAY: bool = true;
+    type State: Lattice;
 
-    fn merge(inputs: &Vec<&Self::State>) -> Self::State;
+    fn merge(inputs: &Vec<&Self::State>) -> Self::State {
+        if Self::MAY {
+            inputs
+                .iter()
+                .fold(Self::State::bottom(), |accum, input| accum.join(input))
+        } else {
+            inputs
+                .iter()
+                .fold(Self::State::top(), |accum, input| accum.meet(input))
+        }
+    }
     fn transfer(block: &Vec<Code>, block_id: usize, input: &Self::State) -> Self::State;
-    fn initial_state() -> Self::State;
+    // transfer over instruction instr_id of a block, by default the block transfer of a
+    // one-instruction block
+    fn transfer_instr(
+        code: &Code,
+        block_id: usize,
+        _instr_id: usize,
+        input: &Self::State,
+    ) -> Self::State {
+        Self::transfer(&vec![code.clone()], block_id, input)
+    }
+    // value flowing into the entry block (forward) or out of the exit blocks (backward)
+    fn boundary_state() -> Self::State;
+    // value every block starts from before the first iteration
+    fn initial_state() -> Self::State {
+        if Self::MAY {
+            Self::State::bottom()
+        } else {
+            Self::State::top()
+        }
+    }
+    // applied at loop headers to the old value and what comes back around the loop, must
+    // eventually stop growing for lattices of infinite height
+    fn widen(_old: &Self::State, new: Self::State) -> Self::State {
+        new
+    }
+    // same, while sweeping over the fixed point once it's reached
+    fn narrow(_old: &Self::State, new: Self::State) -> Self::State {
+        new
+    }
+    const NARROWING_PASSES: usize = 0;
+    // state flowing along the edge from `from` to `to`, None if it's just the out of `from`
+    // only used by forward analyses
+    fn refine_edge(_from: &Vec<Code>, _to: &Vec<Code>, _out: &Self::State) -> Option<Self::State> {
+        None
+    }
     // returns in, out
     fn find(
         blocks: &Vec<Vec<Code>>,
         pred: &Vec<Vec<usize>>,
         succ: &Vec<Vec<usize>>,
-    ) -> (Vec<Self::State>, Vec<Self::State>) {
+    ) -> Solution<Self::State> {
+        Self::find_with_stats(blocks, pred, succ).0
+    }
+    fn find_with_stats(
+        blocks: &Vec<Vec<Code>>,
+        pred: &Vec<Vec<usize>>,
+        succ: &Vec<Vec<usize>>,
+    ) -> (Solution<Self::State>, SolverStats) {
+        Self::find_with_boundary(blocks, pred, succ, Self::boundary_state())
+    }
+    // for boundaries that depend on the function, e.g. its arguments
+    fn find_with_boundary(
+        blocks: &Vec<Vec<Code>>,
+        pred: &Vec<Vec<usize>>,
+        succ: &Vec<Vec<usize>>,
+        boundary: Self::State,
+    ) -> (Solution<Self::State>, SolverStats) {
+        let start = Instant::now();
+        let mut stats = SolverStats::default();
+
         let mut in_: Vec<Self::State> = vec![Self::initial_state(); blocks.len()];
         let mut out: Vec<Self::State> = vec![Self::initial_state(); blocks.len()];
-        let mut worklist: Vec<_> = (0..blocks.len()).collect();
-
-        while let Some(b) = worklist.pop() {
-            if Self::FORWARD {
-                let out_preds = pred
-                    .get(b)
-                    .expect("b should be in pred")
-                    .iter()
-                    .map(|s| out.get(*s).expect("s should be in out"))
-                    .collect::<Vec<_>>();
-                in_[b] = Self::merge(&out_preds);
-
-                let out_b = Self::transfer(
-                    blocks.get(b).expect("b should be in blocks"),
-                    b,
-                    in_.get(b).expect("b should be in in_"),
-                );
-                if out[b] != out_b {
-                    worklist.extend(succ.get(b).expect("b should be in succ"));
-                }
-                out[b] = out_b;
+
+        // the worklist holds priorities, so every block is queued at most once and the block
+        // earliest in the order is always visited first
+        let order = solve_order(succ, Self::FORWARD);
+        let mut priority = vec![0; blocks.len()];
+        for (p, &b) in order.iter().enumerate() {
+            priority[b] = p;
+        }
+        let (sources, targets) = if Self::FORWARD {
+            (pred, succ)
+        } else {
+            (succ, pred)
+        };
+        // blocks entered by an edge going back in the order, i.e. loop headers
+        let is_header: Vec<bool> = (0..blocks.len())
+            .map(|b| sources[b].iter().any(|s| priority[*s] >= priority[b]))
+            .collect();
+
+        let mut worklist: BTreeSet<usize> = (0..blocks.len()).collect();
+        let mut last = None;
+
+        while let Some(p) = worklist.pop_first() {
+            let b = order[p];
+            if last.is_none_or(|last| p <= last) {
+                stats.iterations += 1;
+            }
+            last = Some(p);
+            stats.transfer_calls += 1;
+
+            let old = if Self::FORWARD { &in_[b] } else { &out[b] };
+            let merged = merge_input::<Self>(
+                blocks,
+                sources,
+                &priority,
+                b,
+                (&in_, &out),
+                &boundary,
+                is_header[b].then_some((old, Self::widen as LoopOp<Self>)),
+            );
+            let (before, after) = if Self::FORWARD {
+                (&mut in_, &mut out)
             } else {
-                let in_succs = succ
-                    .get(b)
-                    .expect("b should be in succ")
-                    .iter()
-                    .map(|s| in_.get(*s).expect("s should be in in_"))
-                    .collect::<Vec<_>>();
-                out[b] = Self::merge(&in_succs);
-
-                let in_b = Self::transfer(
-                    blocks.get(b).expect("b should be in blocks"),
+                (&mut out, &mut in_)
+            };
+            before[b] = merged;
+
+            let after_b = Self::transfer(
+                blocks.get(b).expect("b should be in blocks"),
+                b,
+                before.get(b).expect("b should be in before"),
+            );
+            debug_assert_monotone::<Self>(&after[b], &after_b);
+            if after[b] != after_b {
+                worklist.extend(targets[b].iter().map(|t| priority[*t]));
+            }
+            after[b] = after_b;
+        }
+
+        // the widened fixed point can only be refined by sweeping over it again
+        for _ in 0..Self::NARROWING_PASSES {
+            stats.iterations += 1;
+            for &b in order.iter() {
+                stats.transfer_calls += 1;
+
+                let old = if Self::FORWARD { &in_[b] } else { &out[b] };
+                let merged = merge_input::<Self>(
+                    blocks,
+                    sources,
+                    &priority,
                     b,
-                    out.get(b).expect("b should be in out"),
+                    (&in_, &out),
+                    &boundary,
+                    is_header[b].then_some((old, Self::narrow as LoopOp<Self>)),
                 );
-                if in_[b] != in_b {
-                    worklist.extend(pred.get(b).expect("b should be in pred"));
+                let (before, after) = if Self::FORWARD {
+                    (&mut in_, &mut out)
+                } else {
+                    (&mut out, &mut in_)
+                };
+                before[b] = merged;
+                after[b] = Self::transfer(&blocks[b], b, &before[b]);
+            }
+        }
+
+        stats.time = start.elapsed();
+        ((in_, out), stats)
+    }
+
+    // facts at every program point, computed from the solved in/out by replaying transfer_instr
+    // block -> point i is right before instruction i, the last point is right after the block
+    fn program_points(
+        blocks: &Vec<Vec<Code>>,
+        in_: &Vec<Self::State>,
+        out: &Vec<Self::State>,
+    ) -> Vec<Vec<Self::State>> {
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(b, block)| {
+                if Self::FORWARD {
+                    let mut points = vec![in_[b].clone()];
+                    for (i, code) in block.iter().enumerate() {
+                        let after = Self::transfer_instr(code, b, i, points.last().unwrap());
+                        points.push(after);
+                    }
+                    points
+                } else {
+                    let mut points = vec![out[b].clone()];
+                    for (i, code) in block.iter().enumerate().rev() {
+                        let before = Self::transfer_instr(code, b, i, points.last().unwrap());
+                        points.push(before);
+                    }
+                    points.reverse();
+                    points
                 }
-                in_[b] = in_b;
+            })
+            .collect()
+    }
+}
+
+// prints the blocks with the fact computed by each instruction as a trailing comment,
+// i.e. the fact after it for forward analyses and before it for backward ones
+pub fn print_annotated<A: DataFlowAnalysis>(
+    blocks: &[Vec<Code>],
+    points: &[Vec<A::State>],
+    fmt: impl Fn(&A::State) -> String,
+) {
+    for (block, points) in blocks.iter().zip(points) {
+        let mut codes = block.iter().enumerate().peekable();
+
+        if let Some((_, label @ Code::Label { .. })) = codes.peek() {
+            println!("{label}");
+            codes.next();
+        }
+        if A::FORWARD {
+            println!("  # in: {}", fmt(&points[0]));
+        }
+
+        for (i, code) in codes {
+            let fact = if A::FORWARD {
+                &points[i + 1]
+            } else {
+                &points[i]
+            };
+            println!("{code}  # {}", fmt(fact));
+        }
+
+        if !A::FORWARD {
+            println!("  # out: {}", fmt(points.last().unwrap()));
+        }
+    }
+}
+
+// merge of what flows into b: refined outs of its predecessors for forward analyses, ins of its
+// successors for backward ones, plus the boundary where it applies
+// at loop headers, only what comes back around the loop goes through `loop_op` with the old value
+fn merge_input<A: DataFlowAnalysis + ?Sized>(
+    blocks: &Vec<Vec<Code>>,
+    sources: &Vec<Vec<usize>>,
+    priority: &[usize],
+    b: usize,
+    (in_, out): (&Vec<A::State>, &Vec<A::State>),
+    boundary: &A::State,
+    loop_op: Option<(&A::State, LoopOp<A>)>,
+) -> A::State {
+    let refined: Vec<Option<A::State>> = sources[b]
+        .iter()
+        .map(|s| {
+            if A::FORWARD {
+                A::refine_edge(&blocks[*s], &blocks[b], &out[*s])
+            } else {
+                None
             }
+        })
+        .collect();
+    let mut inputs: Vec<&A::State> = vec![];
+    let mut back_inputs: Vec<&A::State> = vec![];
+    for (s, refined) in sources[b].iter().zip(refined.iter()) {
+        let input = match refined {
+            Some(state) => state,
+            None if A::FORWARD => &out[*s],
+            None => &in_[*s],
+        };
+        if loop_op.is_some() && priority[*s] >= priority[b] {
+            back_inputs.push(input);
+        } else {
+            inputs.push(input);
         }
+    }
+    if (A::FORWARD && b == 0) || (!A::FORWARD && sources[b].is_empty()) {
+        inputs.push(boundary);
+    }
 
-        (in_, out)
+    let looped = loop_op.map(|(old, op)| op(old, A::merge(&back_inputs)));
+    if let Some(looped) = looped.as_ref() {
+        inputs.push(looped);
     }
+    A::merge(&inputs)
+}
+
+type LoopOp<A> = fn(
+    &<A as DataFlowAnalysis>::State,
+    <A as DataFlowAnalysis>::State,
+) -> <A as DataFlowAnalysis>::State;
+
+// starting from the initial state, a monotone transfer only ever moves a block's result in the
+// direction of the merge
+fn debug_assert_monotone<A: DataFlowAnalysis + ?Sized>(old: &A::State, new: &A::State) {
+    debug_assert!(
+        if A::MAY { old.leq(new) } else { new.leq(old) },
+        "transfer function should be monotone"
+    );
+}
+
+// where a variable gets its value
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum DefSite {
+    // the function argument at this index, defined on entry
+    Arg(usize),
+    // block id, instruction index within the block
+    Instr(usize, usize),
 }
 
 pub struct ReachingDefs;
 
-fn get_defs(block: &Vec<Code>) -> Vec<String> {
-    let mut defs = Vec::new();
-    for code in block {
-        if let Code::Instruction(
-            Instruction::Constant { dest, .. } | Instruction::Value { dest, .. },
-        ) = code
-        {
-            defs.push(dest.clone());
+fn get_def(code: &Code) -> Option<&String> {
+    match code {
+        Code::Instruction(Instruction::Constant { dest, .. } | Instruction::Value { dest, .. }) => {
+            Some(dest)
         }
+        _ => None,
     }
-    defs
 }
 
 impl DataFlowAnalysis for ReachingDefs {
     const FORWARD: bool = true;
 
-    // var -> block ids
-    type State = HashMap<String, HashSet<usize>>;
+    // var -> definition sites, merge = union
+    type State = MapLattice<String, Powerset<DefSite>>;
 
-    // merge = union
-    fn merge(inputs: &Vec<&Self::State>) -> Self::State {
-        let mut merged: Self::State = HashMap::new();
-        for input in inputs {
-            for (var, block_ids) in input.iter() {
-                merged.entry(var.clone()).or_default().extend(block_ids);
+    // out = def U (in - kill)
+    fn transfer(block: &Vec<Code>, block_id: usize, in_: &Self::State) -> Self::State {
+        let mut out = in_.clone();
+
+        // inserting replaces the definitions coming in, which is both in - kill and def U (in - kill)
+        for (i, code) in block.iter().enumerate() {
+            if let Some(var) = get_def(code) {
+                out.insert(
+                    var.clone(),
+                    [DefSite::Instr(block_id, i)].into_iter().collect(),
+                );
             }
         }
-        merged
+
+        out
     }
 
-    // out = def U (in - kill)
-    fn transfer(block: &Vec<Code>, block_id: usize, in_: &Self::State) -> Self::State {
+    fn transfer_instr(
+        code: &Code,
+        block_id: usize,
+        instr_id: usize,
+        in_: &Self::State,
+    ) -> Self::State {
         let mut out = in_.clone();
+        if let Some(var) = get_def(code) {
+            out.insert(
+                var.clone(),
+                [DefSite::Instr(block_id, instr_id)].into_iter().collect(),
+            );
+        }
+        out
+    }
+
+    // nothing is known about the function here, solve with `arg_defs` as the boundary instead to
+    // count the arguments as definitions
+    fn boundary_state() -> Self::State {
+        Self::State::bottom()
+    }
+}
+
+// the arguments, defined on entry to the function
+pub fn arg_defs(args: &[Argument]) -> <ReachingDefs as DataFlowAnalysis>::State {
+    let mut state = <ReachingDefs as DataFlowAnalysis>::State::bottom();
+    for (i, arg) in args.iter().enumerate() {
+        state.insert(arg.name.clone(), [DefSite::Arg(i)].into_iter().collect());
+    }
+    state
+}
 
-        let defs = get_defs(&block);
+// bit-vector version of ReachingDefs solved from `arg_defs`, same results
+// a definition is a (var, site) pair
+pub fn reaching_defs(
+    args: &[Argument],
+    blocks: &Vec<Vec<Code>>,
+    pred: &Vec<Vec<usize>>,
+    succ: &Vec<Vec<usize>>,
+) -> (
+    Solution<<ReachingDefs as DataFlowAnalysis>::State>,
+    SolverStats,
+) {
+    let mut defs = Interner::new();
+    let mut var_defs: HashMap<String, Vec<usize>> = HashMap::new();
+    let arg_sites = args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| (arg.name.clone(), DefSite::Arg(i)));
+    let instr_sites = blocks.iter().enumerate().flat_map(|(b, block)| {
+        block.iter().enumerate().filter_map(move |(i, code)| {
+            get_def(code).map(|var| (var.clone(), DefSite::Instr(b, i)))
+        })
+    });
+    for def in arg_sites.chain(instr_sites) {
+        let id = defs.intern(&def);
+        var_defs.entry(def.0).or_default().push(id);
+    }
 
-        // in - kill
-        // remove previous definitions that are overwritten in the current block (killed)
-        out.retain(|var, _| !defs.contains(var));
+    let mut boundary = BitSet::new(defs.len());
+    for (i, arg) in args.iter().enumerate() {
+        boundary.insert(
+            defs.get(&(arg.name.clone(), DefSite::Arg(i)))
+                .expect("def should be interned"),
+        );
+    }
 
-        // out U (in - kill)
-        for var in defs {
-            out.entry(var).or_default().insert(block_id);
+    let mut gens = Vec::new();
+    let mut kills = Vec::new();
+    for (b, block) in blocks.iter().enumerate() {
+        // a block kills every definition of the vars it defines, and generates the last one of each
+        let mut last_defs: HashMap<&String, usize> = HashMap::new();
+        for (i, code) in block.iter().enumerate() {
+            if let Some(var) = get_def(code) {
+                last_defs.insert(var, i);
+            }
         }
 
-        out
+        let mut gen_ = BitSet::new(defs.len());
+        let mut kill = BitSet::new(defs.len());
+        for (var, i) in last_defs {
+            for &def in var_defs[var].iter() {
+                kill.insert(def);
+            }
+            gen_.insert(
+                defs.get(&(var.clone(), DefSite::Instr(b, i)))
+                    .expect("def should be interned"),
+            );
+        }
+        gens.push(gen_);
+        kills.push(kill);
     }
 
-    fn initial_state() -> Self::State {
-        HashMap::new()
-    }
+    let problem = BitVectorProblem {
+        forward: true,
+        may: true,
+        width: defs.len(),
+        gens,
+        kills,
+        boundary,
+    };
+    let ((in_, out), stats) = problem.solve(pred, succ);
+
+    let to_state = |set: &BitSet| {
+        let mut state = <ReachingDefs as DataFlowAnalysis>::State::bottom();
+        for def in set.iter() {
+            let (var, site) = defs.value(def);
+            let mut sites = state.get(var).clone();
+            sites.insert(*site);
+            state.insert(var.clone(), sites);
+        }
+        state
+    };
+    (
+        (
+            in_.iter().map(to_state).collect(),
+            out.iter().map(to_state).collect(),
+        ),
+        stats,
+    )
 }
@@ -1,6 +1,11 @@
 use std::collections::HashSet;
 
-fn postorder(u: usize, graph: &Vec<Vec<usize>>, visited: &mut Vec<bool>, order: &mut Vec<usize>) {
+pub fn postorder(
+    u: usize,
+    graph: &Vec<Vec<usize>>,
+    visited: &mut Vec<bool>,
+    order: &mut Vec<usize>,
+) {
     visited[u] = true;
     for v in graph[u].iter() {
         if !visited[*v] {
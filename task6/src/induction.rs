@@ -0,0 +1,520 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use bril_rs::{Code, Function, Instruction, Type, ValueOps};
+
+use crate::{
+    cfg::{form_cfg, get_basic_blocks, get_label},
+    chains::Site,
+    cse::{fresh_name, get_vars},
+    df::DataFlowAnalysis,
+    dom::rev_graph,
+    intervals::{Interval, IntervalAnalysis},
+    loops::{NaturalLoop, find_natural_loops, insert_preheader},
+    undef::{Definedness, MaybeUndefined, entry_state},
+};
+
+// var = var + step, or var - step if negated, once each time around the loop
+pub struct BasicVar {
+    pub var: String,
+    pub step: String,
+    pub negated: bool,
+    pub site: Site,
+}
+
+// var = base * factor + offset for a basic variable base, computed at site
+pub struct DerivedVar {
+    pub var: String,
+    pub base: String,
+    pub factor: String,
+    pub offset: Option<String>,
+    pub site: Site,
+}
+
+pub struct InductionVars {
+    pub basic: Vec<BasicVar>,
+    pub derived: Vec<DerivedVar>,
+}
+
+impl Display for BasicVar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.negated { "-" } else { "+" };
+        write!(f, "{} = {} {sign} {}", self.var, self.var, self.step)
+    }
+}
+
+impl Display for DerivedVar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {} * {}", self.var, self.base, self.factor)?;
+        if let Some(offset) = &self.offset {
+            write!(f, " + {offset}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct ReductionStats {
+    // multiplications replaced by additions carried through the loop
+    pub reduced: usize,
+    // exit tests moved from a basic variable to a derived one
+    pub tests_replaced: usize,
+}
+
+pub(crate) fn get_instr(blocks: &[Vec<Code>], (b, i): Site) -> &Instruction {
+    match &blocks[b][i] {
+        Code::Instruction(instr) => instr,
+        Code::Label { .. } => unreachable!("sites should be instructions"),
+    }
+}
+
+// var -> where it's defined inside the loop
+pub(crate) fn get_loop_defs(
+    blocks: &[Vec<Code>],
+    natural_loop: &NaturalLoop,
+) -> HashMap<String, Vec<Site>> {
+    let mut defs: HashMap<String, Vec<Site>> = HashMap::new();
+    for &b in natural_loop.body.iter() {
+        for (i, code) in blocks[b].iter().enumerate() {
+            if let Code::Instruction(
+                Instruction::Constant { dest, .. } | Instruction::Value { dest, .. },
+            ) = code
+            {
+                defs.entry(dest.clone()).or_default().push((b, i));
+            }
+        }
+    }
+    defs
+}
+
+pub fn find_induction_vars(blocks: &[Vec<Code>], natural_loop: &NaturalLoop) -> InductionVars {
+    let defs = get_loop_defs(blocks, natural_loop);
+    let invariant = |var: &String| !defs.contains_key(var);
+
+    // basic variables are defined once in the loop, by stepping themselves
+    let mut basic = vec![];
+    for (var, sites) in defs.iter() {
+        let [site] = sites[..] else {
+            continue;
+        };
+        let Instruction::Value {
+            op: op @ (ValueOps::Add | ValueOps::Sub),
+            args,
+            ..
+        } = get_instr(blocks, site)
+        else {
+            continue;
+        };
+        let step = match (op, &args[..]) {
+            (ValueOps::Add, [a, b]) if a == var && invariant(b) => b,
+            (ValueOps::Add, [a, b]) if b == var && invariant(a) => a,
+            (ValueOps::Sub, [a, b]) if a == var && invariant(b) => b,
+            _ => continue,
+        };
+        basic.push(BasicVar {
+            var: var.clone(),
+            step: step.clone(),
+            negated: *op == ValueOps::Sub,
+            site,
+        });
+    }
+    basic.sort_by_key(|basic_var| basic_var.site);
+    let is_basic = |var: &String| basic.iter().any(|basic_var| basic_var.var == *var);
+
+    let mut derived: Vec<DerivedVar> = vec![];
+    for &b in natural_loop.body.iter() {
+        for (i, code) in blocks[b].iter().enumerate() {
+            let Code::Instruction(Instruction::Value { op, args, dest, .. }) = code else {
+                continue;
+            };
+            match (op, &args[..]) {
+                (ValueOps::Mul, [x, y]) => {
+                    let (base, factor) = if is_basic(x) && invariant(y) {
+                        (x, y)
+                    } else if is_basic(y) && invariant(x) {
+                        (y, x)
+                    } else {
+                        continue;
+                    };
+                    derived.push(DerivedVar {
+                        var: dest.clone(),
+                        base: base.clone(),
+                        factor: factor.clone(),
+                        offset: None,
+                        site: (b, i),
+                    });
+                }
+                // adding to a product computed earlier in the block, with nothing redefined since
+                (ValueOps::Add, [x, y]) => {
+                    let found = [(x, y), (y, x)].into_iter().find_map(|(product, offset)| {
+                        if !invariant(offset) {
+                            return None;
+                        }
+                        let d = derived
+                            .iter()
+                            .rev()
+                            .find(|d| d.site.0 == b && d.var == *product)?;
+                        let redefined = blocks[b][d.site.1 + 1..i].iter().any(|code| {
+                            matches!(
+                                code,
+                                Code::Instruction(
+                                    Instruction::Constant { dest, .. }
+                                    | Instruction::Value { dest, .. },
+                                ) if *dest == d.var || *dest == d.base
+                            )
+                        });
+                        (d.offset.is_none() && !redefined).then(|| DerivedVar {
+                            var: dest.clone(),
+                            base: d.base.clone(),
+                            factor: d.factor.clone(),
+                            offset: Some(offset.clone()),
+                            site: (b, i),
+                        })
+                    });
+                    derived.extend(found);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    InductionVars { basic, derived }
+}
+
+fn compute(dest: &str, op: ValueOps, args: &[&String]) -> Code {
+    Code::Instruction(Instruction::Value {
+        args: args.iter().map(|arg| (*arg).clone()).collect(),
+        dest: dest.to_string(),
+        funcs: vec![],
+        labels: vec![],
+        op,
+        pos: None,
+        op_type: Type::Int,
+    })
+}
+
+// a product carried through the loop in temp
+struct Family<'a> {
+    base: &'a String,
+    factor: &'a String,
+    offset: Option<&'a String>,
+    temp: String,
+}
+
+// the exit test of a basic variable that is needed for nothing else, as (site, other operand)
+fn find_exit_test<'a>(
+    blocks: &'a [Vec<Code>],
+    natural_loop: &NaturalLoop,
+    basic_var: &BasicVar,
+    reduced: &HashSet<Site>,
+) -> Option<(Site, &'a String)> {
+    let mut test = None;
+    for (b, block) in blocks.iter().enumerate() {
+        for (i, code) in block.iter().enumerate() {
+            let Code::Instruction(
+                Instruction::Value { args, .. } | Instruction::Effect { args, .. },
+            ) = code
+            else {
+                continue;
+            };
+            if !args.contains(&basic_var.var)
+                || (b, i) == basic_var.site
+                || reduced.contains(&(b, i))
+            {
+                continue;
+            }
+            let is_test = natural_loop.contains(b)
+                && matches!(
+                    code,
+                    Code::Instruction(Instruction::Value {
+                        op: ValueOps::Lt
+                            | ValueOps::Le
+                            | ValueOps::Gt
+                            | ValueOps::Ge
+                            | ValueOps::Eq,
+                        ..
+                    })
+                )
+                && args.len() == 2
+                && args[0] != args[1];
+            if !is_test || test.is_some() {
+                return None;
+            }
+            let other = if args[0] == basic_var.var {
+                &args[1]
+            } else {
+                &args[0]
+            };
+            test = Some(((b, i), other));
+        }
+    }
+    test
+}
+
+// whether lo..hi times the factor stays within the i64 range, so comparisons on the product
+// order the same way as on the operand
+fn scales_safely(values: Interval, factor: Interval) -> bool {
+    let (Interval::Range(lo, hi), Interval::Range(k_lo, k_hi)) = (values, factor) else {
+        return false;
+    };
+    let bounded = |x: i64| x != i64::MIN && x != i64::MAX;
+    if !(bounded(lo) && bounded(hi) && bounded(k_hi)) || k_lo <= 0 {
+        return false;
+    }
+    [lo, hi].into_iter().all(|x| {
+        [k_lo, k_hi]
+            .into_iter()
+            .all(|k| i64::try_from(x as i128 * k as i128).is_ok())
+    })
+}
+
+fn reduce_loop(
+    function: &Function,
+    blocks: &Vec<Vec<Code>>,
+    pred: &Vec<Vec<usize>>,
+    succ: &Vec<Vec<usize>>,
+    natural_loop: &NaturalLoop,
+    vars: &mut HashSet<String>,
+) -> Option<(Vec<Vec<Code>>, ReductionStats)> {
+    let ivs = find_induction_vars(blocks, natural_loop);
+    if ivs.derived.is_empty() {
+        return None;
+    }
+
+    // everything computed before the loop must be defined on every way into it
+    let boundary = entry_state(function);
+    let ((_, out), _) = MaybeUndefined::find_with_boundary(blocks, pred, succ, boundary.clone());
+    let mut inputs: Vec<&<MaybeUndefined as DataFlowAnalysis>::State> = pred[natural_loop.header]
+        .iter()
+        .filter(|p| !natural_loop.contains(**p))
+        .map(|p| &out[*p])
+        .collect();
+    if natural_loop.header == 0 {
+        inputs.push(&boundary);
+    }
+    let entering = MaybeUndefined::merge(&inputs);
+    let defined = |var: &String| *entering.get(var) == Definedness::Defined;
+
+    let basic_of = |var: &String| {
+        ivs.basic
+            .iter()
+            .find(|basic_var| basic_var.var == *var)
+            .expect("derived variables should have a basic base")
+    };
+
+    let mut families: Vec<Family> = vec![];
+    let mut replace: HashMap<Site, Vec<Code>> = HashMap::new();
+    let mut stats = ReductionStats::default();
+    for derived_var in ivs.derived.iter() {
+        let basic_var = basic_of(&derived_var.base);
+        if !(defined(&derived_var.base)
+            && defined(&derived_var.factor)
+            && defined(&basic_var.step)
+            && derived_var.offset.as_ref().is_none_or(defined))
+        {
+            continue;
+        }
+
+        let key = (
+            &derived_var.base,
+            &derived_var.factor,
+            derived_var.offset.as_ref(),
+        );
+        let temp = match families
+            .iter()
+            .find(|family| (family.base, family.factor, family.offset) == key)
+        {
+            Some(family) => family.temp.clone(),
+            None => {
+                let temp = fresh_name(&format!("sr.{}", derived_var.var), vars);
+                families.push(Family {
+                    base: key.0,
+                    factor: key.1,
+                    offset: key.2,
+                    temp: temp.clone(),
+                });
+                temp
+            }
+        };
+
+        let Instruction::Value { dest, pos, .. } = get_instr(blocks, derived_var.site) else {
+            unreachable!("derived variables are computed by value instructions");
+        };
+        replace.insert(
+            derived_var.site,
+            vec![Code::Instruction(Instruction::Value {
+                args: vec![temp],
+                dest: dest.clone(),
+                funcs: vec![],
+                labels: vec![],
+                op: ValueOps::Id,
+                pos: pos.clone(),
+                op_type: Type::Int,
+            })],
+        );
+        stats.reduced += 1;
+    }
+    if families.is_empty() {
+        return None;
+    }
+
+    let mut preheader = vec![];
+    let mut after: HashMap<Site, Vec<Code>> = HashMap::new();
+    let mut steps: HashMap<(&String, &String), String> = HashMap::new();
+    for family in families.iter() {
+        let basic_var = basic_of(family.base);
+        let step = steps
+            .entry((family.base, family.factor))
+            .or_insert_with(|| {
+                let step = fresh_name(&format!("sr.{}.step", family.base), vars);
+                preheader.push(compute(
+                    &step,
+                    ValueOps::Mul,
+                    &[&basic_var.step, family.factor],
+                ));
+                step
+            })
+            .clone();
+
+        preheader.push(compute(
+            &family.temp,
+            ValueOps::Mul,
+            &[family.base, family.factor],
+        ));
+        if let Some(offset) = family.offset {
+            preheader.push(compute(
+                &family.temp,
+                ValueOps::Add,
+                &[&family.temp, offset],
+            ));
+        }
+
+        let op = if basic_var.negated {
+            ValueOps::Sub
+        } else {
+            ValueOps::Add
+        };
+        after.entry(basic_var.site).or_default().push(compute(
+            &family.temp,
+            op,
+            &[&family.temp, &step],
+        ));
+    }
+
+    // a basic variable only kept for its exit test can be replaced in the test by a product
+    let reduced: HashSet<Site> = replace.keys().copied().collect();
+    let (in_, out) = IntervalAnalysis::find(blocks, pred, succ);
+    let points = IntervalAnalysis::program_points(blocks, &in_, &out);
+    for basic_var in ivs.basic.iter() {
+        let Some((site, bound)) = find_exit_test(blocks, natural_loop, basic_var, &reduced) else {
+            continue;
+        };
+        let ranges = &points[site.0][site.1];
+        let Some(family) = families.iter().find(|family| {
+            family.base == &basic_var.var
+                && family.offset.is_none()
+                && scales_safely(*ranges.get(&basic_var.var), *ranges.get(family.factor))
+                && scales_safely(*ranges.get(bound), *ranges.get(family.factor))
+        }) else {
+            continue;
+        };
+        if !defined(bound) || get_loop_defs(blocks, natural_loop).contains_key(bound) {
+            continue;
+        }
+
+        let scaled = fresh_name(&format!("sr.{bound}"), vars);
+        preheader.push(compute(&scaled, ValueOps::Mul, &[bound, family.factor]));
+
+        let Code::Instruction(Instruction::Value {
+            op,
+            args,
+            dest,
+            pos,
+            op_type,
+            ..
+        }) = &blocks[site.0][site.1]
+        else {
+            unreachable!("exit tests are value instructions");
+        };
+        let args = args
+            .iter()
+            .map(|arg| {
+                if *arg == basic_var.var {
+                    family.temp.clone()
+                } else {
+                    scaled.clone()
+                }
+            })
+            .collect();
+        replace.insert(
+            site,
+            vec![Code::Instruction(Instruction::Value {
+                args,
+                dest: dest.clone(),
+                funcs: vec![],
+                labels: vec![],
+                op: *op,
+                pos: pos.clone(),
+                op_type: op_type.clone(),
+            })],
+        );
+        // nothing reads the basic variable anymore
+        replace.insert(basic_var.site, vec![]);
+        stats.tests_replaced += 1;
+    }
+
+    let mut new_blocks: Vec<Vec<Code>> = blocks
+        .iter()
+        .enumerate()
+        .map(|(b, block)| {
+            let mut codes = vec![];
+            for (i, code) in block.iter().enumerate() {
+                match replace.remove(&(b, i)) {
+                    Some(replacement) => codes.extend(replacement),
+                    None => codes.push(code.clone()),
+                }
+                codes.extend(after.remove(&(b, i)).unwrap_or_default());
+            }
+            codes
+        })
+        .collect();
+    let p = insert_preheader(&mut new_blocks, pred, natural_loop);
+    new_blocks[p].extend(preheader);
+
+    Some((new_blocks, stats))
+}
+
+// strength reduction of multiplications by induction variables, loop by loop
+pub fn reduce_strength(function: &mut Function) -> ReductionStats {
+    let mut stats = ReductionStats::default();
+    let mut vars = get_vars(function);
+    // loops are found again after every change, and known by their header's label
+    let mut visited: HashSet<String> = HashSet::new();
+
+    loop {
+        let blocks = get_basic_blocks(function);
+        let succ = form_cfg(&blocks);
+        let pred = rev_graph(&succ);
+        let loops = find_natural_loops(&pred, &succ);
+
+        let Some(natural_loop) = loops
+            .into_iter()
+            .find(|natural_loop| !visited.contains(&get_label(&blocks, natural_loop.header)))
+        else {
+            break;
+        };
+        visited.insert(get_label(&blocks, natural_loop.header));
+
+        if let Some((new_blocks, loop_stats)) =
+            reduce_loop(function, &blocks, &pred, &succ, &natural_loop, &mut vars)
+        {
+            function.instrs = new_blocks.into_iter().flatten().collect();
+            stats.reduced += loop_stats.reduced;
+            stats.tests_replaced += loop_stats.tests_replaced;
+        }
+    }
+
+    stats
+}
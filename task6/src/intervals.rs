@@ -0,0 +1,432 @@
+use std::fmt::Display;
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Literal, Type, ValueOps};
+
+use crate::{
+    cfg::{form_cfg, get_basic_blocks},
+    df::DataFlowAnalysis,
+    dom::rev_graph,
+    lattice::{Lattice, MapLattice},
+};
+
+// a set of ints [lo, hi], Bril ints wrap so the full i64 range is also "any int"
+// bounds at the ends of the i64 range are shown as infinite
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Interval {
+    Empty,
+    Range(i64, i64),
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interval::Empty => write!(f, "empty"),
+            Interval::Range(lo, hi) => {
+                if *lo == i64::MIN {
+                    write!(f, "[-inf, ")?;
+                } else {
+                    write!(f, "[{lo}, ")?;
+                }
+                if *hi == i64::MAX {
+                    write!(f, "+inf]")
+                } else {
+                    write!(f, "{hi}]")
+                }
+            }
+        }
+    }
+}
+
+impl Interval {
+    pub fn constant(value: i64) -> Self {
+        Interval::Range(value, value)
+    }
+
+    // result of arithmetic on bounds, anything that left the i64 range may have wrapped around
+    // to any value
+    fn from_wide(lo: i128, hi: i128) -> Self {
+        if lo < i64::MIN as i128 || hi > i64::MAX as i128 {
+            Self::top()
+        } else {
+            Self::range(lo, hi)
+        }
+    }
+
+    // the ints between lo and hi
+    fn range(lo: i128, hi: i128) -> Self {
+        let lo = lo.max(i64::MIN as i128);
+        let hi = hi.min(i64::MAX as i128);
+        if lo > hi {
+            Interval::Empty
+        } else {
+            Interval::Range(lo as i64, hi as i64)
+        }
+    }
+
+    fn bounds(&self) -> Option<(i128, i128)> {
+        match self {
+            Interval::Empty => None,
+            Interval::Range(lo, hi) => Some((*lo as i128, *hi as i128)),
+        }
+    }
+
+    fn corners(a: (i128, i128), b: (i128, i128), op: impl Fn(i128, i128) -> i128) -> Self {
+        let values = [op(a.0, b.0), op(a.0, b.1), op(a.1, b.0), op(a.1, b.1)];
+        Self::from_wide(*values.iter().min().unwrap(), *values.iter().max().unwrap())
+    }
+
+    pub fn eval(op: ValueOps, args: &[Interval]) -> Self {
+        let bounds: Option<Vec<(i128, i128)>> = args.iter().map(Interval::bounds).collect();
+        // an operand without values means the instruction is never reached
+        let Some(bounds) = bounds else {
+            return Interval::Empty;
+        };
+
+        match (op, bounds.as_slice()) {
+            (ValueOps::Id, [_]) => args[0],
+            (ValueOps::Add, [a, b]) => Self::from_wide(a.0 + b.0, a.1 + b.1),
+            (ValueOps::Sub, [a, b]) => Self::from_wide(a.0 - b.1, a.1 - b.0),
+            (ValueOps::Mul, [a, b]) => Self::corners(*a, *b, |x, y| x * y),
+            (ValueOps::Div, [a, b]) => {
+                // dividing by zero traps, so only the negative and positive divisors matter
+                let negative = (b.0, b.1.min(-1));
+                let positive = (b.0.max(1), b.1);
+                [negative, positive]
+                    .into_iter()
+                    .filter(|(lo, hi)| lo <= hi)
+                    .map(|divisor| Self::corners(*a, divisor, |x, y| x / y))
+                    .fold(Interval::Empty, |accum, part| accum.join(&part))
+            }
+            _ => Self::top(),
+        }
+    }
+
+    // values kept in a loop, bounds that still move are pushed out to infinity
+    pub fn widen(&self, new: &Self) -> Self {
+        match (self, new) {
+            (Interval::Empty, _) => *new,
+            (_, Interval::Empty) => *self,
+            (Interval::Range(lo, hi), Interval::Range(new_lo, new_hi)) => Interval::Range(
+                if new_lo < lo { i64::MIN } else { *lo },
+                if new_hi > hi { i64::MAX } else { *hi },
+            ),
+        }
+    }
+
+    // only infinite bounds left by widening are brought back in
+    pub fn narrow(&self, new: &Self) -> Self {
+        match (self, new) {
+            (Interval::Range(lo, hi), Interval::Range(new_lo, new_hi)) => Interval::Range(
+                if *lo == i64::MIN { *new_lo } else { *lo },
+                if *hi == i64::MAX { *new_hi } else { *hi },
+            ),
+            _ => *new,
+        }
+    }
+}
+
+impl Lattice for Interval {
+    fn top() -> Self {
+        Interval::Range(i64::MIN, i64::MAX)
+    }
+
+    fn bottom() -> Self {
+        Interval::Empty
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        match (self.bounds(), other.bounds()) {
+            (Some(a), Some(b)) => Self::range(a.0.max(b.0), a.1.min(b.1)),
+            _ => Interval::Empty,
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Interval::Empty, x) | (x, Interval::Empty) => *x,
+            (Interval::Range(lo, hi), Interval::Range(other_lo, other_hi)) => {
+                Interval::Range(*lo.min(other_lo), *hi.max(other_hi))
+            }
+        }
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Interval::Empty, _) => true,
+            (_, Interval::Empty) => false,
+            (Interval::Range(lo, hi), Interval::Range(other_lo, other_hi)) => {
+                other_lo <= lo && hi <= other_hi
+            }
+        }
+    }
+}
+
+type Ranges = MapLattice<String, Interval>;
+
+// the comparison computing the condition of the `br` ending the block, if its operands still hold
+// their values at the branch
+fn branch_condition(block: &[Code]) -> Option<(ValueOps, &String, &String)> {
+    let Some(Code::Instruction(Instruction::Effect {
+        op: EffectOps::Branch,
+        args,
+        ..
+    })) = block.last()
+    else {
+        return None;
+    };
+    let cond = &args[0];
+
+    let mut redefined = vec![];
+    for code in block.iter().rev().skip(1) {
+        match code {
+            Code::Instruction(Instruction::Value { dest, op, args, .. }) if dest == cond => {
+                return match (op, args.as_slice()) {
+                    (
+                        ValueOps::Lt | ValueOps::Le | ValueOps::Eq | ValueOps::Gt | ValueOps::Ge,
+                        [a, b],
+                    ) if !redefined.contains(&a) && !redefined.contains(&b) => Some((*op, a, b)),
+                    _ => None,
+                };
+            }
+            Code::Instruction(Instruction::Constant { dest, .. }) if dest == cond => return None,
+            Code::Instruction(
+                Instruction::Constant { dest, .. } | Instruction::Value { dest, .. },
+            ) => redefined.push(dest),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// whether `a op b` holds for all, or for none of the values
+fn compare(op: ValueOps, a: Interval, b: Interval) -> Option<bool> {
+    let (Interval::Range(a_lo, a_hi), Interval::Range(b_lo, b_hi)) = (a, b) else {
+        return None;
+    };
+    match op {
+        ValueOps::Lt if a_hi < b_lo => Some(true),
+        ValueOps::Lt if a_lo >= b_hi => Some(false),
+        ValueOps::Le if a_hi <= b_lo => Some(true),
+        ValueOps::Le if a_lo > b_hi => Some(false),
+        ValueOps::Eq if a_lo == a_hi && b_lo == b_hi && a_lo == b_lo => Some(true),
+        ValueOps::Eq if a_hi < b_lo || b_hi < a_lo => Some(false),
+        ValueOps::Gt => compare(ValueOps::Lt, b, a),
+        ValueOps::Ge => compare(ValueOps::Le, b, a),
+        _ => None,
+    }
+}
+
+// the values of a and b for which `a op b` is `taken`
+fn refine(op: ValueOps, taken: bool, a: Interval, b: Interval) -> (Interval, Interval) {
+    use ValueOps::*;
+    let (Some((a_lo, a_hi)), Some((b_lo, b_hi))) = (a.bounds(), b.bounds()) else {
+        return (Interval::Empty, Interval::Empty);
+    };
+
+    match (op, taken) {
+        (Lt, true) | (Ge, false) => (
+            a.meet(&Interval::range(i64::MIN as i128, b_hi - 1)),
+            b.meet(&Interval::range(a_lo + 1, i64::MAX as i128)),
+        ),
+        (Le, true) | (Gt, false) => (
+            a.meet(&Interval::range(i64::MIN as i128, b_hi)),
+            b.meet(&Interval::range(a_lo, i64::MAX as i128)),
+        ),
+        (Gt, true) | (Le, false) => {
+            let (b, a) = refine(Lt, true, b, a);
+            (a, b)
+        }
+        (Ge, true) | (Lt, false) => {
+            let (b, a) = refine(Le, true, b, a);
+            (a, b)
+        }
+        (Eq, true) => (a.meet(&b), a.meet(&b)),
+        (Eq, false) => {
+            // a known value can only be cut off the end of the other range
+            let exclude = |x: Interval, lo: i128, hi: i128, value: i128| {
+                if lo == value {
+                    Interval::range(lo + 1, hi)
+                } else if hi == value {
+                    Interval::range(lo, hi - 1)
+                } else {
+                    x
+                }
+            };
+            let a = if b_lo == b_hi {
+                exclude(a, a_lo, a_hi, b_lo)
+            } else {
+                a
+            };
+            let b = if a_lo == a_hi {
+                exclude(b, b_lo, b_hi, a_lo)
+            } else {
+                b
+            };
+            (a, b)
+        }
+        _ => (a, b),
+    }
+}
+
+pub struct IntervalAnalysis;
+
+impl DataFlowAnalysis for IntervalAnalysis {
+    const FORWARD: bool = true;
+
+    // var -> range of its values, non-int vars are left at the full range
+    type State = Ranges;
+
+    fn transfer(block: &Vec<Code>, _block_id: usize, in_: &Self::State) -> Self::State {
+        let mut out = in_.clone();
+
+        for code in block {
+            match code {
+                Code::Instruction(Instruction::Constant {
+                    dest,
+                    value: Literal::Int(value),
+                    ..
+                }) => {
+                    out.insert(dest.clone(), Interval::constant(*value));
+                }
+                Code::Instruction(Instruction::Value {
+                    dest,
+                    op,
+                    args,
+                    op_type: Type::Int,
+                    ..
+                }) => {
+                    let values: Vec<Interval> = args.iter().map(|arg| *out.get(arg)).collect();
+                    out.insert(dest.clone(), Interval::eval(*op, &values));
+                }
+                Code::Instruction(
+                    Instruction::Constant { dest, .. } | Instruction::Value { dest, .. },
+                ) => {
+                    out.insert(dest.clone(), Interval::top());
+                }
+                _ => {}
+            }
+        }
+
+        out
+    }
+
+    // arguments can hold any value
+    fn boundary_state() -> Self::State {
+        Self::State::top()
+    }
+
+    fn widen(old: &Self::State, new: Self::State) -> Self::State {
+        old.pointwise(&new, Interval::widen)
+    }
+
+    fn narrow(old: &Self::State, new: Self::State) -> Self::State {
+        old.pointwise(&new, Interval::narrow)
+    }
+
+    const NARROWING_PASSES: usize = 2;
+
+    fn refine_edge(from: &Vec<Code>, to: &Vec<Code>, out: &Self::State) -> Option<Self::State> {
+        let (op, a, b) = branch_condition(from)?;
+        let Some(Code::Instruction(Instruction::Effect { labels, .. })) = from.last() else {
+            return None;
+        };
+        let Some(Code::Label { label, .. }) = to.first() else {
+            return None;
+        };
+        // both edges going to the same block tell nothing
+        let taken = match (&labels[0] == label, &labels[1] == label) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => return None,
+        };
+
+        let (a_values, b_values) = refine(op, taken, *out.get(a), *out.get(b));
+        if a_values == Interval::Empty || b_values == Interval::Empty {
+            // the edge is never taken
+            return Some(Self::State::bottom());
+        }
+
+        let mut refined = out.clone();
+        refined.insert(a.clone(), a_values);
+        refined.insert(b.clone(), b_values);
+        Some(refined)
+    }
+}
+
+// replaces branches on comparisons that always have the same outcome with jumps
+// returns the number of folded branches
+pub fn fold_branches(function: &mut Function) -> usize {
+    let mut blocks = get_basic_blocks(function);
+    let succ = form_cfg(&blocks);
+    let pred = rev_graph(&succ);
+
+    let (_, out) = IntervalAnalysis::find(&blocks, &pred, &succ);
+
+    let mut folded = 0;
+    for (block, out) in blocks.iter_mut().zip(out) {
+        let Some((op, a, b)) = branch_condition(block) else {
+            continue;
+        };
+        let Some(outcome) = compare(op, *out.get(a), *out.get(b)) else {
+            continue;
+        };
+
+        if let Some(Code::Instruction(Instruction::Effect { labels, pos, .. })) = block.last() {
+            let target = if outcome {
+                labels[0].clone()
+            } else {
+                labels[1].clone()
+            };
+            let pos = pos.clone();
+            *block.last_mut().unwrap() = Code::Instruction(Instruction::Effect {
+                args: vec![],
+                funcs: vec![],
+                labels: vec![target],
+                op: EffectOps::Jump,
+                pos,
+            });
+            folded += 1;
+        }
+    }
+
+    function.instrs = blocks.into_iter().flatten().collect();
+
+    folded
+}
+
+// sorted "var: range" pairs, or "unreachable"
+pub fn format_ranges(ranges: &Ranges) -> String {
+    if *ranges.rest() == Interval::Empty {
+        return "unreachable".to_string();
+    }
+    let mut ranges: Vec<String> = ranges
+        .iter()
+        .map(|(var, range)| format!("{var}: {range}"))
+        .collect();
+    ranges.sort();
+    ranges.join(", ")
+}
+
+// {"var": [lo, hi], ...} with null for infinite bounds, null altogether if unreachable
+pub fn ranges_to_json(ranges: &Ranges) -> String {
+    if *ranges.rest() == Interval::Empty {
+        return "null".to_string();
+    }
+    let bound = |value: i64| {
+        if value == i64::MIN || value == i64::MAX {
+            "null".to_string()
+        } else {
+            value.to_string()
+        }
+    };
+    let mut entries: Vec<String> = ranges
+        .iter()
+        .map(|(var, range)| match range {
+            Interval::Empty => format!("\"{var}\": []"),
+            Interval::Range(lo, hi) => format!("\"{var}\": [{}, {}]", bound(*lo), bound(*hi)),
+        })
+        .collect();
+    entries.sort();
+    format!("{{{}}}", entries.join(", "))
+}
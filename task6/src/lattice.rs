@@ -0,0 +1,214 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+pub trait Lattice: Clone + PartialEq {
+    fn top() -> Self;
+    fn bottom() -> Self;
+    // greatest lower bound
+    fn meet(&self, other: &Self) -> Self;
+    // least upper bound
+    fn join(&self, other: &Self) -> Self;
+    fn leq(&self, other: &Self) -> bool;
+}
+
+// subsets of T ordered by inclusion
+// the universe is never enumerated, so top and complements are kept as "everything except ..."
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Powerset<T: Eq + Hash> {
+    Finite(HashSet<T>),
+    Cofinite(HashSet<T>),
+}
+
+impl<T: Eq + Hash + Clone> Powerset<T> {
+    pub fn new() -> Self {
+        Powerset::Finite(HashSet::new())
+    }
+
+    pub fn contains(&self, elem: &T) -> bool {
+        match self {
+            Powerset::Finite(elems) => elems.contains(elem),
+            Powerset::Cofinite(excluded) => !excluded.contains(elem),
+        }
+    }
+
+    pub fn insert(&mut self, elem: T) {
+        match self {
+            Powerset::Finite(elems) => {
+                elems.insert(elem);
+            }
+            Powerset::Cofinite(excluded) => {
+                excluded.remove(&elem);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, elem: &T) {
+        match self {
+            Powerset::Finite(elems) => {
+                elems.remove(elem);
+            }
+            Powerset::Cofinite(excluded) => {
+                excluded.insert(elem.clone());
+            }
+        }
+    }
+
+    // only finite sets can be enumerated, which is all a may-analysis ever produces
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        match self {
+            Powerset::Finite(elems) => elems.iter(),
+            Powerset::Cofinite(_) => panic!("can't enumerate a cofinite set"),
+        }
+    }
+}
+
+impl<T: Eq + Hash + Clone> Default for Powerset<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Eq + Hash + Clone> FromIterator<T> for Powerset<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Powerset::Finite(iter.into_iter().collect())
+    }
+}
+
+impl<T: Eq + Hash + Clone> Extend<T> for Powerset<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.insert(elem);
+        }
+    }
+}
+
+impl<T: Eq + Hash + Clone> Lattice for Powerset<T> {
+    fn top() -> Self {
+        Powerset::Cofinite(HashSet::new())
+    }
+
+    fn bottom() -> Self {
+        Powerset::Finite(HashSet::new())
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        use Powerset::*;
+        match (self, other) {
+            (Finite(a), Finite(b)) => Finite(a.intersection(b).cloned().collect()),
+            (Finite(a), Cofinite(b)) | (Cofinite(b), Finite(a)) => {
+                Finite(a.difference(b).cloned().collect())
+            }
+            (Cofinite(a), Cofinite(b)) => Cofinite(a.union(b).cloned().collect()),
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        use Powerset::*;
+        match (self, other) {
+            (Finite(a), Finite(b)) => Finite(a.union(b).cloned().collect()),
+            (Finite(a), Cofinite(b)) | (Cofinite(b), Finite(a)) => {
+                Cofinite(b.difference(a).cloned().collect())
+            }
+            (Cofinite(a), Cofinite(b)) => Cofinite(a.intersection(b).cloned().collect()),
+        }
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        use Powerset::*;
+        match (self, other) {
+            (Finite(a), Finite(b)) => a.is_subset(b),
+            (Finite(a), Cofinite(b)) => a.is_disjoint(b),
+            (Cofinite(_), Finite(_)) => false,
+            (Cofinite(a), Cofinite(b)) => b.is_subset(a),
+        }
+    }
+}
+
+// pointwise lattice over K -> V, keys without an entry map to `rest`
+#[derive(Clone, PartialEq, Debug)]
+pub struct MapLattice<K: Eq + Hash, V> {
+    // never holds an entry equal to `rest`, so that equal maps compare equal
+    entries: HashMap<K, V>,
+    rest: V,
+}
+
+impl<K: Eq + Hash + Clone, V: Lattice> MapLattice<K, V> {
+    // every key maps to `rest`
+    pub fn with_rest(rest: V) -> Self {
+        MapLattice {
+            entries: HashMap::new(),
+            rest,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> &V {
+        self.entries.get(key).unwrap_or(&self.rest)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        if value == self.rest {
+            self.entries.remove(&key);
+        } else {
+            self.entries.insert(key, value);
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    pub fn rest(&self) -> &V {
+        &self.rest
+    }
+
+    // keys that don't map to `rest`
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter()
+    }
+
+    pub fn pointwise(&self, other: &Self, op: impl Fn(&V, &V) -> V) -> Self {
+        let mut result = MapLattice {
+            entries: HashMap::new(),
+            rest: op(&self.rest, &other.rest),
+        };
+        for key in self.entries.keys().chain(other.entries.keys()) {
+            result.insert(key.clone(), op(self.get(key), other.get(key)));
+        }
+        result
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Lattice> Lattice for MapLattice<K, V> {
+    fn top() -> Self {
+        MapLattice {
+            entries: HashMap::new(),
+            rest: V::top(),
+        }
+    }
+
+    fn bottom() -> Self {
+        MapLattice {
+            entries: HashMap::new(),
+            rest: V::bottom(),
+        }
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        self.pointwise(other, V::meet)
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        self.pointwise(other, V::join)
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        self.rest.leq(&other.rest)
+            && self
+                .entries
+                .keys()
+                .chain(other.entries.keys())
+                .all(|key| self.get(key).leq(other.get(key)))
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Type, ValueOps};
+
+use crate::{
+    avail::{Expr, get_expr},
+    bitvec::{BitSet, BitVectorProblem, Interner},
+    cfg::{form_cfg, get_basic_blocks, get_label},
+    cse::{fresh_name, get_vars},
+    dom::rev_graph,
+};
+
+#[derive(Default, Debug, Clone)]
+pub struct MotionStats {
+    pub inserted: usize,
+    pub deleted: usize,
+}
+
+fn is_terminator(code: &Code) -> bool {
+    matches!(
+        code,
+        Code::Instruction(Instruction::Effect {
+            op: EffectOps::Jump | EffectOps::Branch | EffectOps::Return,
+            ..
+        })
+    )
+}
+
+// puts a block holding just a jump on every edge from a block with several successors to a block
+// with several predecessors, right after the branching block
+// returns the labels of the new blocks
+fn split_critical_edges(blocks: &mut Vec<Vec<Code>>) -> HashSet<String> {
+    let succ = form_cfg(blocks);
+    let pred = rev_graph(&succ);
+
+    let mut labels: HashSet<String> = blocks
+        .iter()
+        .filter_map(|block| match block.first() {
+            Some(Code::Label { label, .. }) => Some(label.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut split = HashSet::new();
+
+    // back to front, so that inserting after b leaves the indices before it intact
+    for b in (0..blocks.len()).rev() {
+        if succ[b].len() < 2 {
+            continue;
+        }
+
+        let mut targets = succ[b].clone();
+        targets.dedup();
+        let mut edge_blocks = vec![];
+        for t in targets {
+            if pred[t].len() < 2 {
+                continue;
+            }
+            let target = get_label(blocks, t);
+            let label = fresh_name(&format!("{}.{target}", get_label(blocks, b)), &mut labels);
+
+            if let Some(Code::Instruction(Instruction::Effect {
+                labels: branch_labels,
+                ..
+            })) = blocks[b].last_mut()
+            {
+                for branch_label in branch_labels.iter_mut() {
+                    if *branch_label == target {
+                        *branch_label = label.clone();
+                    }
+                }
+            }
+
+            edge_blocks.push(vec![
+                Code::Label {
+                    label: label.clone(),
+                    pos: None,
+                },
+                Code::Instruction(Instruction::Effect {
+                    args: vec![],
+                    funcs: vec![],
+                    labels: vec![target],
+                    op: EffectOps::Jump,
+                    pos: None,
+                }),
+            ]);
+            split.insert(label);
+        }
+
+        for (i, edge_block) in edge_blocks.into_iter().enumerate() {
+            blocks.insert(b + 1 + i, edge_block);
+        }
+    }
+
+    split
+}
+
+// splits blocks into nodes of at most one instruction, so computations can be placed in front of
+// any instruction, and blocks that fall through get an empty node to place things at their end
+// returns the nodes and the node graph
+fn form_nodes(blocks: &Vec<Vec<Code>>) -> (Vec<Vec<Code>>, Vec<Vec<usize>>) {
+    let block_succ = form_cfg(blocks);
+
+    let mut nodes: Vec<Vec<Code>> = vec![];
+    let mut node_block = vec![];
+    let mut first = vec![];
+    let mut last = vec![];
+
+    for (b, block) in blocks.iter().enumerate() {
+        first.push(nodes.len());
+        let mut pending = vec![];
+        for code in block {
+            pending.push(code.clone());
+            if let Code::Instruction(_) = code {
+                nodes.push(std::mem::take(&mut pending));
+                node_block.push(b);
+            }
+        }
+        if !pending.is_empty() || !block.last().is_some_and(is_terminator) {
+            nodes.push(pending);
+            node_block.push(b);
+        }
+        last.push(nodes.len() - 1);
+    }
+
+    let succ = (0..nodes.len())
+        .map(|n| {
+            let b = node_block[n];
+            if n < last[b] {
+                vec![n + 1]
+            } else {
+                block_succ[b].iter().map(|s| first[*s]).collect()
+            }
+        })
+        .collect();
+
+    (nodes, succ)
+}
+
+fn get_instr(node: &[Code]) -> Option<&Instruction> {
+    node.iter().find_map(|code| match code {
+        Code::Instruction(instr) => Some(instr),
+        Code::Label { .. } => None,
+    })
+}
+
+fn complement(set: &BitSet, width: usize) -> BitSet {
+    let mut result = BitSet::full(width);
+    result.subtract(set);
+    result
+}
+
+fn union(a: &BitSet, b: &BitSet) -> BitSet {
+    let mut result = a.clone();
+    result.union_with(b);
+    result
+}
+
+fn intersection(a: &BitSet, b: &BitSet) -> BitSet {
+    let mut result = a.clone();
+    result.intersect_with(b);
+    result
+}
+
+fn difference(a: &BitSet, b: &BitSet) -> BitSet {
+    let mut result = a.clone();
+    result.subtract(b);
+    result
+}
+
+// partial redundancy elimination by lazy code motion: computations are moved as early as needed
+// to make them fully redundant, then postponed as late as possible to keep temporaries short
+pub fn lazy_code_motion(function: &mut Function) -> MotionStats {
+    let mut blocks = get_basic_blocks(function);
+    if blocks.is_empty() {
+        return MotionStats::default();
+    }
+    let split = split_critical_edges(&mut blocks);
+    let (nodes, succ) = form_nodes(&blocks);
+    let pred = rev_graph(&succ);
+
+    let mut exprs = Interner::new();
+    let mut types: HashMap<Expr, Type> = HashMap::new();
+    for node in nodes.iter() {
+        if let Some(instr @ Instruction::Value { op_type, .. }) = get_instr(node)
+            && let Some(expr) = get_expr(instr)
+        {
+            exprs.intern(&expr);
+            types.entry(expr).or_insert(op_type.clone());
+        }
+    }
+    let width = exprs.len();
+    let empty = BitSet::new(width);
+
+    // e_use: the expression a node computes, e_kill: expressions over the variable it defines
+    let mut e_use = vec![];
+    let mut e_kill = vec![];
+    for node in nodes.iter() {
+        let mut used = BitSet::new(width);
+        let mut killed = BitSet::new(width);
+        if let Some(instr) = get_instr(node) {
+            if let Some(expr) = get_expr(instr) {
+                used.insert(exprs.get(&expr).expect("expr should be interned"));
+            }
+            if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = instr {
+                for e in 0..width {
+                    if exprs.value(e).uses(dest) {
+                        killed.insert(e);
+                    }
+                }
+            }
+        }
+        e_use.push(used);
+        e_kill.push(killed);
+    }
+
+    // computed on every path from the node before any operand changes
+    let anticipated = BitVectorProblem {
+        forward: false,
+        may: false,
+        width,
+        gens: e_use.clone(),
+        kills: e_kill.clone(),
+        boundary: empty.clone(),
+    };
+    let ((anticipated_in, _), _) = anticipated.solve(&pred, &succ);
+
+    // available on every path to the node once computations are placed where they're anticipated
+    let available = BitVectorProblem {
+        forward: true,
+        may: false,
+        width,
+        gens: (0..nodes.len())
+            .map(|n| difference(&anticipated_in[n], &e_kill[n]))
+            .collect(),
+        kills: e_kill.clone(),
+        boundary: empty.clone(),
+    };
+    let ((available_in, _), _) = available.solve(&pred, &succ);
+
+    let earliest: Vec<BitSet> = (0..nodes.len())
+        .map(|n| difference(&anticipated_in[n], &available_in[n]))
+        .collect();
+
+    // placement can be postponed past the node without reaching a use on some path
+    let later = BitVectorProblem {
+        forward: true,
+        may: false,
+        width,
+        gens: (0..nodes.len())
+            .map(|n| difference(&earliest[n], &e_use[n]))
+            .collect(),
+        kills: e_use.clone(),
+        boundary: empty.clone(),
+    };
+    let ((later_in, _), _) = later.solve(&pred, &succ);
+
+    let placeable: Vec<BitSet> = (0..nodes.len())
+        .map(|n| union(&earliest[n], &later_in[n]))
+        .collect();
+    let latest: Vec<BitSet> = (0..nodes.len())
+        .map(|n| {
+            let mut all_succs = BitSet::full(width);
+            for s in succ[n].iter() {
+                all_succs.intersect_with(&placeable[*s]);
+            }
+            intersection(
+                &placeable[n],
+                &union(&e_use[n], &complement(&all_succs, width)),
+            )
+        })
+        .collect();
+
+    // the value placed at the latest point is still needed further down
+    let used = BitVectorProblem {
+        forward: false,
+        may: true,
+        width,
+        gens: (0..nodes.len())
+            .map(|n| difference(&e_use[n], &latest[n]))
+            .collect(),
+        kills: latest.clone(),
+        boundary: empty,
+    };
+    let ((_, used_out), _) = used.solve(&pred, &succ);
+
+    let mut vars = get_vars(function);
+    let temps: Vec<String> = (0..width)
+        .map(|e| fresh_name(&format!("lcm.{}", exprs.value(e).op), &mut vars))
+        .collect();
+
+    let mut stats = MotionStats::default();
+    let mut instrs = vec![];
+    // split blocks that got nothing placed on them are dropped again
+    let mut unsplit: HashMap<String, String> = HashMap::new();
+
+    for (n, node) in nodes.into_iter().enumerate() {
+        let insert = intersection(&latest[n], &used_out[n]);
+
+        if let Some(Code::Label { label, .. }) = node.first()
+            && split.contains(label)
+            && insert.iter().next().is_none()
+        {
+            if let Some(Code::Instruction(Instruction::Effect { labels, .. })) = node.last() {
+                unsplit.insert(label.clone(), labels[0].clone());
+            }
+            continue;
+        }
+
+        let mut codes = node.into_iter().peekable();
+        if let Some(label @ Code::Label { .. }) =
+            codes.next_if(|code| !matches!(code, Code::Instruction(_)))
+        {
+            instrs.push(label);
+        }
+
+        let computed = codes
+            .peek()
+            .and_then(|code| match code {
+                Code::Instruction(instr) => get_expr(instr),
+                Code::Label { .. } => None,
+            })
+            .map(|expr| exprs.get(&expr).expect("expr should be interned"));
+
+        for e in insert.iter() {
+            let expr = exprs.value(e);
+            instrs.push(Code::Instruction(Instruction::Value {
+                args: expr.args.clone(),
+                dest: temps[e].clone(),
+                funcs: vec![],
+                labels: vec![],
+                op: expr.op,
+                pos: None,
+                op_type: types[expr].clone(),
+            }));
+            // computing into the temporary right where the original was is no real insertion
+            if computed != Some(e) {
+                stats.inserted += 1;
+            }
+        }
+
+        for code in codes {
+            match (&code, computed) {
+                (
+                    Code::Instruction(Instruction::Value {
+                        dest, op_type, pos, ..
+                    }),
+                    Some(e),
+                ) if !latest[n].contains(e) || used_out[n].contains(e) => {
+                    if !insert.contains(e) {
+                        stats.deleted += 1;
+                    }
+                    instrs.push(Code::Instruction(Instruction::Value {
+                        args: vec![temps[e].clone()],
+                        dest: dest.clone(),
+                        funcs: vec![],
+                        labels: vec![],
+                        op: ValueOps::Id,
+                        pos: pos.clone(),
+                        op_type: op_type.clone(),
+                    }));
+                }
+                _ => instrs.push(code),
+            }
+        }
+    }
+
+    for code in instrs.iter_mut() {
+        if let Code::Instruction(Instruction::Effect {
+            op: EffectOps::Branch,
+            labels,
+            ..
+        }) = code
+        {
+            for label in labels.iter_mut() {
+                if let Some(target) = unsplit.get(label) {
+                    *label = target.clone();
+                }
+            }
+        }
+    }
+
+    function.instrs = instrs;
+
+    stats
+}
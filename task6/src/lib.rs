@@ -1,4 +1,21 @@
+pub mod avail;
+pub mod bitvec;
+pub mod c_backend;
 pub mod cfg;
+pub mod chains;
+pub mod const_prop;
+pub mod cse;
 pub mod df;
 pub mod dom;
+pub mod induction;
+pub mod intervals;
+pub mod lattice;
+pub mod lcm;
+pub mod loops;
+pub mod mem_opt;
+pub mod memcheck;
+pub mod points_to;
 pub mod ssa;
+pub mod typecheck;
+pub mod undef;
+pub mod unroll;
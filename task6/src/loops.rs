@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+
+use bril_rs::{Code, EffectOps, Instruction};
+
+use crate::{
+    cfg::get_label,
+    cse::fresh_name,
+    dom::{find_dominators, postorder},
+};
+
+pub struct NaturalLoop {
+    pub header: usize,
+    // sources of the back edges
+    pub latches: Vec<usize>,
+    // header included, sorted
+    pub body: Vec<usize>,
+}
+
+impl NaturalLoop {
+    pub fn contains(&self, block: usize) -> bool {
+        self.body.binary_search(&block).is_ok()
+    }
+}
+
+// one loop per header, with the loops of all back edges to it merged, innermost first
+pub fn find_natural_loops(pred: &Vec<Vec<usize>>, succ: &Vec<Vec<usize>>) -> Vec<NaturalLoop> {
+    if succ.is_empty() {
+        return vec![];
+    }
+    let dom = find_dominators(pred, succ);
+    let mut reachable = vec![false; succ.len()];
+    postorder(0, succ, &mut reachable, &mut vec![]);
+
+    let mut loops: Vec<NaturalLoop> = vec![];
+    for header in 0..succ.len() {
+        // an edge to a block that dominates its source goes back around a loop
+        let latches: Vec<usize> = pred[header]
+            .iter()
+            .copied()
+            .filter(|&p| reachable[p] && dom[p].contains(&header))
+            .collect();
+        if latches.is_empty() {
+            continue;
+        }
+
+        let mut body: HashSet<usize> = [header].into();
+        let mut stack = latches.clone();
+        while let Some(b) = stack.pop() {
+            if body.insert(b) {
+                stack.extend(pred[b].iter().copied());
+            }
+        }
+        let mut body: Vec<usize> = body.into_iter().collect();
+        body.sort();
+        let mut latches = latches;
+        latches.sort();
+
+        loops.push(NaturalLoop {
+            header,
+            latches,
+            body,
+        });
+    }
+
+    loops.sort_by_key(|l| (l.body.len(), l.header));
+    loops
+}
+
+pub(crate) fn get_labels(blocks: &[Vec<Code>]) -> HashSet<String> {
+    blocks
+        .iter()
+        .filter_map(|block| match block.first() {
+            Some(Code::Label { label, .. }) => Some(label.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn falls_through(block: &[Code]) -> bool {
+    !matches!(
+        block.last(),
+        Some(Code::Instruction(Instruction::Effect {
+            op: EffectOps::Jump | EffectOps::Branch | EffectOps::Return,
+            ..
+        }))
+    )
+}
+
+// gives the loop an empty block that every entry into it goes through, placed right before the
+// header, and returns its index
+// the header, and every block after it, moves down by one
+pub fn insert_preheader(
+    blocks: &mut Vec<Vec<Code>>,
+    pred: &[Vec<usize>],
+    natural_loop: &NaturalLoop,
+) -> usize {
+    let header = natural_loop.header;
+    let mut labels = get_labels(blocks);
+
+    if !matches!(blocks[header].first(), Some(Code::Label { .. })) {
+        let label = fresh_name(&get_label(blocks, header), &mut labels);
+        blocks[header].insert(0, Code::Label { label, pos: None });
+    }
+    let header_label = get_label(blocks, header);
+    let preheader_label = fresh_name(&format!("{header_label}.preheader"), &mut labels);
+
+    for &p in pred[header].iter() {
+        if natural_loop.contains(p) {
+            // a latch falling through into the header would now fall into the preheader
+            if p + 1 == header && falls_through(&blocks[p]) {
+                blocks[p].push(Code::Instruction(Instruction::Effect {
+                    args: vec![],
+                    funcs: vec![],
+                    labels: vec![header_label.clone()],
+                    op: EffectOps::Jump,
+                    pos: None,
+                }));
+            }
+            continue;
+        }
+        if let Some(Code::Instruction(Instruction::Effect {
+            op: EffectOps::Jump | EffectOps::Branch,
+            labels,
+            ..
+        })) = blocks[p].last_mut()
+        {
+            for label in labels.iter_mut() {
+                if *label == header_label {
+                    *label = preheader_label.clone();
+                }
+            }
+        }
+    }
+
+    blocks.insert(
+        header,
+        vec![Code::Label {
+            label: preheader_label,
+            pos: None,
+        }],
+    );
+    header
+}
@@ -0,0 +1,514 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Literal, Type, ValueOps};
+
+use crate::{
+    bitvec::{BitSet, BitVectorProblem, Interner},
+    cfg::{form_cfg, get_basic_blocks},
+    dom::rev_graph,
+};
+
+#[derive(Default, Debug, Clone)]
+pub struct MemoryStats {
+    pub forwarded: usize,
+    pub removed: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Offset {
+    Const(i64),
+    // an offset only known to be the one the variable was computed with
+    Unknown(String),
+}
+
+// a pointer as a base variable plus an offset in elements
+// the base is either defined only once or the pointer itself, so the address only changes when
+// a variable it mentions is redefined
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct Addr {
+    root: String,
+    offset: Offset,
+}
+
+impl Addr {
+    fn mentions(&self, var: &str) -> bool {
+        self.root == var || self.offset == Offset::Unknown(var.to_string())
+    }
+}
+
+struct AliasModel {
+    addrs: HashMap<String, Addr>,
+    // bases that are fresh allocations, which never overlap each other
+    allocs: HashSet<String>,
+}
+
+impl AliasModel {
+    fn new(function: &Function) -> Self {
+        let mut defs: HashMap<&String, Vec<&Instruction>> = HashMap::new();
+        let mut args: HashSet<&String> = HashSet::new();
+        for arg in function.args.iter() {
+            args.insert(&arg.name);
+            defs.entry(&arg.name).or_default();
+        }
+        for code in function.instrs.iter() {
+            if let Code::Instruction(
+                instr @ (Instruction::Constant { dest, .. } | Instruction::Value { dest, .. }),
+            ) = code
+            {
+                defs.entry(dest).or_default().push(instr);
+            }
+        }
+
+        // the single definition of a variable that is never reassigned
+        let single: HashMap<&String, &Instruction> = defs
+            .iter()
+            .filter(|(var, instrs)| instrs.len() == 1 && !args.contains(*var))
+            .map(|(var, instrs)| (*var, instrs[0]))
+            .collect();
+
+        let mut model = AliasModel {
+            addrs: HashMap::new(),
+            allocs: HashSet::new(),
+        };
+        for (var, instr) in single.iter() {
+            if let Instruction::Value {
+                op: ValueOps::Alloc,
+                ..
+            } = instr
+            {
+                model.allocs.insert((*var).clone());
+            }
+        }
+        for var in single.keys() {
+            let addr = resolve(var, &single, single.len());
+            model.addrs.insert((*var).clone(), addr);
+        }
+        model
+    }
+
+    fn addr(&self, ptr: &str) -> Addr {
+        self.addrs.get(ptr).cloned().unwrap_or(Addr {
+            root: ptr.to_string(),
+            offset: Offset::Const(0),
+        })
+    }
+
+    fn may_alias(&self, a: &Addr, b: &Addr) -> bool {
+        if a.root == b.root {
+            match (&a.offset, &b.offset) {
+                (Offset::Const(x), Offset::Const(y)) => x == y,
+                _ => true,
+            }
+        } else {
+            !(self.allocs.contains(&a.root) && self.allocs.contains(&b.root))
+        }
+    }
+}
+
+// follows copies and pointer arithmetic through variables that are never reassigned
+// depth bounds the walk, since definitions in unreachable code may form cycles
+fn resolve(var: &String, single: &HashMap<&String, &Instruction>, depth: usize) -> Addr {
+    let itself = Addr {
+        root: var.clone(),
+        offset: Offset::Const(0),
+    };
+    if depth == 0 {
+        return itself;
+    }
+
+    match single.get(var) {
+        Some(Instruction::Value {
+            op: ValueOps::Id,
+            args,
+            ..
+        }) if single.contains_key(&args[0]) => resolve(&args[0], single, depth - 1),
+        Some(Instruction::Value {
+            op: ValueOps::PtrAdd,
+            args,
+            ..
+        }) if single.contains_key(&args[0]) => {
+            let base = resolve(&args[0], single, depth - 1);
+            let step = match single.get(&args[1]) {
+                Some(Instruction::Constant {
+                    value: Literal::Int(step),
+                    ..
+                }) => Some(*step),
+                _ => None,
+            };
+            let offset = match (base.offset, step) {
+                (Offset::Const(offset), Some(step)) => offset.checked_add(step),
+                _ => None,
+            };
+            Addr {
+                root: base.root,
+                offset: offset.map_or(Offset::Unknown(var.clone()), Offset::Const),
+            }
+        }
+        _ => itself,
+    }
+}
+
+// what an instruction does to memory
+enum Access<'a> {
+    Load(&'a String),
+    Store(&'a String, &'a String),
+    Free(&'a String),
+    // may read and write anything, like a call that is passed a pointer
+    Clobber,
+    None,
+}
+
+fn get_access<'a>(instr: &'a Instruction, types: &HashMap<&String, &Type>) -> Access<'a> {
+    match instr {
+        Instruction::Value {
+            op: ValueOps::Load,
+            args,
+            ..
+        } => Access::Load(&args[0]),
+        Instruction::Effect {
+            op: EffectOps::Store,
+            args,
+            ..
+        } => Access::Store(&args[0], &args[1]),
+        Instruction::Effect {
+            op: EffectOps::Free,
+            args,
+            ..
+        } => Access::Free(&args[0]),
+        Instruction::Value {
+            op: ValueOps::Call,
+            args,
+            ..
+        }
+        | Instruction::Effect {
+            op: EffectOps::Call,
+            args,
+            ..
+        } => {
+            // without globals, a callee can only reach memory through the pointers it gets
+            if args.iter().any(|arg| {
+                types
+                    .get(arg)
+                    .is_none_or(|tipe| matches!(tipe, Type::Pointer(_)))
+            }) {
+                Access::Clobber
+            } else {
+                Access::None
+            }
+        }
+        _ => Access::None,
+    }
+}
+
+fn get_types(function: &Function) -> HashMap<&String, &Type> {
+    let mut types: HashMap<&String, &Type> = function
+        .args
+        .iter()
+        .map(|arg| (&arg.name, &arg.arg_type))
+        .collect();
+    for code in function.instrs.iter() {
+        if let Code::Instruction(
+            Instruction::Constant {
+                dest, const_type, ..
+            }
+            | Instruction::Value {
+                dest,
+                op_type: const_type,
+                ..
+            },
+        ) = code
+        {
+            types.insert(dest, const_type);
+        }
+    }
+    types
+}
+
+fn get_dest(instr: &Instruction) -> Option<&String> {
+    match instr {
+        Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => Some(dest),
+        Instruction::Effect { .. } => None,
+    }
+}
+
+fn matching<T: Eq + Hash + Clone>(facts: &Interner<T>, mut pred: impl FnMut(&T) -> bool) -> BitSet {
+    let mut set = BitSet::new(facts.len());
+    for i in 0..facts.len() {
+        if pred(facts.value(i)) {
+            set.insert(i);
+        }
+    }
+    set
+}
+
+fn apply(state: &mut BitSet, (gen_, kill): &(BitSet, BitSet)) {
+    state.subtract(kill);
+    state.union_with(gen_);
+}
+
+// gen and kill of a sequence of steps, in the order they run
+fn summarize<'a>(
+    steps: impl Iterator<Item = &'a (BitSet, BitSet)>,
+    width: usize,
+) -> (BitSet, BitSet) {
+    let mut block_gen = BitSet::new(width);
+    let mut block_kill = BitSet::new(width);
+    for (gen_, kill) in steps {
+        block_gen.subtract(kill);
+        block_gen.union_with(gen_);
+        block_kill.union_with(kill);
+        block_kill.subtract(gen_);
+    }
+    (block_gen, block_kill)
+}
+
+// replaces loads of an address that certainly holds the value of some variable on every path,
+// because it was just stored or loaded, with copies of that variable
+fn forward_loads(function: &mut Function) -> usize {
+    let blocks = get_basic_blocks(function);
+    let succ = form_cfg(&blocks);
+    let pred = rev_graph(&succ);
+    let model = AliasModel::new(function);
+    let types = get_types(function);
+
+    // (address, variable) pairs where the variable holds the value at the address
+    let mut facts: Interner<(Addr, String)> = Interner::new();
+    for code in function.instrs.iter() {
+        let Code::Instruction(instr) = code else {
+            continue;
+        };
+        match (get_access(instr, &types), get_dest(instr)) {
+            (Access::Load(ptr), Some(dest)) => {
+                facts.intern(&(model.addr(ptr), dest.clone()));
+            }
+            (Access::Store(ptr, value), _) => {
+                facts.intern(&(model.addr(ptr), value.clone()));
+            }
+            _ => {}
+        }
+    }
+    let width = facts.len();
+
+    let steps: Vec<Vec<(BitSet, BitSet)>> = blocks
+        .iter()
+        .map(|block| {
+            block
+                .iter()
+                .map(|code| {
+                    let Code::Instruction(instr) = code else {
+                        return (BitSet::new(width), BitSet::new(width));
+                    };
+                    let access = get_access(instr, &types);
+                    let dest = get_dest(instr);
+
+                    let mut kill = match &access {
+                        Access::Store(ptr, _) | Access::Free(ptr) => {
+                            let addr = model.addr(ptr);
+                            matching(&facts, |(other, _)| model.may_alias(&addr, other))
+                        }
+                        Access::Clobber => BitSet::full(width),
+                        Access::Load(_) | Access::None => BitSet::new(width),
+                    };
+                    if let Some(dest) = dest {
+                        kill.union_with(&matching(&facts, |(addr, value)| {
+                            value == dest || addr.mentions(dest)
+                        }));
+                    }
+
+                    let mut gen_ = BitSet::new(width);
+                    let fact = match (&access, dest) {
+                        (Access::Load(ptr), Some(dest)) => Some((model.addr(ptr), dest.clone())),
+                        (Access::Store(ptr, value), _) => Some((model.addr(ptr), (*value).clone())),
+                        _ => None,
+                    };
+                    if let Some(fact @ (addr, _)) = &fact
+                        && !dest.is_some_and(|dest| addr.mentions(dest))
+                    {
+                        gen_.insert(facts.get(fact).expect("fact should be interned"));
+                    }
+                    (gen_, kill)
+                })
+                .collect()
+        })
+        .collect();
+
+    let (gens, kills) = steps
+        .iter()
+        .map(|steps| summarize(steps.iter(), width))
+        .unzip();
+    let problem = BitVectorProblem {
+        forward: true,
+        may: false,
+        width,
+        gens,
+        kills,
+        boundary: BitSet::new(width),
+    };
+    let ((in_, _), _) = problem.solve(&pred, &succ);
+
+    let mut forwarded = 0;
+    let mut instrs = vec![];
+    for ((block, steps), mut state) in blocks.into_iter().zip(steps.iter()).zip(in_) {
+        for (code, step) in block.into_iter().zip(steps) {
+            let replacement = match &code {
+                Code::Instruction(Instruction::Value {
+                    op: ValueOps::Load,
+                    args,
+                    dest,
+                    op_type,
+                    pos,
+                    ..
+                }) => {
+                    let addr = model.addr(&args[0]);
+                    state
+                        .iter()
+                        .map(|fact| facts.value(fact))
+                        .find(|(other, _)| *other == addr)
+                        .map(|(_, value)| {
+                            Code::Instruction(Instruction::Value {
+                                args: vec![value.clone()],
+                                dest: dest.clone(),
+                                funcs: vec![],
+                                labels: vec![],
+                                op: ValueOps::Id,
+                                pos: pos.clone(),
+                                op_type: op_type.clone(),
+                            })
+                        })
+                }
+                _ => None,
+            };
+
+            match replacement {
+                Some(copy) => {
+                    forwarded += 1;
+                    instrs.push(copy);
+                }
+                None => instrs.push(code),
+            }
+            apply(&mut state, step);
+        }
+    }
+
+    function.instrs = instrs;
+
+    forwarded
+}
+
+// removes stores whose address is certainly written again before anything may read it
+fn eliminate_dead_stores(function: &mut Function) -> usize {
+    let blocks = get_basic_blocks(function);
+    let succ = form_cfg(&blocks);
+    let pred = rev_graph(&succ);
+    let model = AliasModel::new(function);
+    let types = get_types(function);
+
+    // addresses that are overwritten before they may be read
+    let mut addrs: Interner<Addr> = Interner::new();
+    for code in function.instrs.iter() {
+        if let Code::Instruction(instr) = code
+            && let Access::Store(ptr, _) = get_access(instr, &types)
+        {
+            addrs.intern(&model.addr(ptr));
+        }
+    }
+    let width = addrs.len();
+
+    // in reverse order within each block
+    let steps: Vec<Vec<(BitSet, BitSet)>> = blocks
+        .iter()
+        .map(|block| {
+            block
+                .iter()
+                .rev()
+                .map(|code| {
+                    let Code::Instruction(instr) = code else {
+                        return (BitSet::new(width), BitSet::new(width));
+                    };
+                    let access = get_access(instr, &types);
+
+                    // freeing counts as a read, which keeps stores before it
+                    let mut kill = match &access {
+                        Access::Load(ptr) | Access::Free(ptr) => {
+                            let addr = model.addr(ptr);
+                            matching(&addrs, |other| model.may_alias(&addr, other))
+                        }
+                        Access::Clobber => BitSet::full(width),
+                        Access::Store(..) | Access::None => BitSet::new(width),
+                    };
+                    if let Some(dest) = get_dest(instr) {
+                        kill.union_with(&matching(&addrs, |addr| addr.mentions(dest)));
+                    }
+
+                    let mut gen_ = BitSet::new(width);
+                    if let Access::Store(ptr, _) = access {
+                        gen_.insert(
+                            addrs
+                                .get(&model.addr(ptr))
+                                .expect("addr should be interned"),
+                        );
+                    }
+                    (gen_, kill)
+                })
+                .collect()
+        })
+        .collect();
+
+    let (gens, kills) = steps
+        .iter()
+        .map(|steps| summarize(steps.iter(), width))
+        .unzip();
+    let problem = BitVectorProblem {
+        forward: false,
+        may: false,
+        width,
+        gens,
+        kills,
+        boundary: BitSet::new(width),
+    };
+    let ((_, out), _) = problem.solve(&pred, &succ);
+
+    let mut removed = 0;
+    let mut instrs = vec![];
+    for ((block, steps), mut state) in blocks.into_iter().zip(steps.iter()).zip(out) {
+        let mut kept = vec![];
+        for (code, step) in block.into_iter().rev().zip(steps) {
+            let dead = match &code {
+                Code::Instruction(instr @ Instruction::Effect { .. }) => {
+                    match get_access(instr, &types) {
+                        Access::Store(ptr, _) => state.contains(
+                            addrs
+                                .get(&model.addr(ptr))
+                                .expect("addr should be interned"),
+                        ),
+                        _ => false,
+                    }
+                }
+                _ => false,
+            };
+
+            if dead {
+                removed += 1;
+            } else {
+                kept.push(code);
+            }
+            apply(&mut state, step);
+        }
+        instrs.extend(kept.into_iter().rev());
+    }
+
+    function.instrs = instrs;
+
+    removed
+}
+
+// forwards stored and loaded values to later loads, then removes stores that are overwritten
+// before any possible read
+pub fn optimize_memory(function: &mut Function) -> MemoryStats {
+    let forwarded = forward_loads(function);
+    let removed = eliminate_dead_stores(function);
+    MemoryStats { forwarded, removed }
+}
@@ -0,0 +1,355 @@
+use std::collections::HashSet;
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Position, ValueOps};
+
+use crate::{
+    cfg::{form_cfg, get_basic_blocks},
+    chains::{Site, get_pos},
+    df::DataFlowAnalysis,
+    dom::rev_graph,
+    lattice::{Lattice, MapLattice, Powerset},
+};
+
+// what the latest object from an allocation site may be on the paths reaching a point,
+// ordered by inclusion, so nothing at all means the point is unreached
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Status {
+    pub unallocated: bool,
+    pub live: bool,
+    pub freed: bool,
+}
+
+impl Status {
+    const FREED: Status = Status {
+        unallocated: false,
+        live: false,
+        freed: true,
+    };
+    const LIVE: Status = Status {
+        unallocated: false,
+        live: true,
+        freed: false,
+    };
+}
+
+impl Lattice for Status {
+    fn top() -> Self {
+        Status {
+            unallocated: true,
+            live: true,
+            freed: true,
+        }
+    }
+
+    fn bottom() -> Self {
+        Status::default()
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        Status {
+            unallocated: self.unallocated && other.unallocated,
+            live: self.live && other.live,
+            freed: self.freed && other.freed,
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        Status {
+            unallocated: self.unallocated || other.unallocated,
+            live: self.live || other.live,
+            freed: self.freed || other.freed,
+        }
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        self.join(other) == *other
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MemoryState {
+    // var -> allocation sites it may point into, empty for pointers from elsewhere
+    pub pointers: MapLattice<String, Powerset<Site>>,
+    pub sites: MapLattice<Site, Status>,
+}
+
+impl Lattice for MemoryState {
+    fn top() -> Self {
+        MemoryState {
+            pointers: MapLattice::top(),
+            sites: MapLattice::top(),
+        }
+    }
+
+    fn bottom() -> Self {
+        MemoryState {
+            pointers: MapLattice::bottom(),
+            sites: MapLattice::bottom(),
+        }
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        MemoryState {
+            pointers: self.pointers.meet(&other.pointers),
+            sites: self.sites.meet(&other.sites),
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        MemoryState {
+            pointers: self.pointers.join(&other.pointers),
+            sites: self.sites.join(&other.sites),
+        }
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        self.pointers.leq(&other.pointers) && self.sites.leq(&other.sites)
+    }
+}
+
+// intraprocedural, so frees done by callees are not seen
+pub struct MemorySafety;
+
+impl DataFlowAnalysis for MemorySafety {
+    const FORWARD: bool = true;
+
+    type State = MemoryState;
+
+    fn transfer(block: &Vec<Code>, block_id: usize, in_: &Self::State) -> Self::State {
+        block
+            .iter()
+            .enumerate()
+            .fold(in_.clone(), |state, (i, code)| {
+                Self::transfer_instr(code, block_id, i, &state)
+            })
+    }
+
+    fn transfer_instr(
+        code: &Code,
+        block_id: usize,
+        instr_id: usize,
+        in_: &Self::State,
+    ) -> Self::State {
+        let mut out = in_.clone();
+        match code {
+            Code::Instruction(Instruction::Value {
+                op: ValueOps::Alloc,
+                dest,
+                ..
+            }) => {
+                let site = (block_id, instr_id);
+                out.pointers
+                    .insert(dest.clone(), [site].into_iter().collect());
+                out.sites.insert(site, Status::LIVE);
+            }
+            Code::Instruction(Instruction::Value {
+                op: ValueOps::Id | ValueOps::PtrAdd,
+                dest,
+                args,
+                ..
+            }) => {
+                let sites = in_.pointers.get(&args[0]).clone();
+                out.pointers.insert(dest.clone(), sites);
+            }
+            Code::Instruction(
+                Instruction::Constant { dest, .. } | Instruction::Value { dest, .. },
+            ) => {
+                out.pointers.remove(dest);
+            }
+            Code::Instruction(Instruction::Effect {
+                op: EffectOps::Free,
+                args,
+                ..
+            }) => {
+                let sites: Vec<&Site> = in_.pointers.get(&args[0]).iter().collect();
+                // only a pointer into a single site frees that site's object for sure
+                for site in sites.iter() {
+                    let status = if sites.len() == 1 {
+                        Status::FREED
+                    } else {
+                        in_.sites.get(site).join(&Status::FREED)
+                    };
+                    out.sites.insert(**site, status);
+                }
+            }
+            _ => {}
+        }
+        out
+    }
+
+    // nothing is allocated yet and no variable points into an allocation
+    fn boundary_state() -> Self::State {
+        MemoryState {
+            pointers: MapLattice::bottom(),
+            sites: MapLattice::with_rest(Status {
+                unallocated: true,
+                live: false,
+                freed: false,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Problem {
+    // an allocation that can reach the end of the function, or its own next execution,
+    // without being freed
+    Leak,
+    DoubleFree,
+    UseAfterFree,
+}
+
+pub struct Diagnostic {
+    pub problem: Problem,
+    // on every path, rather than only on some
+    pub definitely: bool,
+    // the allocation for leaks, the offending instruction otherwise
+    pub instr: String,
+    pub pos: Option<Position>,
+}
+
+fn diagnostic(problem: Problem, definitely: bool, code: &Code) -> Diagnostic {
+    Diagnostic {
+        problem,
+        definitely,
+        instr: code.to_string().trim().to_string(),
+        pos: get_pos(code),
+    }
+}
+
+// sites with a pointer that leaves the function's sight: stored, passed to a call or returned
+fn find_escaped(blocks: &[Vec<Code>], points: &[Vec<MemoryState>]) -> HashSet<Site> {
+    let mut escaped = HashSet::new();
+    for (block, points) in blocks.iter().zip(points) {
+        for (i, code) in block.iter().enumerate() {
+            let leaving: &[String] = match code {
+                Code::Instruction(Instruction::Effect {
+                    op: EffectOps::Store,
+                    args,
+                    ..
+                }) => &args[1..],
+                Code::Instruction(
+                    Instruction::Value {
+                        op: ValueOps::Call,
+                        args,
+                        ..
+                    }
+                    | Instruction::Effect {
+                        op: EffectOps::Call | EffectOps::Return,
+                        args,
+                        ..
+                    },
+                ) => args,
+                _ => continue,
+            };
+            for arg in leaving {
+                escaped.extend(points[i].pointers.get(arg).iter().copied());
+            }
+        }
+    }
+    escaped
+}
+
+// leaks, double frees and accesses to freed memory, sorted by position
+pub fn check_memory(function: &Function) -> Vec<Diagnostic> {
+    let blocks = get_basic_blocks(function);
+    let succ = form_cfg(&blocks);
+    let pred = rev_graph(&succ);
+
+    let (in_, out) = MemorySafety::find(&blocks, &pred, &succ);
+    let points = MemorySafety::program_points(&blocks, &in_, &out);
+    let escaped = find_escaped(&blocks, &points);
+
+    let mut diagnostics = vec![];
+    // site -> its status wherever its object is abandoned, to report a leak if it may be live
+    let mut abandoned: Vec<(Site, Status)> = vec![];
+    let mut abandon = |site: Site, status: &Status| {
+        if escaped.contains(&site) {
+            return;
+        }
+        match abandoned.iter_mut().find(|(s, _)| *s == site) {
+            Some((_, joined)) => *joined = joined.join(status),
+            None => abandoned.push((site, *status)),
+        }
+    };
+
+    for (b, (block, points)) in blocks.iter().zip(points.iter()).enumerate() {
+        for (i, code) in block.iter().enumerate() {
+            let Code::Instruction(instr) = code else {
+                continue;
+            };
+            let (problem, ptr) = match instr {
+                Instruction::Effect {
+                    op: EffectOps::Free,
+                    args,
+                    ..
+                } => (Problem::DoubleFree, &args[0]),
+                Instruction::Value {
+                    op: ValueOps::Load,
+                    args,
+                    ..
+                }
+                | Instruction::Effect {
+                    op: EffectOps::Store,
+                    args,
+                    ..
+                } => (Problem::UseAfterFree, &args[0]),
+                Instruction::Value {
+                    op: ValueOps::Alloc,
+                    ..
+                } => {
+                    // reallocating abandons the previous object, if there is one
+                    let site = (b, i);
+                    let status = points[i].sites.get(&site);
+                    if status.live {
+                        abandon(
+                            site,
+                            &Status {
+                                unallocated: false,
+                                ..*status
+                            },
+                        );
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let sites = points[i].pointers.get(ptr);
+            let mut freed = false;
+            let mut definitely = true;
+            for site in sites.iter() {
+                let status = points[i].sites.get(site);
+                freed |= status.freed;
+                definitely &= *status == Status::FREED;
+            }
+            if freed {
+                diagnostics.push(diagnostic(problem, definitely, code));
+            }
+        }
+
+        // leaving the function, by returning or falling off the end
+        if succ[b].is_empty() {
+            let exit = points
+                .last()
+                .expect("points should include the block's end");
+            for (site, status) in exit.sites.iter() {
+                abandon(*site, status);
+            }
+        }
+    }
+
+    for ((b, i), status) in abandoned {
+        if status.live {
+            let definitely = status == Status::LIVE;
+            diagnostics.push(diagnostic(Problem::Leak, definitely, &blocks[b][i]));
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| {
+        diagnostic
+            .pos
+            .as_ref()
+            .map(|pos| (pos.pos.row, pos.pos.col))
+    });
+    diagnostics
+}
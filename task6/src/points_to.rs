@@ -0,0 +1,301 @@
+use std::collections::{BTreeSet, HashMap};
+
+use bril_rs::{Code, EffectOps, Instruction, Position, Program, ValueOps};
+
+use crate::bitvec::Interner;
+
+// an abstract object standing for everything allocated by one `alloc`
+#[derive(Clone, Debug)]
+pub struct AllocSite {
+    pub func: String,
+    // index into the function's instrs
+    pub index: usize,
+    // unique, readable name
+    pub name: String,
+    pub pos: Option<Position>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Node {
+    Var(String, String),
+    // the shadow variable `set` writes and `get` reads
+    Shadow(String, String),
+    // whatever is stored anywhere in the object
+    Contents(usize),
+    // whatever the function returns
+    Return(String),
+}
+
+// flow- and context-insensitive, inclusion-based (Andersen) points-to sets for a whole program
+// offsets are ignored, so a pointer into any part of an object points to the object
+pub struct PointsTo {
+    nodes: Interner<Node>,
+    pts: Vec<BTreeSet<usize>>,
+    sites: Vec<AllocSite>,
+}
+
+#[derive(Default)]
+struct Constraints {
+    // pts(from) ⊆ pts(to)
+    copies: Vec<(usize, usize)>,
+    // pts(*ptr) ⊆ pts(dest), as (ptr, dest)
+    loads: Vec<(usize, usize)>,
+    // pts(value) ⊆ pts(*ptr), as (ptr, value)
+    stores: Vec<(usize, usize)>,
+    // site ∈ pts(dest), as (site, dest)
+    allocs: Vec<(usize, usize)>,
+}
+
+impl PointsTo {
+    pub fn analyze(program: &Program) -> Self {
+        let mut nodes = Interner::new();
+        let mut sites = vec![];
+        let mut constraints = Constraints::default();
+
+        let params: HashMap<&str, Vec<&String>> = program
+            .functions
+            .iter()
+            .map(|function| {
+                (
+                    function.name.as_str(),
+                    function.args.iter().map(|arg| &arg.name).collect(),
+                )
+            })
+            .collect();
+
+        let mut name_counts: HashMap<String, usize> = HashMap::new();
+        for function in program.functions.iter() {
+            let var = |nodes: &mut Interner<Node>, name: &String| {
+                nodes.intern(&Node::Var(function.name.clone(), name.clone()))
+            };
+            let mut calls = vec![];
+
+            for (index, code) in function.instrs.iter().enumerate() {
+                let Code::Instruction(instr) = code else {
+                    continue;
+                };
+                match instr {
+                    Instruction::Value {
+                        op: ValueOps::Alloc,
+                        dest,
+                        pos,
+                        ..
+                    } => {
+                        let base = format!("{}.{dest}", function.name);
+                        let count = name_counts.entry(base.clone()).or_default();
+                        let name = if *count == 0 {
+                            base
+                        } else {
+                            format!("{base}.{count}")
+                        };
+                        *count += 1;
+                        constraints
+                            .allocs
+                            .push((sites.len(), var(&mut nodes, dest)));
+                        sites.push(AllocSite {
+                            func: function.name.clone(),
+                            index,
+                            name,
+                            pos: pos.clone(),
+                        });
+                    }
+                    Instruction::Value {
+                        op: op @ (ValueOps::Id | ValueOps::PtrAdd),
+                        dest,
+                        args,
+                        ..
+                    } => {
+                        // ptradd's second argument is the offset
+                        let sources = if *op == ValueOps::PtrAdd {
+                            &args[..1]
+                        } else {
+                            &args[..]
+                        };
+                        let dest = var(&mut nodes, dest);
+                        for source in sources {
+                            constraints.copies.push((var(&mut nodes, source), dest));
+                        }
+                    }
+                    Instruction::Value {
+                        op: ValueOps::Get,
+                        dest,
+                        ..
+                    } => {
+                        let shadow =
+                            nodes.intern(&Node::Shadow(function.name.clone(), dest.clone()));
+                        constraints.copies.push((shadow, var(&mut nodes, dest)));
+                    }
+                    Instruction::Effect {
+                        op: EffectOps::Set,
+                        args,
+                        ..
+                    } => {
+                        let shadow =
+                            nodes.intern(&Node::Shadow(function.name.clone(), args[0].clone()));
+                        constraints.copies.push((var(&mut nodes, &args[1]), shadow));
+                    }
+                    Instruction::Value {
+                        op: ValueOps::Load,
+                        dest,
+                        args,
+                        ..
+                    } => {
+                        constraints
+                            .loads
+                            .push((var(&mut nodes, &args[0]), var(&mut nodes, dest)));
+                    }
+                    Instruction::Effect {
+                        op: EffectOps::Store,
+                        args,
+                        ..
+                    } => {
+                        constraints
+                            .stores
+                            .push((var(&mut nodes, &args[0]), var(&mut nodes, &args[1])));
+                    }
+                    Instruction::Value {
+                        op: ValueOps::Call,
+                        dest,
+                        args,
+                        funcs,
+                        ..
+                    } => calls.push((&funcs[0], args, Some(var(&mut nodes, dest)))),
+                    Instruction::Effect {
+                        op: EffectOps::Call,
+                        args,
+                        funcs,
+                        ..
+                    } => calls.push((&funcs[0], args, None)),
+                    Instruction::Effect {
+                        op: EffectOps::Return,
+                        args,
+                        ..
+                    } if !args.is_empty() => {
+                        let ret = nodes.intern(&Node::Return(function.name.clone()));
+                        let value = var(&mut nodes, &args[0]);
+                        constraints.copies.push((value, ret));
+                    }
+                    _ => {}
+                }
+            }
+
+            for (callee, args, dest) in calls {
+                for (arg, param) in args
+                    .iter()
+                    .zip(params.get(callee.as_str()).into_iter().flatten())
+                {
+                    let arg = var(&mut nodes, arg);
+                    let param = nodes.intern(&Node::Var(callee.clone(), (*param).clone()));
+                    constraints.copies.push((arg, param));
+                }
+                if let Some(dest) = dest {
+                    let ret = nodes.intern(&Node::Return(callee.clone()));
+                    constraints.copies.push((ret, dest));
+                }
+            }
+        }
+
+        for site in 0..sites.len() {
+            nodes.intern(&Node::Contents(site));
+        }
+
+        let mut points_to = PointsTo {
+            pts: vec![BTreeSet::new(); nodes.len()],
+            nodes,
+            sites,
+        };
+        points_to.solve(constraints);
+        points_to
+    }
+
+    fn contents(&self, site: usize) -> usize {
+        self.nodes
+            .get(&Node::Contents(site))
+            .expect("every site should have a contents node")
+    }
+
+    fn solve(&mut self, constraints: Constraints) {
+        let n = self.nodes.len();
+        let mut edges: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); n];
+        let mut loads: Vec<Vec<usize>> = vec![vec![]; n];
+        let mut stores: Vec<Vec<usize>> = vec![vec![]; n];
+
+        for (from, to) in constraints.copies {
+            edges[from].insert(to);
+        }
+        for (ptr, dest) in constraints.loads {
+            loads[ptr].push(dest);
+        }
+        for (ptr, value) in constraints.stores {
+            stores[ptr].push(value);
+        }
+
+        let mut worklist: BTreeSet<usize> = BTreeSet::new();
+        for (site, dest) in constraints.allocs {
+            self.pts[dest].insert(site);
+            worklist.insert(dest);
+        }
+
+        while let Some(node) = worklist.pop_first() {
+            // loads and stores through the node add edges from and to the objects it points to
+            for site in self.pts[node].clone() {
+                let contents = self.contents(site);
+                for &dest in loads[node].iter() {
+                    if edges[contents].insert(dest) {
+                        worklist.insert(contents);
+                    }
+                }
+                for &value in stores[node].iter() {
+                    if edges[value].insert(contents) {
+                        worklist.insert(value);
+                    }
+                }
+            }
+
+            for &to in edges[node].iter() {
+                let before = self.pts[to].len();
+                let from = self.pts[node].clone();
+                self.pts[to].extend(from);
+                if self.pts[to].len() != before {
+                    worklist.insert(to);
+                }
+            }
+        }
+    }
+
+    pub fn sites(&self) -> &[AllocSite] {
+        &self.sites
+    }
+
+    // variables of func that may point anywhere, sorted
+    pub fn pointers(&self, func: &str) -> Vec<&String> {
+        let mut vars: Vec<&String> = (0..self.nodes.len())
+            .filter_map(|node| match self.nodes.value(node) {
+                Node::Var(f, var) if f == func && !self.pts[node].is_empty() => Some(var),
+                _ => None,
+            })
+            .collect();
+        vars.sort();
+        vars
+    }
+
+    // allocation sites var in func may point into
+    pub fn points_to(&self, func: &str, var: &str) -> BTreeSet<usize> {
+        self.nodes
+            .get(&Node::Var(func.to_string(), var.to_string()))
+            .map(|node| self.pts[node].clone())
+            .unwrap_or_default()
+    }
+
+    // allocation sites whose contents may point into the given site
+    pub fn contents_of(&self, site: usize) -> &BTreeSet<usize> {
+        &self.pts[self.contents(site)]
+    }
+
+    // whether a and b in func may point into the same object
+    pub fn may_alias(&self, func: &str, a: &str, b: &str) -> bool {
+        !self
+            .points_to(func, a)
+            .is_disjoint(&self.points_to(func, b))
+    }
+}
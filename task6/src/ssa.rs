@@ -2,7 +2,7 @@ use std::collections::{HashMap, HashSet, VecDeque};
 
 use bril_rs::{Code, Instruction};
 
-use crate::df::{DataFlowAnalysis, ReachingDefs};
+use crate::df::{DefSite, reaching_defs};
 
 pub fn get_defs(blocks: &Vec<Vec<Code>>) -> HashMap<String, HashSet<usize>> {
     let mut defs: HashMap<String, HashSet<usize>> = HashMap::new();
@@ -33,7 +33,7 @@ pub fn place_phi_nodes(
     let n = df.len();
     let mut phi_nodes: Vec<HashMap<String, HashSet<usize>>> = vec![HashMap::new(); n];
 
-    let (reaching_defs, _) = ReachingDefs::find(&blocks, &pred, &succ);
+    let ((reaching_defs, _), _) = reaching_defs(&[], &blocks, &pred, &succ);
 
     for (var, def_blocks) in defs {
         let mut added = vec![false; n];
@@ -56,8 +56,12 @@ pub fn place_phi_nodes(
                         var.clone(),
                         reaching_defs[df_block]
                             .get(var)
-                            .expect("var should be in reaching_defs[df_block]")
-                            .clone(),
+                            .iter()
+                            .map(|site| match site {
+                                DefSite::Instr(b, _) => *b,
+                                DefSite::Arg(_) => unreachable!("args aren't passed as defs"),
+                            })
+                            .collect(),
                     );
 
                 if !added[df_block] {
@@ -0,0 +1,501 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Literal, Position, Program, Type, ValueOps};
+
+use crate::chains::get_pos;
+
+pub struct TypeError {
+    pub func: String,
+    pub message: String,
+    pub pos: Option<Position>,
+}
+
+struct Signature<'a> {
+    args: Vec<&'a Type>,
+    return_type: Option<&'a Type>,
+}
+
+// operand and result types of the operators that aren't polymorphic
+fn get_op_signature(op: ValueOps) -> Option<(Vec<Type>, Type)> {
+    use Type::*;
+    use ValueOps::*;
+    let signature = match op {
+        Add | Sub | Mul | Div => (vec![Int, Int], Int),
+        Eq | Lt | Gt | Le | Ge => (vec![Int, Int], Bool),
+        Not => (vec![Bool], Bool),
+        And | Or => (vec![Bool, Bool], Bool),
+        Fadd | Fsub | Fmul | Fdiv => (vec![Float, Float], Float),
+        Feq | Flt | Fgt | Fle | Fge => (vec![Float, Float], Bool),
+        Ceq | Clt | Cgt | Cle | Cge => (vec![Char, Char], Bool),
+        Char2int => (vec![Char], Int),
+        Int2char => (vec![Int], Char),
+        Float2Bits => (vec![Float], Int),
+        Bits2Float => (vec![Int], Float),
+        Call | Id | Get | Undef | Alloc | Load | PtrAdd => return None,
+    };
+    Some(signature)
+}
+
+fn literal_fits(value: &Literal, tipe: &Type) -> bool {
+    // the JSON form can't tell integral floats from ints
+    matches!(
+        (value, tipe),
+        (Literal::Int(_), Type::Int | Type::Float)
+            | (Literal::Bool(_), Type::Bool)
+            | (Literal::Float(_), Type::Float)
+            | (Literal::Char(_), Type::Char)
+    )
+}
+
+struct Checker<'a> {
+    function: &'a Function,
+    signatures: &'a HashMap<&'a str, Signature<'a>>,
+    imported: &'a HashSet<&'a str>,
+    // the type every variable gets from its first definition
+    vars: HashMap<&'a String, &'a Type>,
+    // the types of the shadow variables `set` writes and `get` reads
+    shadows: HashMap<&'a String, &'a Type>,
+    labels: HashSet<&'a String>,
+    errors: Vec<TypeError>,
+    pos: Option<Position>,
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, message: String) {
+        self.errors.push(TypeError {
+            func: self.function.name.clone(),
+            message,
+            pos: self.pos.clone(),
+        });
+    }
+
+    fn declare(&mut self, var: &'a String, tipe: &'a Type) {
+        match self.vars.get(var) {
+            Some(&declared) if declared != tipe => {
+                self.error(format!(
+                    "`{var}` is defined as {tipe}, but elsewhere as {declared}"
+                ));
+            }
+            Some(_) => {}
+            None => {
+                self.vars.insert(var, tipe);
+            }
+        }
+    }
+
+    fn arity(&mut self, what: &str, args: &[String], expected: usize) -> bool {
+        if args.len() != expected {
+            self.error(format!(
+                "`{what}` takes {expected} argument{}, not {}",
+                if expected == 1 { "" } else { "s" },
+                args.len()
+            ));
+            return false;
+        }
+        true
+    }
+
+    fn type_of(&mut self, var: &String) -> Option<&'a Type> {
+        let tipe = self.vars.get(var).copied();
+        if tipe.is_none() {
+            self.error(format!("`{var}` is never defined"));
+        }
+        tipe
+    }
+
+    fn expect(&mut self, what: &str, var: &String, expected: &Type) {
+        if let Some(tipe) = self.type_of(var)
+            && tipe != expected
+        {
+            self.error(format!(
+                "argument `{var}` of `{what}` should be {expected}, not {tipe}"
+            ));
+        }
+    }
+
+    fn expect_pointer(&mut self, what: &str, var: &String) -> Option<&'a Type> {
+        match self.type_of(var)? {
+            Type::Pointer(pointee) => Some(pointee),
+            tipe => {
+                self.error(format!(
+                    "argument `{var}` of `{what}` should be a pointer, not {tipe}"
+                ));
+                None
+            }
+        }
+    }
+
+    fn produces(&mut self, what: &str, result: &Type, op_type: &Type) {
+        if result != op_type {
+            self.error(format!("`{what}` produces {result}, not {op_type}"));
+        }
+    }
+
+    fn check_labels(&mut self, what: &str, labels: &[String], expected: usize) {
+        if labels.len() != expected {
+            self.error(format!(
+                "`{what}` takes {expected} label{}, not {}",
+                if expected == 1 { "" } else { "s" },
+                labels.len()
+            ));
+        }
+        for label in labels {
+            if !self.labels.contains(label) {
+                self.error(format!("unknown label `.{label}`"));
+            }
+        }
+    }
+
+    // checks arguments against the callee's parameters and returns its return type,
+    // None if the callee is unknown or imported
+    fn check_call(&mut self, funcs: &[String], args: &[String]) -> Option<Option<&'a Type>> {
+        let Some(callee) = funcs.first() else {
+            self.error("`call` needs a function".to_string());
+            return None;
+        };
+        if self.imported.contains(callee.as_str()) {
+            return None;
+        }
+        let Some(signature) = self.signatures.get(callee.as_str()) else {
+            self.error(format!("unknown function `@{callee}`"));
+            return None;
+        };
+
+        let what = format!("@{callee}");
+        if self.arity(&what, args, signature.args.len()) {
+            for (arg, param) in args.iter().zip(signature.args.iter()) {
+                self.expect(&what, arg, param);
+            }
+        }
+        Some(signature.return_type)
+    }
+
+    fn check_value(&mut self, instr: &'a Instruction) {
+        let Instruction::Value {
+            op,
+            dest,
+            op_type,
+            args,
+            funcs,
+            labels,
+            ..
+        } = instr
+        else {
+            return;
+        };
+        let what = op.to_string();
+
+        if *op != ValueOps::Call && !funcs.is_empty() {
+            self.error(format!("`{what}` takes no functions"));
+        }
+        if !labels.is_empty() {
+            self.error(format!("`{what}` takes no labels"));
+        }
+
+        if let Some((params, result)) = get_op_signature(*op) {
+            if self.arity(&what, args, params.len()) {
+                for (arg, param) in args.iter().zip(params.iter()) {
+                    self.expect(&what, arg, param);
+                }
+            }
+            self.produces(&what, &result, op_type);
+            return;
+        }
+
+        match op {
+            ValueOps::Id => {
+                if self.arity(&what, args, 1) {
+                    self.expect(&what, &args[0], op_type);
+                }
+            }
+            ValueOps::Call => match self.check_call(funcs, args) {
+                Some(Some(return_type)) => {
+                    self.produces(&format!("@{}", funcs[0]), return_type, op_type)
+                }
+                Some(None) => self.error(format!("`@{}` doesn't return anything", funcs[0])),
+                None => {}
+            },
+            ValueOps::Get => {
+                self.arity(&what, args, 0);
+                match self.shadows.get(dest) {
+                    Some(&tipe) if tipe != op_type => {
+                        self.error(format!("`get` of `{dest}` should be {tipe}, not {op_type}"))
+                    }
+                    Some(_) => {}
+                    None => {
+                        self.shadows.insert(dest, op_type);
+                    }
+                }
+            }
+            ValueOps::Undef => {
+                self.arity(&what, args, 0);
+            }
+            ValueOps::Alloc => {
+                if self.arity(&what, args, 1) {
+                    self.expect(&what, &args[0], &Type::Int);
+                }
+                if !matches!(op_type, Type::Pointer(_)) {
+                    self.error(format!("`alloc` produces a pointer, not {op_type}"));
+                }
+            }
+            ValueOps::Load => {
+                if self.arity(&what, args, 1)
+                    && let Some(pointee) = self.expect_pointer(&what, &args[0])
+                {
+                    self.produces(&what, pointee, op_type);
+                }
+            }
+            ValueOps::PtrAdd => {
+                if self.arity(&what, args, 2) {
+                    if let Some(pointee) = self.expect_pointer(&what, &args[0]) {
+                        self.produces(&what, &Type::Pointer(Box::new(pointee.clone())), op_type);
+                    }
+                    self.expect(&what, &args[1], &Type::Int);
+                }
+            }
+            _ => unreachable!("operators with a signature are checked above"),
+        }
+    }
+
+    fn check_effect(&mut self, instr: &'a Instruction) {
+        let Instruction::Effect {
+            op,
+            args,
+            funcs,
+            labels,
+            ..
+        } = instr
+        else {
+            return;
+        };
+        let what = op.to_string();
+
+        match op {
+            EffectOps::Jump => {
+                self.arity(&what, args, 0);
+                self.check_labels(&what, labels, 1);
+            }
+            EffectOps::Branch => {
+                if self.arity(&what, args, 1) {
+                    self.expect(&what, &args[0], &Type::Bool);
+                }
+                self.check_labels(&what, labels, 2);
+            }
+            EffectOps::Guard => {
+                if self.arity(&what, args, 1) {
+                    self.expect(&what, &args[0], &Type::Bool);
+                }
+                self.check_labels(&what, labels, 1);
+            }
+            EffectOps::Call => {
+                self.check_call(funcs, args);
+            }
+            EffectOps::Return => match &self.function.return_type {
+                Some(return_type) => {
+                    if self.arity(&what, args, 1) {
+                        self.expect(&what, &args[0], return_type);
+                    }
+                }
+                None => {
+                    if !args.is_empty() {
+                        self.error(format!(
+                            "`ret` takes no arguments in `@{}`, which returns nothing",
+                            self.function.name
+                        ));
+                    }
+                }
+            },
+            EffectOps::Print => {
+                for arg in args {
+                    self.type_of(arg);
+                }
+            }
+            EffectOps::Nop | EffectOps::Speculate | EffectOps::Commit => {
+                self.arity(&what, args, 0);
+            }
+            EffectOps::Store => {
+                if self.arity(&what, args, 2)
+                    && let Some(pointee) = self.expect_pointer(&what, &args[0])
+                {
+                    self.expect(&what, &args[1], pointee);
+                }
+            }
+            EffectOps::Free => {
+                if self.arity(&what, args, 1) {
+                    self.expect_pointer(&what, &args[0]);
+                }
+            }
+            EffectOps::Set => {
+                if self.arity(&what, args, 2)
+                    && let Some(tipe) = self.type_of(&args[1])
+                {
+                    let shadow = &args[0];
+                    match self.shadows.get(shadow) {
+                        Some(&expected) if expected != tipe => self.error(format!(
+                            "`set` of `{shadow}` should be {expected}, not {tipe}"
+                        )),
+                        Some(_) => {}
+                        None => {
+                            self.shadows.insert(shadow, tipe);
+                        }
+                    }
+                }
+            }
+        }
+
+        if !matches!(op, EffectOps::Call) && !funcs.is_empty() {
+            self.error(format!("`{what}` takes no functions"));
+        }
+        if !matches!(op, EffectOps::Jump | EffectOps::Branch | EffectOps::Guard)
+            && !labels.is_empty()
+        {
+            self.error(format!("`{what}` takes no labels"));
+        }
+    }
+
+    fn check(&mut self) {
+        let function = self.function;
+
+        let mut params = HashSet::new();
+        for arg in function.args.iter() {
+            if !params.insert(&arg.name) {
+                self.pos = function.pos.clone();
+                self.error(format!("argument `{}` is declared twice", arg.name));
+            }
+            self.vars.insert(&arg.name, &arg.arg_type);
+        }
+
+        // every variable has the type of its first definition, so uses may come before it
+        for code in function.instrs.iter() {
+            match code {
+                Code::Label { label, .. } => {
+                    self.labels.insert(label);
+                }
+                Code::Instruction(
+                    Instruction::Constant {
+                        dest, const_type, ..
+                    }
+                    | Instruction::Value {
+                        dest,
+                        op_type: const_type,
+                        ..
+                    },
+                ) => {
+                    self.pos = get_pos(code);
+                    self.declare(dest, const_type);
+                }
+                Code::Instruction(Instruction::Effect { .. }) => {}
+            }
+        }
+
+        // get reads what set writes, so the shadow types come from the gets first
+        for code in function.instrs.iter() {
+            if let Code::Instruction(
+                instr @ Instruction::Value {
+                    op: ValueOps::Get, ..
+                },
+            ) = code
+            {
+                self.pos = get_pos(code);
+                self.check_value(instr);
+            }
+        }
+
+        for code in function.instrs.iter() {
+            let Code::Instruction(instr) = code else {
+                continue;
+            };
+            self.pos = get_pos(code);
+            match instr {
+                Instruction::Constant {
+                    value, const_type, ..
+                } => {
+                    if !literal_fits(value, const_type) {
+                        self.error(format!("constant {value} is not {const_type}"));
+                    }
+                }
+                Instruction::Value {
+                    op: ValueOps::Get, ..
+                } => {}
+                Instruction::Value { .. } => self.check_value(instr),
+                Instruction::Effect { .. } => self.check_effect(instr),
+            }
+        }
+    }
+}
+
+// every type error in the program, function by function, in the order they are found
+pub fn check_program(program: &Program) -> Vec<TypeError> {
+    let signatures: HashMap<&str, Signature> = program
+        .functions
+        .iter()
+        .map(|function| {
+            (
+                function.name.as_str(),
+                Signature {
+                    args: function.args.iter().map(|arg| &arg.arg_type).collect(),
+                    return_type: function.return_type.as_ref(),
+                },
+            )
+        })
+        .collect();
+    let imported: HashSet<&str> = program
+        .imports
+        .iter()
+        .flat_map(|import| import.functions.iter())
+        .map(|function| function.alias.as_ref().unwrap_or(&function.name).as_str())
+        .collect();
+
+    let mut errors = vec![];
+    let mut names = HashSet::new();
+    for function in program.functions.iter() {
+        if !names.insert(&function.name) {
+            errors.push(TypeError {
+                func: function.name.clone(),
+                message: format!("function `@{}` is defined twice", function.name),
+                pos: function.pos.clone(),
+            });
+        }
+
+        let mut checker = Checker {
+            function,
+            signatures: &signatures,
+            imported: &imported,
+            vars: HashMap::new(),
+            shadows: HashMap::new(),
+            labels: HashSet::new(),
+            errors: vec![],
+            pos: None,
+        };
+        checker.check();
+        errors.extend(checker.errors);
+    }
+    errors
+}
+
+// variable -> type for a function, from its arguments and the first definition of each variable
+pub fn infer_types(function: &Function) -> HashMap<String, Type> {
+    let mut types: HashMap<String, Type> = function
+        .args
+        .iter()
+        .map(|arg| (arg.name.clone(), arg.arg_type.clone()))
+        .collect();
+    for code in function.instrs.iter() {
+        if let Code::Instruction(
+            Instruction::Constant {
+                dest, const_type, ..
+            }
+            | Instruction::Value {
+                dest,
+                op_type: const_type,
+                ..
+            },
+        ) = code
+        {
+            types
+                .entry(dest.clone())
+                .or_insert_with(|| const_type.clone());
+        }
+    }
+    types
+}
@@ -0,0 +1,142 @@
+use bril_rs::{Code, Function, Instruction, Position};
+
+use crate::{
+    cfg::{form_cfg, get_basic_blocks},
+    chains::get_pos,
+    df::DataFlowAnalysis,
+    dom::rev_graph,
+    lattice::{Lattice, MapLattice},
+};
+
+// unreached < defined, undefined < maybe
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Definedness {
+    Unreached,
+    Defined,
+    Undefined,
+    Maybe,
+}
+
+impl Lattice for Definedness {
+    fn top() -> Self {
+        Definedness::Maybe
+    }
+
+    fn bottom() -> Self {
+        Definedness::Unreached
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        use Definedness::*;
+        match (self, other) {
+            (Maybe, x) | (x, Maybe) => *x,
+            _ if self == other => *self,
+            _ => Unreached,
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        use Definedness::*;
+        match (self, other) {
+            (Unreached, x) | (x, Unreached) => *x,
+            _ if self == other => *self,
+            _ => Maybe,
+        }
+    }
+
+    fn leq(&self, other: &Self) -> bool {
+        use Definedness::*;
+        matches!((self, other), (Unreached, _) | (_, Maybe)) || self == other
+    }
+}
+
+pub struct MaybeUndefined;
+
+impl DataFlowAnalysis for MaybeUndefined {
+    const FORWARD: bool = true;
+
+    // var -> whether it's defined on the paths reaching here
+    type State = MapLattice<String, Definedness>;
+
+    fn transfer(block: &Vec<Code>, _block_id: usize, in_: &Self::State) -> Self::State {
+        let mut out = in_.clone();
+
+        for code in block {
+            if let Code::Instruction(
+                Instruction::Constant { dest, .. } | Instruction::Value { dest, .. },
+            ) = code
+            {
+                out.insert(dest.clone(), Definedness::Defined);
+            }
+        }
+
+        out
+    }
+
+    // nothing is defined at the entry, solve with `entry_state` to count the arguments as defined
+    fn boundary_state() -> Self::State {
+        MapLattice::with_rest(Definedness::Undefined)
+    }
+}
+
+pub fn entry_state(function: &Function) -> <MaybeUndefined as DataFlowAnalysis>::State {
+    let mut state = MaybeUndefined::boundary_state();
+    for arg in function.args.iter() {
+        state.insert(arg.name.clone(), Definedness::Defined);
+    }
+    state
+}
+
+pub struct Diagnostic {
+    pub var: String,
+    // undefined on every path, rather than only on some
+    pub definitely: bool,
+    pub instr: String,
+    pub pos: Option<Position>,
+}
+
+// uses of variables that are undefined on some path reaching them, in program order
+pub fn find_undefined_uses(function: &Function) -> Vec<Diagnostic> {
+    let blocks = get_basic_blocks(function);
+    let succ = form_cfg(&blocks);
+    let pred = rev_graph(&succ);
+
+    let ((in_, out), _) =
+        MaybeUndefined::find_with_boundary(&blocks, &pred, &succ, entry_state(function));
+    let points = MaybeUndefined::program_points(&blocks, &in_, &out);
+
+    let mut diagnostics = vec![];
+    for (block, points) in blocks.iter().zip(points) {
+        for (i, code) in block.iter().enumerate() {
+            let args = match code {
+                Code::Instruction(
+                    Instruction::Value { args, .. } | Instruction::Effect { args, .. },
+                ) => args,
+                _ => continue,
+            };
+
+            let mut reported = vec![];
+            for arg in args {
+                // unreached uses are dead code, not errors
+                let definitely = match points[i].get(arg) {
+                    Definedness::Undefined => true,
+                    Definedness::Maybe => false,
+                    Definedness::Defined | Definedness::Unreached => continue,
+                };
+                if reported.contains(&arg) {
+                    continue;
+                }
+                reported.push(arg);
+
+                diagnostics.push(Diagnostic {
+                    var: arg.clone(),
+                    definitely,
+                    instr: code.to_string().trim().to_string(),
+                    pos: get_pos(code),
+                });
+            }
+        }
+    }
+
+    diagnostics
+}
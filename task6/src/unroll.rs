@@ -0,0 +1,971 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::{Code, ConstOps, EffectOps, Function, Instruction, Literal, Type, ValueOps};
+
+use crate::{
+    bitvec::{BitSet, BitVectorProblem, Interner},
+    cfg::{form_cfg, get_basic_blocks, get_label},
+    chains::Site,
+    cse::{fresh_name, get_vars},
+    df::DataFlowAnalysis,
+    dom::{find_dominators, rev_graph},
+    induction::{get_instr, get_loop_defs},
+    intervals::{Interval, IntervalAnalysis},
+    loops::{NaturalLoop, falls_through, find_natural_loops, get_labels, insert_preheader},
+    undef::{Definedness, MaybeUndefined, entry_state},
+};
+
+pub struct UnrollConfig {
+    // how many iterations one trip around an unrolled loop does, 1 to never unroll partially
+    pub factor: usize,
+    // loops with a known trip count are unrolled completely if it takes at most this many
+    // instructions
+    pub size_limit: usize,
+}
+
+impl Default for UnrollConfig {
+    fn default() -> Self {
+        UnrollConfig {
+            factor: 4,
+            size_limit: 64,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct UnrollStats {
+    // loops replaced by a copy of each of their iterations
+    pub fully: usize,
+    // loops unrolled by the factor, with the original kept for the remaining iterations
+    pub partially: usize,
+}
+
+// a loop that only exits by testing a variable stepping by a constant against an invariant
+struct CountedLoop {
+    latch: usize,
+    // the header or the latch
+    exiting: usize,
+    // where the exit test is in the exiting block, if nothing but its branch reads it
+    test: Option<usize>,
+    // labels the exit test branches to
+    stay: String,
+    exit: String,
+    // the loop goes on while `value op bound`
+    op: ValueOps,
+    bound: String,
+    // value = carried + offset, with carried as it is at the top of the iteration
+    carried: String,
+    offset: i64,
+    step: i64,
+    // in SSA form, the variables the header gets, with their types and what the latch sets them to
+    gets: Vec<(String, Type, String)>,
+}
+
+impl CountedLoop {
+    fn get(&self, var: &String) -> Option<&(String, Type, String)> {
+        self.gets.iter().find(|(get, ..)| get == var)
+    }
+}
+
+// variables defined only by an int constant
+fn get_constants(function: &Function) -> HashMap<String, i64> {
+    let mut defs: HashMap<&String, Option<i64>> = HashMap::new();
+    for arg in function.args.iter() {
+        defs.insert(&arg.name, None);
+    }
+    for code in function.instrs.iter() {
+        let (dest, value) = match code {
+            Code::Instruction(Instruction::Constant {
+                dest,
+                value: Literal::Int(value),
+                ..
+            }) => (dest, Some(*value)),
+            Code::Instruction(
+                Instruction::Constant { dest, .. } | Instruction::Value { dest, .. },
+            ) => (dest, None),
+            _ => continue,
+        };
+        // a second definition makes the value unknown
+        defs.entry(dest)
+            .and_modify(|known| *known = None)
+            .or_insert(value);
+    }
+    defs.into_iter()
+        .filter_map(|(var, value)| Some((var.clone(), value?)))
+        .collect()
+}
+
+// the comparison with its operands swapped
+fn flip(op: ValueOps) -> ValueOps {
+    match op {
+        ValueOps::Lt => ValueOps::Gt,
+        ValueOps::Le => ValueOps::Ge,
+        ValueOps::Gt => ValueOps::Lt,
+        ValueOps::Ge => ValueOps::Le,
+        _ => unreachable!("only orderings are flipped"),
+    }
+}
+
+fn negate(op: ValueOps) -> ValueOps {
+    match op {
+        ValueOps::Lt => ValueOps::Ge,
+        ValueOps::Le => ValueOps::Gt,
+        ValueOps::Gt => ValueOps::Le,
+        ValueOps::Ge => ValueOps::Lt,
+        _ => unreachable!("only orderings are negated"),
+    }
+}
+
+fn passes(op: ValueOps, value: i64, bound: i64) -> bool {
+    match op {
+        ValueOps::Lt => value < bound,
+        ValueOps::Le => value <= bound,
+        ValueOps::Gt => value > bound,
+        ValueOps::Ge => value >= bound,
+        _ => unreachable!("only orderings are tested"),
+    }
+}
+
+fn find_counted_loop(
+    blocks: &Vec<Vec<Code>>,
+    succ: &[Vec<usize>],
+    natural_loop: &NaturalLoop,
+    constants: &HashMap<String, i64>,
+) -> Option<CountedLoop> {
+    let header = natural_loop.header;
+    let [latch] = natural_loop.latches[..] else {
+        return None;
+    };
+
+    let mut exits = natural_loop.body.iter().flat_map(|&b| {
+        succ[b]
+            .iter()
+            .filter(|s| !natural_loop.contains(**s))
+            .map(move |&s| (b, s))
+    });
+    let (exiting, exit_block) = exits.next()?;
+    if exits.next().is_some() || (exiting != header && exiting != latch) {
+        return None;
+    }
+    let Some(Code::Instruction(Instruction::Effect {
+        op: EffectOps::Branch,
+        args,
+        labels,
+        ..
+    })) = blocks[exiting].last()
+    else {
+        return None;
+    };
+    let exit = get_label(blocks, exit_block);
+    if labels[0] == labels[1] {
+        return None;
+    }
+    let stays_if_true = labels[1] == exit;
+    let stay = if stays_if_true {
+        &labels[0]
+    } else {
+        &labels[1]
+    };
+
+    // the test is the last definition of the condition before the branch
+    let (test, instr) =
+        blocks[exiting]
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, code)| match code {
+                Code::Instruction(
+                    instr @ (Instruction::Constant { dest, .. } | Instruction::Value { dest, .. }),
+                ) if *dest == args[0] => Some((i, instr)),
+                _ => None,
+            })?;
+    let Instruction::Value {
+        op: op @ (ValueOps::Lt | ValueOps::Le | ValueOps::Gt | ValueOps::Ge),
+        args: operands,
+        ..
+    } = instr
+    else {
+        return None;
+    };
+
+    let defs = get_loop_defs(blocks, natural_loop);
+    let invariant = |var: &String| !defs.contains_key(var);
+    let (op, value, bound) = match &operands[..] {
+        [a, b] if !invariant(a) && invariant(b) => (*op, a, b),
+        [a, b] if invariant(a) && !invariant(b) => (flip(*op), b, a),
+        _ => return None,
+    };
+    let op = if stays_if_true { op } else { negate(op) };
+    let reads = blocks
+        .iter()
+        .flatten()
+        .filter(|code| match code {
+            Code::Instruction(instr) => get_uses(instr).contains(&args[0]),
+            Code::Label { .. } => false,
+        })
+        .count();
+    let dead_test = (reads == 1).then_some(test);
+
+    // in SSA form, every variable the header gets is set once in the loop, by the latch
+    let mut sets: HashMap<&String, Vec<(usize, &String)>> = HashMap::new();
+    for &b in natural_loop.body.iter() {
+        for code in blocks[b].iter() {
+            if let Code::Instruction(Instruction::Effect {
+                op: EffectOps::Set,
+                args,
+                ..
+            }) = code
+            {
+                sets.entry(&args[0]).or_default().push((b, &args[1]));
+            }
+        }
+    }
+    let mut gets = vec![];
+    for code in blocks[header].iter() {
+        if let Code::Instruction(Instruction::Value {
+            op: ValueOps::Get,
+            dest,
+            op_type,
+            ..
+        }) = code
+        {
+            let [(b, source)] = sets.get(dest)?[..] else {
+                return None;
+            };
+            if b != latch {
+                return None;
+            }
+            gets.push((dest.clone(), op_type.clone(), source.clone()));
+        }
+    }
+
+    // the site and step of var's only definition in the loop, if it's `of` plus a constant
+    let stepping = |var: &String, of: &String| -> Option<(Site, i64)> {
+        let [site] = defs.get(var)?[..] else {
+            return None;
+        };
+        let Instruction::Value { op, args, .. } = get_instr(blocks, site) else {
+            return None;
+        };
+        let step = match (op, &args[..]) {
+            (ValueOps::Add, [a, c] | [c, a]) if a == of && c != of => *constants.get(c)?,
+            (ValueOps::Sub, [a, c]) if a == of && c != of => constants.get(c)?.checked_neg()?,
+            _ => return None,
+        };
+        (step != 0).then_some((site, step))
+    };
+
+    let (carried, offset, step) =
+        if let Some((_, _, source)) = gets.iter().find(|(get, ..)| get == value) {
+            // SSA form, testing the value the header gets
+            let (_, step) = stepping(source, value)?;
+            (value.clone(), 0, step)
+        } else if let Some((get, ..)) = gets.iter().find(|(_, _, source)| source == value) {
+            // SSA form, testing the value the latch sets
+            let (_, step) = stepping(value, get)?;
+            (get.clone(), step, step)
+        } else {
+            // before SSA, a variable stepping itself in a block run on every iteration
+            let (site, step) = stepping(value, value)?;
+            if site.0 != header && site.0 != latch {
+                return None;
+            }
+            // the header comes first in an iteration, the latch last
+            let order = |(b, i): Site| (b != header, i);
+            let offset = if order(site) < order((exiting, test)) {
+                step
+            } else {
+                0
+            };
+            (value.clone(), offset, step)
+        };
+
+    Some(CountedLoop {
+        latch,
+        exiting,
+        test: dead_test,
+        stay: stay.clone(),
+        exit,
+        op,
+        bound: bound.clone(),
+        carried,
+        offset,
+        step,
+        gets,
+    })
+}
+
+// the variables an instruction reads, a set's first argument names a shadow variable instead
+fn get_uses(instr: &Instruction) -> &[String] {
+    match instr {
+        Instruction::Effect {
+            op: EffectOps::Set,
+            args,
+            ..
+        } => &args[1..],
+        Instruction::Value { args, .. } | Instruction::Effect { args, .. } => args,
+        Instruction::Constant { .. } => &[],
+    }
+}
+
+// variables live on entry to each block
+fn find_live_in(
+    blocks: &[Vec<Code>],
+    pred: &Vec<Vec<usize>>,
+    succ: &Vec<Vec<usize>>,
+) -> Vec<HashSet<String>> {
+    let mut vars = Interner::new();
+    let mut steps: Vec<Vec<(Vec<usize>, Option<usize>)>> = vec![];
+    for block in blocks {
+        let mut block_steps = vec![];
+        for code in block {
+            let Code::Instruction(instr) = code else {
+                continue;
+            };
+            let uses = get_uses(instr).iter().map(|var| vars.intern(var)).collect();
+            let def = match instr {
+                Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => {
+                    Some(vars.intern(dest))
+                }
+                Instruction::Effect { .. } => None,
+            };
+            block_steps.push((uses, def));
+        }
+        steps.push(block_steps);
+    }
+
+    let width = vars.len();
+    let mut gens = vec![];
+    let mut kills = vec![];
+    for block_steps in steps.iter() {
+        // gen = uses not preceded by a definition in the block, kill = definitions
+        let mut gen_ = BitSet::new(width);
+        let mut kill = BitSet::new(width);
+        for (uses, def) in block_steps.iter().rev() {
+            if let Some(def) = def {
+                gen_.remove(*def);
+                kill.insert(*def);
+            }
+            for var in uses {
+                gen_.insert(*var);
+            }
+        }
+        gens.push(gen_);
+        kills.push(kill);
+    }
+
+    let problem = BitVectorProblem {
+        forward: false,
+        may: true,
+        width,
+        gens,
+        kills,
+        boundary: BitSet::new(width),
+    };
+    let ((in_, _), _) = problem.solve(pred, succ);
+    in_.into_iter()
+        .map(|live| live.iter().map(|var| vars.value(var).clone()).collect())
+        .collect()
+}
+
+fn value(dest: &str, op: ValueOps, args: &[&String], op_type: Type) -> Code {
+    Code::Instruction(Instruction::Value {
+        args: args.iter().map(|arg| (*arg).clone()).collect(),
+        dest: dest.to_string(),
+        funcs: vec![],
+        labels: vec![],
+        op,
+        pos: None,
+        op_type,
+    })
+}
+
+fn constant(dest: &str, value: i64) -> Code {
+    Code::Instruction(Instruction::Constant {
+        dest: dest.to_string(),
+        op: ConstOps::Const,
+        pos: None,
+        const_type: Type::Int,
+        value: Literal::Int(value),
+    })
+}
+
+fn effect(op: EffectOps, args: &[&String], labels: &[&String]) -> Code {
+    Code::Instruction(Instruction::Effect {
+        args: args.iter().map(|arg| (*arg).clone()).collect(),
+        funcs: vec![],
+        labels: labels.iter().map(|label| (*label).clone()).collect(),
+        op,
+        pos: None,
+    })
+}
+
+fn rename(var: &String, names: &HashMap<String, String>) -> String {
+    names.get(var).unwrap_or(var).clone()
+}
+
+// how one iteration of the loop is copied
+struct IterationCopy {
+    // var -> its name in the copy, the variables the header gets included
+    names: HashMap<String, String>,
+    // loop block -> its label in the copy
+    labels: HashMap<usize, String>,
+    // what the header's gets become, keyed by variable, dropped if missing
+    gets: HashMap<String, Code>,
+    // what the latch's sets of the variables the header gets target instead, dropped if missing
+    sets: HashMap<String, String>,
+    // where the loop would go back to its header
+    next: String,
+    // whether the exit test exits instead of staying
+    exits: bool,
+}
+
+fn copy_iteration(
+    blocks: &Vec<Vec<Code>>,
+    succ: &[Vec<usize>],
+    natural_loop: &NaturalLoop,
+    counted: &CountedLoop,
+    copy: &IterationCopy,
+) -> Vec<Vec<Code>> {
+    let header = natural_loop.header;
+    let loop_labels: HashMap<String, usize> = natural_loop
+        .body
+        .iter()
+        .map(|&b| (get_label(blocks, b), b))
+        .collect();
+    let target = |label: &String| match loop_labels.get(label) {
+        Some(&b) if b == header => copy.next.clone(),
+        Some(b) => copy.labels[b].clone(),
+        None => label.clone(),
+    };
+
+    // the header first, the rest in their original order
+    let order = [header]
+        .into_iter()
+        .chain(natural_loop.body.iter().copied().filter(|&b| b != header));
+    // a copy ending with the exit test doesn't need what the test would have stayed for
+    let order: Vec<usize> = if copy.exits && counted.exiting == header {
+        vec![header]
+    } else {
+        order.collect()
+    };
+
+    let mut copied = vec![];
+    for b in order {
+        let mut codes = vec![Code::Label {
+            label: copy.labels[&b].clone(),
+            pos: None,
+        }];
+        for (i, code) in blocks[b].iter().enumerate() {
+            let Code::Instruction(instr) = code else {
+                continue;
+            };
+            match instr {
+                Instruction::Value {
+                    op: ValueOps::Get,
+                    dest,
+                    ..
+                } if b == header && counted.get(dest).is_some() => {
+                    codes.extend(copy.gets.get(dest).cloned());
+                }
+                Instruction::Effect {
+                    op: EffectOps::Set,
+                    args,
+                    ..
+                } if b == counted.latch && counted.get(&args[0]).is_some() => {
+                    if let Some(shadow) = copy.sets.get(&args[0]) {
+                        let source = rename(&args[1], &copy.names);
+                        codes.push(effect(EffectOps::Set, &[shadow, &source], &[]));
+                    }
+                }
+                Instruction::Effect {
+                    op: EffectOps::Branch,
+                    ..
+                } if b == counted.exiting && i + 1 == blocks[b].len() => {
+                    let label = if copy.exits {
+                        counted.exit.clone()
+                    } else {
+                        target(&counted.stay)
+                    };
+                    codes.push(effect(EffectOps::Jump, &[], &[&label]));
+                }
+                // the copies don't branch on the test
+                _ if b == counted.exiting && counted.test == Some(i) => {}
+                _ => {
+                    let mut instr = instr.clone();
+                    match &mut instr {
+                        Instruction::Constant { dest, .. } => *dest = rename(dest, &copy.names),
+                        Instruction::Value { dest, args, .. } => {
+                            *dest = rename(dest, &copy.names);
+                            args.iter_mut()
+                                .for_each(|arg| *arg = rename(arg, &copy.names));
+                        }
+                        Instruction::Effect { args, labels, .. } => {
+                            args.iter_mut()
+                                .for_each(|arg| *arg = rename(arg, &copy.names));
+                            labels.iter_mut().for_each(|label| *label = target(label));
+                        }
+                    }
+                    codes.push(Code::Instruction(instr));
+                }
+            }
+        }
+        // blocks are copied in order, but the next one might not be the same, so every block ends
+        // with its own jump until drop_jumps_to_next
+        if falls_through(&codes)
+            && let Some(&s) = succ[b].first()
+        {
+            let label = target(&get_label(blocks, s));
+            codes.push(effect(EffectOps::Jump, &[], &[&label]));
+        }
+        copied.push(codes);
+    }
+    copied
+}
+
+// points every set outside the loop of a variable the header gets to its new name
+fn retarget_sets(
+    blocks: &mut [Vec<Code>],
+    natural_loop: &NaturalLoop,
+    shadows: &HashMap<String, String>,
+) {
+    for (b, block) in blocks.iter_mut().enumerate() {
+        if natural_loop.contains(b) {
+            continue;
+        }
+        for code in block.iter_mut() {
+            if let Code::Instruction(Instruction::Effect {
+                op: EffectOps::Set,
+                args,
+                ..
+            }) = code
+            {
+                args[0] = rename(&args[0], shadows);
+            }
+        }
+    }
+}
+
+// drops the jumps of blocks[start..end] to the block right after them
+fn drop_jumps_to_next(blocks: &mut [Vec<Code>], start: usize, end: usize) {
+    for b in start..end {
+        let Some(Code::Label { label, .. }) = blocks.get(b + 1).and_then(|next| next.first())
+        else {
+            continue;
+        };
+        if let Some(Code::Instruction(Instruction::Effect {
+            op: EffectOps::Jump,
+            labels,
+            ..
+        })) = blocks[b].last()
+            && labels[0] == *label
+        {
+            blocks[b].pop();
+        }
+    }
+}
+
+enum Unrolled {
+    Fully,
+    // with the label of the new loop's header
+    Partially(String),
+}
+
+// the number of times the exit test stays, if it's known and below max
+fn find_trip_count(
+    blocks: &Vec<Vec<Code>>,
+    pred: &Vec<Vec<usize>>,
+    succ: &Vec<Vec<usize>>,
+    preheader: usize,
+    natural_loop: &NaturalLoop,
+    counted: &CountedLoop,
+    max: usize,
+) -> Option<usize> {
+    let (in_, out) = IntervalAnalysis::find(blocks, pred, succ);
+    let known = |interval: &Interval| match interval {
+        Interval::Range(lo, hi) if lo == hi && *lo != i64::MIN && *lo != i64::MAX => Some(*lo),
+        _ => None,
+    };
+    let entering = &out[preheader];
+    let bound = known(entering.get(&counted.bound))?;
+    let start = if counted.get(&counted.carried).is_some() {
+        // in SSA form, the value the only set before the loop gives
+        let points = IntervalAnalysis::program_points(blocks, &in_, &out);
+        let mut sets = blocks.iter().enumerate().flat_map(|(b, block)| {
+            block
+                .iter()
+                .enumerate()
+                .filter_map(move |(i, code)| match code {
+                    Code::Instruction(Instruction::Effect {
+                        op: EffectOps::Set,
+                        args,
+                        ..
+                    }) if args[0] == counted.carried && !natural_loop.contains(b) => {
+                        Some((b, i, &args[1]))
+                    }
+                    _ => None,
+                })
+        });
+        let (b, i, source) = sets.next()?;
+        if sets.next().is_some() {
+            return None;
+        }
+        known(points[b][i].get(source))?
+    } else {
+        known(entering.get(&counted.carried))?
+    };
+
+    let mut value = start.wrapping_add(counted.offset);
+    let mut trips = 0;
+    while passes(counted.op, value, bound) {
+        trips += 1;
+        if trips >= max {
+            return None;
+        }
+        value = value.wrapping_add(counted.step);
+    }
+    Some(trips)
+}
+
+fn unroll_loop(
+    function: &Function,
+    blocks: &[Vec<Code>],
+    pred: &[Vec<usize>],
+    natural_loop: &NaturalLoop,
+    config: &UnrollConfig,
+    vars: &mut HashSet<String>,
+) -> Option<(Vec<Vec<Code>>, Unrolled)> {
+    let mut blocks = blocks.to_vec();
+    let preheader = insert_preheader(&mut blocks, pred, natural_loop);
+    let succ = form_cfg(&blocks);
+    let pred = rev_graph(&succ);
+    let natural_loop = find_natural_loops(&pred, &succ)
+        .into_iter()
+        .find(|natural_loop| natural_loop.header == preheader + 1)
+        .expect("the loop should still be there after its preheader");
+    let header = natural_loop.header;
+    let counted = find_counted_loop(&blocks, &succ, &natural_loop, &get_constants(function))?;
+
+    // the test is done before the loop, so its operands must be defined there
+    let ((_, out), _) =
+        MaybeUndefined::find_with_boundary(&blocks, &pred, &succ, entry_state(function));
+    let defined = |var: &String| *out[preheader].get(var) == Definedness::Defined;
+    if !defined(&counted.bound)
+        || !(counted.get(&counted.carried).is_some() || defined(&counted.carried))
+    {
+        return None;
+    }
+
+    let size: usize = natural_loop
+        .body
+        .iter()
+        .map(|&b| {
+            blocks[b]
+                .iter()
+                .filter(|code| matches!(code, Code::Instruction(_)))
+                .count()
+        })
+        .sum();
+    let trips = find_trip_count(
+        &blocks,
+        &pred,
+        &succ,
+        preheader,
+        &natural_loop,
+        &counted,
+        config.size_limit / size.max(1),
+    );
+    if trips.is_none() && config.factor < 2 {
+        return None;
+    }
+
+    // variables carried from one iteration to the next, or out of the loop, keep their names
+    let live_in = find_live_in(&blocks, &pred, &succ);
+    let exit = (0..blocks.len())
+        .find(|&b| get_label(&blocks, b) == counted.exit)
+        .expect("the exit should be a block");
+    let dom = find_dominators(&pred, &succ);
+    let defs = get_loop_defs(&blocks, &natural_loop);
+    let mut kept: HashSet<&String> = live_in[header].iter().collect();
+    // whatever leaves the loop comes from its original or its last copy, which keep the names,
+    // so only values they might not redefine before exiting must be kept
+    kept.extend(live_in[exit].iter().filter(|var| {
+        defs.get(*var)
+            .is_some_and(|sites| sites.iter().any(|(b, _)| !dom[counted.exiting].contains(b)))
+    }));
+    let mut renamed: Vec<&String> = defs
+        .keys()
+        .filter(|var| !kept.contains(var) && counted.get(var).is_none())
+        .collect();
+    renamed.sort();
+
+    let mut labels = get_labels(&blocks);
+    let copies = match trips {
+        Some(trips) => trips + 1,
+        None => config.factor,
+    };
+    let mut iterations: Vec<IterationCopy> = (0..copies)
+        .map(|k| {
+            // a loop unrolled fully is gone, so its last copy can keep the names
+            let last = trips.is_some() && k + 1 == copies;
+            let fresh = |name: &String, used: &mut HashSet<String>| {
+                if last {
+                    name.clone()
+                } else {
+                    fresh_name(&format!("{name}.{k}"), used)
+                }
+            };
+            let names = renamed
+                .iter()
+                .map(|var| (*var).clone())
+                .chain(counted.gets.iter().map(|(get, ..)| get.clone()))
+                .map(|var| {
+                    let name = fresh(&var, vars);
+                    (var, name)
+                })
+                .collect();
+            let block_labels = natural_loop
+                .body
+                .iter()
+                .map(|&b| {
+                    (
+                        b,
+                        fresh_name(&format!("{}.{k}", get_label(&blocks, b)), &mut labels),
+                    )
+                })
+                .collect();
+            IterationCopy {
+                names,
+                labels: block_labels,
+                gets: HashMap::new(),
+                sets: HashMap::new(),
+                next: String::new(),
+                exits: false,
+            }
+        })
+        .collect();
+    for k in 1..copies {
+        iterations[k - 1].next = iterations[k].labels[&header].clone();
+        for (get, op_type, source) in counted.gets.iter() {
+            let prev = rename(source, &iterations[k - 1].names);
+            let dest = iterations[k].names[get].clone();
+            // a renamed variable can just be the previous copy's value, unless the copy
+            // defines that again
+            if dest != *get && prev != rename(source, &iterations[k].names) {
+                iterations[k].names.insert(get.clone(), prev);
+            } else {
+                iterations[k].gets.insert(
+                    get.clone(),
+                    value(&dest, ValueOps::Id, &[&prev], op_type.clone()),
+                );
+            }
+        }
+    }
+
+    let mut new_blocks: Vec<Vec<Code>>;
+    let unrolled = if trips.is_some() {
+        // the first copy gets what was set for the header
+        let first = &mut iterations[0];
+        let mut shadows = HashMap::new();
+        for (get, op_type, _) in counted.gets.iter() {
+            let dest = first.names[get].clone();
+            first.gets.insert(
+                get.clone(),
+                value(&dest, ValueOps::Get, &[], op_type.clone()),
+            );
+            shadows.insert(get.clone(), dest);
+        }
+        iterations.last_mut().expect("there should be a copy").exits = true;
+        retarget_sets(&mut blocks, &natural_loop, &shadows);
+        new_blocks = blocks[..=preheader].to_vec();
+
+        for copy in iterations.iter() {
+            new_blocks.extend(copy_iteration(
+                &blocks,
+                &succ,
+                &natural_loop,
+                &counted,
+                copy,
+            ));
+        }
+        let end = new_blocks.len();
+        new_blocks.extend(
+            blocks[header..]
+                .iter()
+                .enumerate()
+                .filter(|(b, _)| !natural_loop.contains(header + b))
+                .map(|(_, block)| block.clone()),
+        );
+        drop_jumps_to_next(&mut new_blocks, preheader + 1, end);
+        Unrolled::Fully
+    } else {
+        let span = counted.step.checked_mul(config.factor as i64 - 1)?;
+        let header_label = get_label(&blocks, header);
+        let guard_label = fresh_name(&format!("{header_label}.unrolled"), &mut labels);
+
+        // the new loop gets what was set for the header, and passes it on to the original when
+        // it doesn't go around again
+        let mut guard = vec![Code::Label {
+            label: guard_label.clone(),
+            pos: None,
+        }];
+        let mut remainder = vec![];
+        let mut shadows = HashMap::new();
+        for (get, op_type, _) in counted.gets.iter() {
+            let shadow = fresh_name(&format!("{get}.unrolled"), vars);
+            guard.push(value(&shadow, ValueOps::Get, &[], op_type.clone()));
+            remainder.push(effect(EffectOps::Set, &[get, &shadow], &[]));
+            shadows.insert(get.clone(), shadow);
+        }
+        retarget_sets(&mut blocks, &natural_loop, &shadows);
+        new_blocks = blocks[..=preheader].to_vec();
+
+        // the tested value only moves one way, so if nothing overflows, all the tests of the
+        // next iterations stay if the one nearest the bound does, i.e. if carried passes the
+        // bound moved back by how far that test is from it
+        let carried = rename(&counted.carried, &shadows);
+        let ends = [counted.offset, counted.offset.checked_add(span)?];
+        let (lo, hi) = (ends[0].min(ends[1]), ends[0].max(ends[1]));
+        let below = matches!(counted.op, ValueOps::Lt | ValueOps::Le);
+        let (nearest, furthest) = if below { (hi, lo) } else { (lo, hi) };
+        let stays = fresh_name("unroll.stays", vars);
+        let mut conds = vec![stays.clone()];
+        let moved = if nearest == 0 {
+            counted.bound.clone()
+        } else {
+            // worked out before the loop, where the guard can't go if it overflows
+            let distance = fresh_name("unroll.distance", vars);
+            let moved = fresh_name("unroll.bound", vars);
+            let edge = fresh_name("unroll.edge", vars);
+            let fits = fresh_name("unroll.bound.fits", vars);
+            let (edge_value, fits_op) = if nearest > 0 {
+                (i64::MIN + nearest, ValueOps::Ge)
+            } else {
+                (i64::MAX + nearest, ValueOps::Le)
+            };
+            new_blocks[preheader].extend([
+                constant(&distance, nearest),
+                value(
+                    &moved,
+                    ValueOps::Sub,
+                    &[&counted.bound, &distance],
+                    Type::Int,
+                ),
+                constant(&edge, edge_value),
+                value(&fits, fits_op, &[&counted.bound, &edge], Type::Bool),
+            ]);
+            conds.push(fits);
+            moved
+        };
+        guard.push(value(&stays, counted.op, &[&carried, &moved], Type::Bool));
+        // passing the test nearest the bound keeps it from overflowing, the one furthest away is
+        // checked when it moves away from the bound
+        if (below && furthest < 0) || (!below && furthest > 0) {
+            let limit = fresh_name("unroll.limit", vars);
+            let fits = fresh_name("unroll.fits", vars);
+            let (limit_value, fits_op) = if furthest < 0 {
+                (i64::MIN - furthest, ValueOps::Ge)
+            } else {
+                (i64::MAX - furthest, ValueOps::Le)
+            };
+            new_blocks[preheader].push(constant(&limit, limit_value));
+            guard.push(value(&fits, fits_op, &[&carried, &limit], Type::Bool));
+            conds.push(fits);
+        }
+        let go = conds
+            .into_iter()
+            .reduce(|a, b| {
+                let go = fresh_name("unroll.go", vars);
+                guard.push(value(&go, ValueOps::And, &[&a, &b], Type::Bool));
+                go
+            })
+            .expect("the guard should test something");
+        let remainder_label = if remainder.is_empty() {
+            header_label
+        } else {
+            fresh_name(&format!("{header_label}.remainder"), &mut labels)
+        };
+        guard.push(effect(
+            EffectOps::Branch,
+            &[&go],
+            &[&iterations[0].labels[&header], &remainder_label],
+        ));
+
+        // the first copy reads the new loop's values where they're gotten
+        let first = &mut iterations[0];
+        for (get, ..) in counted.gets.iter() {
+            first.names.insert(get.clone(), shadows[get].clone());
+        }
+        let last = iterations.last_mut().expect("there should be a copy");
+        last.next = guard_label.clone();
+        last.sets = shadows;
+
+        new_blocks.push(guard);
+        let start = new_blocks.len();
+        for copy in iterations.iter() {
+            new_blocks.extend(copy_iteration(
+                &blocks,
+                &succ,
+                &natural_loop,
+                &counted,
+                copy,
+            ));
+        }
+        let end = new_blocks.len();
+        drop_jumps_to_next(&mut new_blocks, start, end);
+        if !remainder.is_empty() {
+            remainder.insert(
+                0,
+                Code::Label {
+                    label: remainder_label,
+                    pos: None,
+                },
+            );
+            new_blocks.push(remainder);
+        }
+        new_blocks.extend(blocks[header..].iter().cloned());
+        Unrolled::Partially(guard_label)
+    };
+
+    Some((new_blocks, unrolled))
+}
+
+// unrolls innermost loops, one at a time
+pub fn unroll_loops(function: &mut Function, config: &UnrollConfig) -> UnrollStats {
+    let mut stats = UnrollStats::default();
+    let mut vars = get_vars(function);
+    // loops are found again after every change, and known by their header's label
+    let mut visited: HashSet<String> = HashSet::new();
+
+    loop {
+        let blocks = get_basic_blocks(function);
+        let succ = form_cfg(&blocks);
+        let pred = rev_graph(&succ);
+        let loops = find_natural_loops(&pred, &succ);
+
+        let Some(natural_loop) = loops.iter().find(|natural_loop| {
+            let innermost = loops.iter().all(|other| {
+                other.header == natural_loop.header || !natural_loop.contains(other.header)
+            });
+            innermost && !visited.contains(&get_label(&blocks, natural_loop.header))
+        }) else {
+            break;
+        };
+        visited.insert(get_label(&blocks, natural_loop.header));
+
+        if let Some((new_blocks, unrolled)) =
+            unroll_loop(function, &blocks, &pred, natural_loop, config, &mut vars)
+        {
+            function.instrs = new_blocks.into_iter().flatten().collect();
+            match unrolled {
+                Unrolled::Fully => stats.fully += 1,
+                Unrolled::Partially(label) => {
+                    visited.insert(label);
+                    stats.partially += 1;
+                }
+            }
+        }
+    }
+
+    stats
+}
@@ -0,0 +1,66 @@
+use std::{fs, path::Path, process::Command};
+
+use interp::parse::parse_program;
+use task6::c_backend::emit_c;
+
+// what the compiled program prints, or why it couldn't be built
+fn compile_and_run(path: &Path) -> Result<String, String> {
+    let text = fs::read_to_string(path).expect("test program should be readable");
+    let args: Vec<&str> = text
+        .lines()
+        .find_map(|line| line.strip_prefix("# ARGS:"))
+        .map(|args| args.split_whitespace().collect())
+        .unwrap_or_default();
+    let program = parse_program(&text).expect("test program should parse");
+
+    let stem = path.file_stem().expect("test program should have a name");
+    let base = Path::new(env!("CARGO_TARGET_TMPDIR")).join(stem);
+    let source = base.with_extension("c");
+    let binary = base.with_extension("exe");
+    fs::write(&source, emit_c(&program)).expect("C source should be writable");
+
+    let cc = Command::new("cc")
+        .args(["-Wall", "-Werror", "-o"])
+        .arg(&binary)
+        .arg(&source)
+        .arg("-lm")
+        .output()
+        .expect("cc should run");
+    if !cc.status.success() {
+        return Err(format!(
+            "cc failed:\n{}",
+            String::from_utf8_lossy(&cc.stderr)
+        ));
+    }
+
+    let run = Command::new(&binary)
+        .args(args)
+        .output()
+        .expect("compiled program should run");
+    Ok(String::from_utf8_lossy(&run.stdout).into_owned())
+}
+
+#[test]
+fn compiled_programs_match_snapshots() {
+    let mut failures = vec![];
+    let mut count = 0;
+    for entry in fs::read_dir("test/bril2c").expect("test/bril2c should exist") {
+        let path = entry.expect("test entry should be readable").path();
+        if path.extension().is_none_or(|ext| ext != "bril") {
+            continue;
+        }
+        count += 1;
+        let expected = fs::read_to_string(path.with_extension("out"))
+            .expect("every test program should have a snapshot");
+        match compile_and_run(&path) {
+            Ok(actual) if actual == expected => {}
+            Ok(actual) => failures.push(format!(
+                "{}:\nexpected:\n{expected}actual:\n{actual}",
+                path.display()
+            )),
+            Err(error) => failures.push(format!("{}: {error}", path.display())),
+        }
+    }
+    assert!(count > 0, "test/bril2c should have programs");
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
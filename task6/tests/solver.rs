@@ -0,0 +1,53 @@
+use std::{fs, path::Path};
+
+use bril_rs::Program;
+use interp::parse::parse_program;
+use task6::{
+    cfg::{form_cfg, get_basic_blocks},
+    df::{DataFlowAnalysis, ReachingDefs, arg_defs, reaching_defs},
+    dom::rev_graph,
+};
+
+// every Bril program in the repository's test directories
+fn programs() -> Vec<(String, Program)> {
+    fn visit(dir: &Path, programs: &mut Vec<(String, Program)>) {
+        for entry in fs::read_dir(dir).expect("directory should be readable") {
+            let path = entry.expect("entry should be readable").path();
+            if path.is_dir() {
+                if path.file_name().is_some_and(|name| name != "target") {
+                    visit(&path, programs);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "bril") {
+                let text = fs::read_to_string(&path).expect("program should be readable");
+                let program = parse_program(&text).expect("program should parse");
+                programs.push((path.display().to_string(), program));
+            }
+        }
+    }
+
+    let mut programs = vec![];
+    visit(Path::new(".."), &mut programs);
+    assert!(
+        !programs.is_empty(),
+        "the repository should have test programs"
+    );
+    programs
+}
+
+#[test]
+fn bit_vectors_match_generic_solver() {
+    for (path, program) in programs() {
+        for function in program.functions.iter() {
+            let blocks = get_basic_blocks(function);
+            let succ = form_cfg(&blocks);
+            let pred = rev_graph(&succ);
+            let (generic, _) =
+                ReachingDefs::find_with_boundary(&blocks, &pred, &succ, arg_defs(&function.args));
+            assert!(
+                reaching_defs(&function.args, &blocks, &pred, &succ).0 == generic,
+                "reaching definitions differ in {path} @{}",
+                function.name
+            );
+        }
+    }
+}
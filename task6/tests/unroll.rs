@@ -0,0 +1,46 @@
+use std::fs;
+
+use bril_rs::Program;
+use interp::{interp::run_program, parse::parse_program};
+use task6::unroll::{UnrollConfig, unroll_loops};
+
+// what the program prints and how many instructions it runs
+fn run(program: &Program, args: &[&str]) -> (String, u64) {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let mut out = vec![];
+    let profile = run_program(program, &args, &mut out).expect("program should run");
+    let out = String::from_utf8(out).expect("output should be text");
+    (out, profile.total_dyn_inst)
+}
+
+// unrolled loops print the same and run fewer instructions, once they go around a few times
+#[test]
+fn unrolling_runs_fewer_instructions() {
+    // program in test/unroll, factor, size limit, and arguments
+    let cases: &[(&str, usize, usize, &[&str])] = &[
+        ("full", 4, 64, &[]),
+        ("ssa-full", 4, 64, &[]),
+        ("partial", 3, 64, &["10"]),
+        ("bottom", 4, 64, &["99"]),
+        ("ssa", 2, 64, &["9"]),
+        ("limit", 2, 8, &[]),
+    ];
+    for &(name, factor, size_limit, args) in cases {
+        let text = fs::read_to_string(format!("test/unroll/{name}.bril"))
+            .expect("test program should be readable");
+        let program = parse_program(&text).expect("test program should parse");
+        let mut unrolled = program.clone();
+        let config = UnrollConfig { factor, size_limit };
+        for function in unrolled.functions.iter_mut() {
+            unroll_loops(function, &config);
+        }
+
+        let (expected, before) = run(&program, args);
+        let (actual, after) = run(&unrolled, args);
+        assert_eq!(actual, expected, "{name} should print the same");
+        assert!(
+            after < before,
+            "{name} should run fewer instructions, but went from {before} to {after}"
+        );
+    }
+}